@@ -0,0 +1,332 @@
+//! Support for the Apple Partition Map, as found on old Apple disks and
+//! hybrid CD images.
+//!
+//! Every field of the APM is big endian. Block zero holds the driver
+//! descriptor map, starting with "ER", and each following block holds one
+//! partition map entry, starting with "PM".
+
+use embedded_io::{
+    blocking::{Read, Seek},
+    Io, SeekFrom,
+};
+
+use crate::{error::MbrError, Partition, BLOCK_SIZE};
+
+/// Signature of the driver descriptor map, "ER"
+pub const DDM_SIGNATURE: u16 = 0x4552;
+/// Signature of each partition map entry, "PM"
+pub const ENTRY_SIGNATURE: u16 = 0x504d;
+/// Length of the name and type strings of an entry
+pub const APM_STRING_LEN: usize = 32;
+
+/// Offset of the signature in the DDM and in each entry
+const SIGNATURE_OFFSET: usize = 0;
+/// Offset of the block size in the DDM
+const BLOCK_SIZE_OFFSET: usize = 2;
+/// Offset of the number of entries in the map, stored in every entry
+const MAP_BLOCK_COUNT_OFFSET: usize = 4;
+/// Offset of the first block of the partition
+const START_BLOCK_OFFSET: usize = 8;
+/// Offset of the number of blocks in the partition
+const BLOCK_COUNT_OFFSET: usize = 12;
+/// Offset of the partition name
+const NAME_OFFSET: usize = 16;
+/// Offset of the partition type
+const TYPE_OFFSET: usize = 48;
+/// Bytes of an entry that are read, up to the end of the type
+const ENTRY_READ_LEN: usize = TYPE_OFFSET + APM_STRING_LEN;
+
+#[inline]
+/// Read a big endian u16 from a slice at the given offset
+fn read_u16_be(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+#[inline]
+/// Read a big endian u32 from a slice at the given offset
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline]
+/// Get a null terminated string from a fixed length field
+fn trim_nul(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    &bytes[..len]
+}
+
+/// Used to store data about partitions in the APM
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ApmEntry {
+    block_size: u16,
+    start_block: u32,
+    block_count: u32,
+    name: [u8; APM_STRING_LEN],
+    partition_type: [u8; APM_STRING_LEN],
+}
+
+impl ApmEntry {
+    /// Create an entry from bytes, returning None without the signature
+    fn from_bytes(bytes: &[u8; ENTRY_READ_LEN], block_size: u16) -> Option<Self> {
+        if read_u16_be(bytes, SIGNATURE_OFFSET) != ENTRY_SIGNATURE {
+            return None;
+        }
+
+        Some(Self {
+            block_size,
+            start_block: read_u32_be(bytes, START_BLOCK_OFFSET),
+            block_count: read_u32_be(bytes, BLOCK_COUNT_OFFSET),
+            name: bytes[NAME_OFFSET..NAME_OFFSET + APM_STRING_LEN]
+                .try_into()
+                .unwrap(),
+            partition_type: bytes[TYPE_OFFSET..TYPE_OFFSET + APM_STRING_LEN]
+                .try_into()
+                .unwrap(),
+        })
+    }
+
+    #[inline]
+    /// Get the first block of the partition
+    pub fn get_start_block(&self) -> u32 {
+        self.start_block
+    }
+
+    #[inline]
+    /// Get the number of blocks in the partition
+    pub fn get_block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    #[inline]
+    /// Get the name of the partition, without the trailing nulls
+    pub fn get_name(&self) -> &[u8] {
+        trim_nul(&self.name)
+    }
+
+    #[inline]
+    /// Get the type of the partition, eg. `Apple_HFS`, without the
+    /// trailing nulls
+    pub fn get_partition_type(&self) -> &[u8] {
+        trim_nul(&self.partition_type)
+    }
+
+    #[inline]
+    /// Check if the partition is marked as free space
+    pub fn is_free(&self) -> bool {
+        self.get_partition_type() == b"Apple_Free"
+    }
+
+    #[inline]
+    /// Get the starting position of the partition
+    pub fn get_start_pos(&self) -> u64 {
+        (self.start_block as u64) * (self.block_size as u64)
+    }
+
+    #[inline]
+    /// Get the end position of the partition
+    pub fn get_end_pos(&self) -> u64 {
+        self.get_start_pos() + (self.block_count as u64) * (self.block_size as u64)
+    }
+}
+
+/// Used to grab partitions from the APM
+pub struct APM<IO: Read + Seek> {
+    block_size: u16,
+    entry_count: u32,
+    io: IO,
+}
+
+impl<IO: Read + Seek> APM<IO> {
+    /// Create a new APM from anything that implements embedded_io
+    pub fn new(mut io: IO) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        let mut ddm: [u8; 4] = [0; 4];
+
+        io.seek(SeekFrom::Start(0))?;
+        io.read_exact(&mut ddm)?;
+
+        if read_u16_be(&ddm, SIGNATURE_OFFSET) != DDM_SIGNATURE {
+            return Err(MbrError::MissingApmSignature);
+        }
+
+        // Some images leave the block size zeroed, assume 512 bytes then
+        let block_size = match read_u16_be(&ddm, BLOCK_SIZE_OFFSET) {
+            0 => BLOCK_SIZE as u16,
+            block_size => block_size,
+        };
+
+        if (block_size as usize) < ENTRY_READ_LEN {
+            return Err(MbrError::InvalidApmEntry(0));
+        }
+
+        let mut apm = Self {
+            block_size,
+            entry_count: 1,
+            io,
+        };
+        let mut entry: [u8; ENTRY_READ_LEN] = [0; ENTRY_READ_LEN];
+
+        // The first entry holds the size of the whole map
+        apm.read_entry(0, &mut entry)?;
+
+        if read_u16_be(&entry, SIGNATURE_OFFSET) != ENTRY_SIGNATURE {
+            return Err(MbrError::InvalidApmEntry(0));
+        }
+
+        apm.entry_count = read_u32_be(&entry, MAP_BLOCK_COUNT_OFFSET);
+
+        Ok(apm)
+    }
+
+    /// Read the raw bytes of an entry
+    fn read_entry(
+        &mut self,
+        index: u32,
+        entry: &mut [u8; ENTRY_READ_LEN],
+    ) -> Result<(), MbrError<IO::Error>> {
+        let pos = (index as u64 + 1) * (self.block_size as u64);
+
+        self.io.seek(SeekFrom::Start(pos))?;
+        self.io.read_exact(entry)?;
+
+        Ok(())
+    }
+
+    #[inline]
+    /// Get the block size given by the driver descriptor map
+    pub fn get_block_size(&self) -> u16 {
+        self.block_size
+    }
+
+    #[inline]
+    /// Get the number of entries in the partition map, including the map
+    /// itself
+    pub fn partition_count(&self) -> u32 {
+        self.entry_count
+    }
+
+    /// Read a partition map entry from the disk
+    pub fn get_entry(&mut self, index: u32) -> Result<ApmEntry, MbrError<IO::Error>> {
+        if index >= self.entry_count {
+            return Err(MbrError::InvalidPartitionIndex(index));
+        }
+
+        let mut entry: [u8; ENTRY_READ_LEN] = [0; ENTRY_READ_LEN];
+
+        self.read_entry(index, &mut entry)?;
+
+        ApmEntry::from_bytes(&entry, self.block_size).ok_or(MbrError::InvalidApmEntry(index))
+    }
+
+    /// Find the first entry with the given type, eg. `Apple_HFS`
+    pub fn find_by_type(
+        &mut self,
+        partition_type: &[u8],
+    ) -> Result<Option<(u32, ApmEntry)>, MbrError<IO::Error>> {
+        for index in 0..self.entry_count {
+            let entry = self.get_entry(index)?;
+
+            if entry.get_partition_type() == partition_type {
+                return Ok(Some((index, entry)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Get a partition from the APM
+    pub fn get_partition(&mut self, index: u32) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let entry = self.get_entry(index)?;
+
+        if entry.block_count == 0 {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        Partition::new(entry.get_start_pos(), entry.get_end_pos(), &mut self.io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Read};
+
+    use crate::{apm::*, BLOCK_SIZE};
+
+    /// Write an entry into the block at the given index
+    fn write_entry(img: &mut [u8], index: usize, entry: (u32, u32, &[u8], &[u8])) {
+        let block = &mut img[(index + 1) * BLOCK_SIZE as usize..];
+        let (start_block, block_count, name, partition_type) = entry;
+
+        block[SIGNATURE_OFFSET..SIGNATURE_OFFSET + 2]
+            .copy_from_slice(&ENTRY_SIGNATURE.to_be_bytes());
+        block[MAP_BLOCK_COUNT_OFFSET..MAP_BLOCK_COUNT_OFFSET + 4]
+            .copy_from_slice(&3u32.to_be_bytes());
+        block[START_BLOCK_OFFSET..START_BLOCK_OFFSET + 4]
+            .copy_from_slice(&start_block.to_be_bytes());
+        block[BLOCK_COUNT_OFFSET..BLOCK_COUNT_OFFSET + 4]
+            .copy_from_slice(&block_count.to_be_bytes());
+        block[NAME_OFFSET..NAME_OFFSET + name.len()].copy_from_slice(name);
+        block[TYPE_OFFSET..TYPE_OFFSET + partition_type.len()].copy_from_slice(partition_type);
+    }
+
+    /// Build a 32 block image with the map itself, an HFS partition at
+    /// block 8 and some free space
+    fn test_img() -> Vec<u8> {
+        let mut img = vec![0; 32 * BLOCK_SIZE as usize];
+
+        img[SIGNATURE_OFFSET..SIGNATURE_OFFSET + 2].copy_from_slice(&DDM_SIGNATURE.to_be_bytes());
+        img[BLOCK_SIZE_OFFSET..BLOCK_SIZE_OFFSET + 2]
+            .copy_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+
+        write_entry(&mut img, 0, (1, 3, b"Apple", b"Apple_partition_map"));
+        write_entry(&mut img, 1, (8, 16, b"Macintosh HD", b"Apple_HFS"));
+        write_entry(&mut img, 2, (24, 8, b"Extra", b"Apple_Free"));
+
+        img[8 * BLOCK_SIZE as usize] = 4;
+
+        img
+    }
+
+    #[test]
+    /// Ensure that APM entries and partitions can be read
+    fn test_apm() {
+        let mut apm = APM::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(apm.partition_count(), 3);
+
+        let entry = apm.get_entry(1).unwrap();
+
+        assert_eq!(entry.get_name(), b"Macintosh HD");
+        assert_eq!(entry.get_partition_type(), b"Apple_HFS");
+        assert!(apm.get_entry(2).unwrap().is_free());
+        assert_eq!(apm.find_by_type(b"Apple_HFS").unwrap(), Some((1, entry)));
+
+        let mut partition = apm.get_partition(1).unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        assert_eq!(partition.len(), 16 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 4);
+
+        assert!(matches!(
+            apm.get_entry(3),
+            Err(MbrError::InvalidPartitionIndex(3))
+        ));
+        assert!(matches!(
+            APM::new(FromStd::new(Cursor::new(vec![0; 4096]))),
+            Err(MbrError::MissingApmSignature)
+        ));
+
+        // The largest possible entry doesn't overflow
+        let mut img = test_img();
+
+        write_entry(&mut img, 2, (u32::MAX, u32::MAX, b"Big", b"Apple_HFS"));
+
+        let mut apm = APM::new(FromStd::new(Cursor::new(img))).unwrap();
+        let entry = apm.get_entry(2).unwrap();
+
+        assert_eq!(entry.get_end_pos(), 2 * u32::MAX as u64 * BLOCK_SIZE);
+    }
+}
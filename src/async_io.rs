@@ -0,0 +1,356 @@
+//! Async partitions over the futures-io traits.
+//!
+//! [`AsyncPartition`] keeps a [`Partition`](crate::Partition)'s bounds and
+//! seek policy over a device implementing `futures_io::{AsyncRead,
+//! AsyncWrite, AsyncSeek}`, and [`read_partition_table`] reads the MBR of
+//! such a device.
+
+use core::{
+    cmp,
+    future::poll_fn,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use std::io;
+
+use embedded_io::SeekFrom;
+use futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+use crate::{
+    error::{MbrError, PartitionError},
+    parse_records, PartitionRecord, SeekPolicy, RECORDS_START, RECORD_COUNT, TABLE_LEN,
+};
+
+/// Read the partition records of an async device, checking the signature
+/// like [`MBR::new`](crate::MBR::new)
+pub async fn read_partition_table<IO: AsyncRead + AsyncSeek + Unpin>(
+    io: &mut IO,
+) -> Result<[PartitionRecord; RECORD_COUNT], MbrError<io::Error>> {
+    let mut buffer: [u8; TABLE_LEN] = [0; TABLE_LEN];
+    let mut filled = 0;
+
+    poll_fn(|cx| Pin::new(&mut *io).poll_seek(cx, io::SeekFrom::Start(RECORDS_START))).await?;
+
+    // The device may return short reads
+    while filled < TABLE_LEN {
+        match poll_fn(|cx| Pin::new(&mut *io).poll_read(cx, &mut buffer[filled..])).await? {
+            0 => return Err(MbrError::UnexpectedEof),
+            read => filled += read,
+        }
+    }
+
+    parse_records(&buffer, true)
+}
+
+/// Used to interface with partitions of an async device
+pub struct AsyncPartition<IO> {
+    start_pos: u64,
+    end_pos: u64,
+    pos: u64,
+    seek_policy: SeekPolicy,
+    /// Whether the device is known to be at the cursor
+    synced: bool,
+    io: IO,
+}
+
+impl<IO> AsyncPartition<IO> {
+    /// Create a new partition given the start and end position
+    ///
+    /// Fails with [`MbrError::InvalidPartitionBounds`] if the partition ends
+    /// before it starts. The device isn't touched until the partition is
+    /// first used
+    pub fn new(start_pos: u64, end_pos: u64, io: IO) -> Result<Self, MbrError<io::Error>> {
+        if end_pos < start_pos {
+            return Err(MbrError::InvalidPartitionBounds);
+        }
+
+        Ok(Self {
+            start_pos,
+            end_pos,
+            pos: 0,
+            seek_policy: SeekPolicy::default(),
+            synced: false,
+            io,
+        })
+    }
+
+    /// Create a new partition from one of the records of
+    /// [`read_partition_table`]
+    ///
+    /// Fails the same way as [`MBR::get_partition`](crate::MBR::get_partition)
+    pub fn from_record(record: &PartitionRecord, io: IO) -> Result<Self, MbrError<io::Error>> {
+        record.check_primary(true)?;
+
+        Self::new(record.get_start_pos(), record.get_end_pos(), io)
+    }
+
+    #[inline]
+    /// Get the length of the partition in bytes
+    pub fn len(&self) -> u64 {
+        self.end_pos - self.start_pos
+    }
+
+    #[inline]
+    /// Check if the partition has a length of zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    /// Get the position of the cursor, relative to the start of the
+    /// partition
+    pub fn get_position(&self) -> u64 {
+        self.pos
+    }
+
+    #[inline]
+    /// Set what the partition does when seeking past its end
+    pub fn set_seek_policy(&mut self, seek_policy: SeekPolicy) {
+        self.seek_policy = seek_policy;
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+}
+
+impl<IO: AsyncSeek + Unpin> AsyncPartition<IO> {
+    /// Move the device to the cursor, unless it is known to be there
+    fn poll_sync(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.synced {
+            let target = self.start_pos + cmp::min(self.pos, self.len());
+
+            ready!(Pin::new(&mut self.io).poll_seek(cx, io::SeekFrom::Start(target)))?;
+
+            self.synced = true;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Keep track of the cursor after the device was used
+    fn advance(&mut self, result: io::Result<usize>) -> io::Result<usize> {
+        match result {
+            Ok(len) => self.pos += len as u64,
+            // Who knows where the device ended up
+            Err(_) => self.synced = false,
+        }
+
+        result
+    }
+}
+
+impl<IO: AsyncRead + AsyncSeek + Unpin> AsyncRead for AsyncPartition<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_sync(cx))?;
+
+        // Limit the amount of data available to read to the size of the partition
+        let available = this.len().saturating_sub(this.pos);
+        let len = cmp::min(buf.len() as u64, available) as usize;
+
+        let result = ready!(Pin::new(&mut this.io).poll_read(cx, &mut buf[..len]));
+
+        Poll::Ready(this.advance(result))
+    }
+}
+
+impl<IO: AsyncWrite + AsyncSeek + Unpin> AsyncWrite for AsyncPartition<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let available = this.len().saturating_sub(this.pos);
+
+        if available == 0 && !buf.is_empty() {
+            return Poll::Ready(Err(PartitionError::<io::Error>::OutOfSpace.into()));
+        }
+
+        ready!(this.poll_sync(cx))?;
+
+        let len = cmp::min(buf.len() as u64, available) as usize;
+
+        let result = ready!(Pin::new(&mut this.io).poll_write(cx, &buf[..len]));
+
+        Poll::Ready(this.advance(result))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_close(cx)
+    }
+}
+
+impl<IO: AsyncSeek + Unpin> AsyncSeek for AsyncPartition<IO> {
+    /// Only moves the cursor, the device follows on the next read or write
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        let seek = match pos {
+            io::SeekFrom::Start(pos) => SeekFrom::Start(pos),
+            io::SeekFrom::Current(pos) => SeekFrom::Current(pos),
+            io::SeekFrom::End(pos) => SeekFrom::End(pos),
+        };
+
+        let result = this
+            .seek_policy
+            .resolve::<io::Error>(seek, this.pos, this.len())
+            .map_err(io::Error::from);
+
+        if let Ok(pos) = result {
+            this.pos = pos;
+            this.synced = false;
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        io::{self, Cursor, Read, Seek, Write},
+        pin::{pin, Pin},
+        task::{Context, Poll, Waker},
+    };
+
+    use futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+    use crate::{async_io::*, PartitionId};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    /// Device that is only ready every other time it is polled
+    struct SlowCursor {
+        inner: Cursor<Vec<u8>>,
+        ready: bool,
+    }
+
+    impl SlowCursor {
+        fn poll_with<T>(
+            &mut self,
+            cx: &mut Context<'_>,
+            f: impl FnOnce(&mut Cursor<Vec<u8>>) -> T,
+        ) -> Poll<T> {
+            self.ready = !self.ready;
+
+            match self.ready {
+                true => Poll::Ready(f(&mut self.inner)),
+                false => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl AsyncRead for SlowCursor {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_with(cx, |inner| inner.read(buf))
+        }
+    }
+
+    impl AsyncWrite for SlowCursor {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_with(cx, |inner| inner.write(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncSeek for SlowCursor {
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            pos: io::SeekFrom,
+        ) -> Poll<io::Result<u64>> {
+            self.get_mut().poll_with(cx, |inner| inner.seek(pos))
+        }
+    }
+
+    /// Run a future to completion on the current thread
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    /// Ensure that async partitions keep to their bounds, even when the
+    /// device isn't always ready
+    fn test_async_partition() {
+        let mut io = SlowCursor {
+            inner: Cursor::new(TEST_IMG_1.to_vec()),
+            ready: false,
+        };
+        let records = block_on(read_partition_table(&mut io)).unwrap();
+        let record = records[PartitionId::One as usize];
+        let start = record.get_start_pos() as usize;
+        let mut partition = AsyncPartition::from_record(&record, io).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        let written = block_on(poll_fn(|cx| {
+            Pin::new(&mut partition).poll_write(cx, &[1, 2, 3, 4])
+        }))
+        .unwrap();
+
+        assert_eq!(written, 4);
+        assert_eq!(partition.get_position(), 4);
+
+        // Reads stop at the end of the partition
+        let end = io::SeekFrom::End(-2);
+
+        block_on(poll_fn(|cx| Pin::new(&mut partition).poll_seek(cx, end))).unwrap();
+
+        let read = block_on(poll_fn(|cx| {
+            Pin::new(&mut partition).poll_read(cx, &mut buf)
+        }))
+        .unwrap();
+
+        assert_eq!(read, 2);
+
+        let error =
+            block_on(poll_fn(|cx| Pin::new(&mut partition).poll_write(cx, &buf))).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+
+        let img = partition.into_inner().inner.into_inner();
+
+        assert_eq!(img[start..start + 4], [1, 2, 3, 4]);
+    }
+}
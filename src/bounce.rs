@@ -0,0 +1,318 @@
+//! Bounce buffering for drivers with strict DMA requirements.
+//!
+//! Many SD and eMMC drivers can only transfer whole sectors into word or
+//! cache-line aligned buffers, while file systems read and write arbitrary
+//! byte ranges from wherever their buffers happen to live. A
+//! [`BounceBuffer`] sits between the two, splitting every transfer into
+//! aligned, whole-sector transfers on the device.
+
+use core::{cmp, mem};
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io, SeekFrom,
+};
+
+use crate::BLOCK_SIZE;
+
+/// Data aligned to at least the alignment of `A`
+#[repr(C)]
+struct Aligned<A, T> {
+    _align: [A; 0],
+    data: T,
+}
+
+/// Wrapper that only ever hands aligned, whole-sector transfers to the
+/// device
+///
+/// The buffers given to the device are aligned to `A`, which defaults to a
+/// 32 bit word. For cache-line alignment, use a type with a matching
+/// `#[repr(align(N))]`. Buffers that are already aligned and cover whole
+/// sectors are passed straight through without copying.
+pub struct BounceBuffer<IO, A = u32> {
+    io: IO,
+    pos: u64,
+    buffer: Aligned<A, [u8; BLOCK_SIZE as usize]>,
+}
+
+impl<IO, A> BounceBuffer<IO, A> {
+    /// Wrap a device, starting at the beginning of it
+    pub fn new(io: IO) -> Self {
+        Self {
+            io,
+            pos: 0,
+            buffer: Aligned {
+                _align: [],
+                data: [0; BLOCK_SIZE as usize],
+            },
+        }
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device
+    ///
+    /// Moving the device's cursor doesn't affect the wrapper's position
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+
+    #[inline]
+    /// Get the offset into the current sector
+    fn sector_offset(&self) -> usize {
+        (self.pos % BLOCK_SIZE) as usize
+    }
+
+    /// Get how many bytes of `buf` can go straight to the device, which is
+    /// only possible when both the buffer and the position are aligned
+    fn direct_len(&self, buf: &[u8]) -> usize {
+        let aligned = buf.as_ptr().align_offset(mem::align_of::<A>()) == 0;
+
+        match aligned && self.sector_offset() == 0 {
+            true => buf.len() - buf.len() % BLOCK_SIZE as usize,
+            false => 0,
+        }
+    }
+}
+
+impl<IO: Read + Seek, A> BounceBuffer<IO, A> {
+    /// Read the sector holding the current position into the buffer,
+    /// returning how many bytes of it exist on the device
+    fn fill_buffer(&mut self) -> Result<usize, IO::Error> {
+        let sector_start = self.pos - self.sector_offset() as u64;
+        let mut filled = 0;
+
+        self.io.seek(SeekFrom::Start(sector_start))?;
+
+        while filled < self.buffer.data.len() {
+            match self.io.read(&mut self.buffer.data[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+impl<IO: Io, A> Io for BounceBuffer<IO, A> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Seek, A> Read for BounceBuffer<IO, A> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let direct_len = self.direct_len(buf);
+
+        if direct_len > 0 {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+
+            let read = self.io.read(&mut buf[..direct_len])?;
+
+            self.pos += read as u64;
+
+            return Ok(read);
+        }
+
+        let offset = self.sector_offset();
+        let filled = self.fill_buffer()?;
+
+        if filled <= offset {
+            return Ok(0);
+        }
+
+        let len = cmp::min(buf.len(), filled - offset);
+
+        buf[..len].copy_from_slice(&self.buffer.data[offset..offset + len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<IO: Read + Write + Seek, A> Write for BounceBuffer<IO, A> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let direct_len = self.direct_len(buf);
+
+        if direct_len > 0 {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+
+            let written = self.io.write(&buf[..direct_len])?;
+
+            self.pos += written as u64;
+
+            return Ok(written);
+        }
+
+        let offset = self.sector_offset();
+        let len = cmp::min(buf.len(), BLOCK_SIZE as usize - offset);
+
+        // Only partially overwritten sectors need to be read first
+        if len < BLOCK_SIZE as usize {
+            let filled = self.fill_buffer()?;
+
+            // A short sector at the end of the device is padded with zeros,
+            // rather than whatever the buffer last held
+            self.buffer.data[filled..].fill(0);
+        }
+
+        self.buffer.data[offset..offset + len].copy_from_slice(&buf[..len]);
+        self.io.seek(SeekFrom::Start(self.pos - offset as u64))?;
+        self.io.write_all(&self.buffer.data)?;
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.io.flush()
+    }
+}
+
+impl<IO: Seek, A> Seek for BounceBuffer<IO, A> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        // Let the device decide what is a valid position
+        if let SeekFrom::Current(_) = pos {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+        }
+
+        self.pos = self.io.seek(pos)?;
+
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::adapters::FromStd;
+
+    use crate::{bounce::*, PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    /// Device that panics on anything but aligned, whole-sector transfers
+    struct StrictDevice {
+        inner: FromStd<Cursor<Vec<u8>>>,
+        pos: u64,
+    }
+
+    impl StrictDevice {
+        fn check(&self, buf: &[u8]) {
+            assert_eq!(buf.as_ptr() as usize % mem::align_of::<u64>(), 0);
+            assert_eq!(buf.len() as u64 % BLOCK_SIZE, 0);
+            assert_eq!(self.pos % BLOCK_SIZE, 0);
+        }
+    }
+
+    impl Io for StrictDevice {
+        type Error = std::io::Error;
+    }
+
+    impl Read for StrictDevice {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.check(buf);
+            let read = self.inner.read(buf)?;
+            self.pos += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl Write for StrictDevice {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.check(buf);
+            let written = self.inner.write(buf)?;
+            self.pos += written as u64;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for StrictDevice {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.pos = self.inner.seek(pos)?;
+            Ok(self.pos)
+        }
+    }
+
+    #[test]
+    /// Ensure that unaligned transfers reach the device as aligned,
+    /// whole-sector transfers
+    fn test_bounce_buffer() {
+        let device = StrictDevice {
+            inner: FromStd::new(Cursor::new(TEST_IMG_1.to_vec())),
+            pos: 0,
+        };
+        let mut mbr = MBR::new(BounceBuffer::<_, u64>::new(device)).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+
+        // A buffer that is guaranteed to be misaligned
+        let mut buffer: Aligned<u64, [u8; 1024]> = Aligned {
+            _align: [],
+            data: [0; 1024],
+        };
+        let message = [0x5a; 700];
+
+        partition.seek(SeekFrom::Start(3)).unwrap();
+        partition.write_all(&message).unwrap();
+        partition.seek(SeekFrom::Start(3)).unwrap();
+        partition
+            .read_exact(&mut buffer.data[1..1 + message.len()])
+            .unwrap();
+
+        assert_eq!(&buffer.data[1..1 + message.len()], &message[..]);
+
+        // Aligned whole sectors are passed straight through
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.read_exact(&mut buffer.data).unwrap();
+
+        assert_eq!(&buffer.data[3..3 + message.len()], &message[..]);
+    }
+    #[test]
+    /// Ensure that a short sector at the end of the device is written as a
+    /// whole sector padded with zeros
+    fn test_bounce_buffer_short_sector() {
+        let mut img = vec![0xff; BLOCK_SIZE as usize];
+
+        img.extend_from_slice(&[0x11; 100]);
+
+        let mut bounce = BounceBuffer::<_, u32>::new(FromStd::new(Cursor::new(img)));
+
+        // Leave the first sector in the buffer
+        bounce.seek(SeekFrom::Start(3)).unwrap();
+        bounce.write_all(&[1; 2]).unwrap();
+        bounce.seek(SeekFrom::Start(BLOCK_SIZE + 3)).unwrap();
+        bounce.write_all(&[2; 2]).unwrap();
+
+        let img = bounce.into_inner().into_inner().into_inner();
+        let sector = &img[BLOCK_SIZE as usize..];
+
+        assert_eq!(img.len(), 2 * BLOCK_SIZE as usize);
+        assert_eq!(sector[..3], [0x11; 3]);
+        assert_eq!(sector[3..5], [2; 2]);
+        assert!(sector[5..100].iter().all(|b| *b == 0x11));
+        assert!(sector[100..].iter().all(|b| *b == 0));
+    }
+}
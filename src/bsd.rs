@@ -0,0 +1,438 @@
+//! Support for BSD disklabels inside of FreeBSD, OpenBSD and NetBSD slices.
+//!
+//! A BSD slice is a single MBR partition holding a disklabel in its second
+//! sector, which splits the slice into lettered sub-partitions. Parsing is
+//! opt-in through [`MBR::get_disklabel`] and [`MBR::get_bsd_partition`].
+
+use core::cmp;
+
+use embedded_io::{
+    blocking::{Read, Seek},
+    SeekFrom,
+};
+
+use crate::{
+    error::MbrError, gpt::read_u32, lba_to_u64, types::PartitionType, Partition, PartitionId,
+    PartitionRecord, BLOCK_SIZE, MBR,
+};
+
+/// Magic number found at the start and end of a disklabel header
+pub const DISKLABEL_MAGIC: u32 = 0x82564557;
+/// Sector of the slice holding the disklabel
+pub const LABEL_SECTOR: u32 = 1;
+/// Length of each sub-partition entry in bytes
+pub const BSD_PARTITION_LEN: usize = 16;
+/// Most sub-partitions that fit in the label sector
+pub const MAX_BSD_PARTITIONS: usize = (BLOCK_SIZE as usize - PARTITIONS_OFFSET) / BSD_PARTITION_LEN;
+/// Index of the raw partition, 'c', which covers the slice on FreeBSD and
+/// NetBSD, and the whole disk on OpenBSD
+pub const RAW_PARTITION: usize = 2;
+/// Index of the extra raw partition NetBSD keeps, 'd', covering the whole
+/// disk
+pub const NETBSD_RAW_PARTITION: usize = 3;
+
+/// Offset of the first magic number
+const MAGIC_OFFSET: usize = 0;
+/// Offset of the sector size
+const SECSIZE_OFFSET: usize = 40;
+/// Offset of the second magic number
+const MAGIC2_OFFSET: usize = 132;
+/// Offset of the number of sub-partitions
+const NPARTITIONS_OFFSET: usize = 138;
+/// Offset of the sub-partition entries
+const PARTITIONS_OFFSET: usize = 148;
+/// Offset of the size field in a sub-partition entry
+const SIZE_OFFSET: usize = 0;
+/// Offset of the offset field in a sub-partition entry
+const OFFSET_OFFSET: usize = 4;
+/// Offset of the filesystem type in a sub-partition entry
+const FSTYPE_OFFSET: usize = 12;
+
+/// Used to store data about a sub-partition of a disklabel
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BsdPartitionRecord {
+    offset: u32,
+    size: u32,
+    fstype: u8,
+}
+
+impl BsdPartitionRecord {
+    /// Create a sub-partition record from bytes, with its offset already
+    /// made relative to the start of the disk
+    ///
+    /// Returns None if the offset wraps past the 32 bit LBAs
+    fn from_bytes(bytes: &[u8], base: u32) -> Option<Self> {
+        Some(Self {
+            offset: read_u32(bytes, OFFSET_OFFSET).checked_add(base)?,
+            size: read_u32(bytes, SIZE_OFFSET),
+            fstype: bytes[FSTYPE_OFFSET],
+        })
+    }
+
+    /// Cut the sub-partition down to the part within the slice, for raw
+    /// partitions that cover the whole disk
+    fn clamp_to(&mut self, slice: &PartitionRecord) {
+        let start = cmp::max(self.offset, slice.relative_sector);
+        let end = cmp::min(
+            self.offset as u64 + self.size as u64,
+            slice.relative_sector as u64 + slice.total_sectors as u64,
+        );
+
+        self.offset = start;
+        self.size = end.saturating_sub(start as u64) as u32;
+    }
+
+    #[inline]
+    /// Get the first LBA of the sub-partition, relative to the start of the
+    /// disk
+    pub fn get_offset(&self) -> u32 {
+        self.offset
+    }
+
+    #[inline]
+    /// Get the number of sectors in the sub-partition
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    #[inline]
+    /// Get the raw BSD filesystem type of the sub-partition
+    pub fn get_fstype(&self) -> u8 {
+        self.fstype
+    }
+
+    #[inline]
+    /// Check if the sub-partition covers any sectors
+    pub fn is_used(&self) -> bool {
+        self.size != 0
+    }
+
+    #[inline]
+    /// Get the starting position of the sub-partition
+    pub fn get_start_pos(&self) -> u64 {
+        lba_to_u64(self.offset)
+    }
+
+    #[inline]
+    /// Get the end position of the sub-partition
+    pub fn get_end_pos(&self) -> u64 {
+        self.get_start_pos() + lba_to_u64(self.size)
+    }
+}
+
+/// A BSD disklabel read from a slice
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Disklabel {
+    slice: PartitionRecord,
+    partition_count: usize,
+    partitions: [BsdPartitionRecord; MAX_BSD_PARTITIONS],
+}
+
+impl Disklabel {
+    /// Read the disklabel of the given slice
+    ///
+    /// FreeBSD labels with a raw partition starting at zero use offsets
+    /// relative to the slice, otherwise offsets are relative to the start of
+    /// the disk. Raw partitions covering the whole disk, like 'c' on OpenBSD
+    /// and 'd' on NetBSD, are cut down to the slice.
+    pub fn read<IO: Read + Seek>(
+        io: &mut IO,
+        slice: &PartitionRecord,
+    ) -> Result<Self, MbrError<IO::Error>> {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        if slice.total_sectors <= LABEL_SECTOR {
+            return Err(MbrError::InvalidDisklabel);
+        }
+
+        let label_lba = slice
+            .relative_sector
+            .checked_add(LABEL_SECTOR)
+            .ok_or(MbrError::InvalidDisklabel)?;
+
+        io.seek(SeekFrom::Start(lba_to_u64(label_lba)))?;
+        io.read_exact(&mut sector)?;
+
+        if read_u32(&sector, MAGIC_OFFSET) != DISKLABEL_MAGIC
+            || read_u32(&sector, MAGIC2_OFFSET) != DISKLABEL_MAGIC
+        {
+            return Err(MbrError::MissingDisklabel);
+        }
+
+        let partition_count = u16::from_le_bytes(
+            sector[NPARTITIONS_OFFSET..NPARTITIONS_OFFSET + 2]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let secsize = read_u32(&sector, SECSIZE_OFFSET);
+
+        if partition_count > MAX_BSD_PARTITIONS || secsize as u64 != BLOCK_SIZE {
+            return Err(MbrError::InvalidDisklabel);
+        }
+
+        // The checksum is an XOR of every u16 up to the end of the entries,
+        // so XORing in the stored checksum as well gives zero
+        let label_end = PARTITIONS_OFFSET + partition_count * BSD_PARTITION_LEN;
+        let checksum = sector[..label_end]
+            .chunks_exact(2)
+            .fold(0, |sum, word| sum ^ u16::from_le_bytes([word[0], word[1]]));
+
+        if checksum != 0 {
+            return Err(MbrError::InvalidDisklabel);
+        }
+
+        let entry_at = |i: usize| &sector[PARTITIONS_OFFSET + i * BSD_PARTITION_LEN..];
+        let partition_type = slice.get_partition_type();
+        let base = match partition_type == PartitionType::FreeBSD
+            && partition_count > RAW_PARTITION
+            && read_u32(entry_at(RAW_PARTITION), OFFSET_OFFSET) == 0
+        {
+            true => slice.relative_sector,
+            false => 0,
+        };
+        let is_raw = |i: usize| {
+            i == RAW_PARTITION
+                || (partition_type == PartitionType::NetBSD && i == NETBSD_RAW_PARTITION)
+        };
+
+        let mut partitions = [BsdPartitionRecord::default(); MAX_BSD_PARTITIONS];
+
+        for (i, partition) in partitions.iter_mut().enumerate().take(partition_count) {
+            *partition = BsdPartitionRecord::from_bytes(entry_at(i), base)
+                .ok_or(MbrError::InvalidDisklabel)?;
+
+            if is_raw(i) {
+                partition.clamp_to(slice);
+            } else if partition.is_used()
+                && (partition.get_start_pos() < slice.get_start_pos()
+                    || partition.get_end_pos() > slice.get_end_pos())
+            {
+                return Err(MbrError::InvalidDisklabel);
+            }
+        }
+
+        Ok(Self {
+            slice: *slice,
+            partition_count,
+            partitions,
+        })
+    }
+
+    #[inline]
+    /// Get the record of the slice holding the disklabel
+    pub fn get_slice(&self) -> PartitionRecord {
+        self.slice
+    }
+
+    #[inline]
+    /// Get the number of sub-partition entries in the disklabel
+    pub fn partition_count(&self) -> usize {
+        self.partition_count
+    }
+
+    #[inline]
+    /// Get the sub-partition entry at an index, 0 being 'a'
+    pub fn get_record(&self, index: usize) -> Option<BsdPartitionRecord> {
+        self.partitions[..self.partition_count].get(index).copied()
+    }
+}
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Read the disklabel of a BSD slice
+    pub fn get_disklabel(&mut self, id: PartitionId) -> Result<Disklabel, MbrError<IO::Error>> {
+        let slice = self.partitions[id as usize];
+
+        if !slice.is_used() || !slice.get_partition_type().is_bsd() {
+            return Err(MbrError::MissingDisklabel);
+        }
+
+        Disklabel::read(&mut self.io, &slice)
+    }
+
+    /// Get a sub-partition of a BSD slice, 0 being 'a'
+    pub fn get_bsd_partition(
+        &mut self,
+        id: PartitionId,
+        index: usize,
+    ) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let record = self
+            .get_disklabel(id)?
+            .get_record(index)
+            .ok_or(MbrError::InvalidPartitionIndex(index as u32))?;
+
+        if !record.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        Partition::new(record.get_start_pos(), record.get_end_pos(), &mut self.io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Read};
+
+    use crate::{
+        bsd::*, ebr::tests::write_table, gpt::write_u32, types::PartitionType, PartitionId,
+        PartitionRecord, BLOCK_SIZE, MBR,
+    };
+
+    /// Offset of the checksum
+    const CHECKSUM_OFFSET: usize = 136;
+
+    /// Build a 64 sector image with a slice of the given type from LBA 8 to
+    /// 56, labelled with the given (offset, size, fstype) sub-partitions
+    fn label_img(partition_type: PartitionType, entries: &[(u32, u32, u8)]) -> Vec<u8> {
+        let mut img = vec![0; 64 * BLOCK_SIZE as usize];
+
+        write_table(
+            &mut img,
+            0,
+            &[PartitionRecord::new(8, 48, partition_type, false)],
+        );
+
+        let label = &mut img[9 * BLOCK_SIZE as usize..10 * BLOCK_SIZE as usize];
+
+        write_u32(label, MAGIC_OFFSET, DISKLABEL_MAGIC);
+        write_u32(label, SECSIZE_OFFSET, BLOCK_SIZE as u32);
+        write_u32(label, MAGIC2_OFFSET, DISKLABEL_MAGIC);
+        label[NPARTITIONS_OFFSET] = entries.len() as u8;
+
+        for (i, &(offset, size, fstype)) in entries.iter().enumerate() {
+            let entry = PARTITIONS_OFFSET + i * BSD_PARTITION_LEN;
+
+            write_u32(label, entry + OFFSET_OFFSET, offset);
+            write_u32(label, entry + SIZE_OFFSET, size);
+            label[entry + FSTYPE_OFFSET] = fstype;
+        }
+
+        let label_end = PARTITIONS_OFFSET + entries.len() * BSD_PARTITION_LEN;
+        let checksum = label[..label_end]
+            .chunks_exact(2)
+            .fold(0, |sum, word| sum ^ u16::from_le_bytes([word[0], word[1]]));
+
+        label[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 2].copy_from_slice(&checksum.to_le_bytes());
+
+        img[16 * BLOCK_SIZE as usize] = 9;
+
+        img
+    }
+
+    /// Build a FreeBSD image holding sub-partitions 'a' at LBA 16 and 'c'
+    /// covering the slice, with offsets relative to the given base
+    fn test_img(base: u32) -> Vec<u8> {
+        label_img(
+            PartitionType::FreeBSD,
+            &[(16 - base, 8, 7), (0, 0, 0), (8 - base, 48, 0)],
+        )
+    }
+
+    #[test]
+    /// Ensure that both absolute and slice relative disklabels are read
+    fn test_disklabel() {
+        for base in [0, 8] {
+            let mut mbr = MBR::new(FromStd::new(Cursor::new(test_img(base)))).unwrap();
+            let label = mbr.get_disklabel(PartitionId::One).unwrap();
+
+            assert_eq!(label.partition_count(), 3);
+            assert_eq!(label.get_record(0).unwrap().get_offset(), 16);
+            assert_eq!(label.get_record(0).unwrap().get_fstype(), 7);
+            assert!(!label.get_record(1).unwrap().is_used());
+            assert_eq!(label.get_record(3), None);
+
+            let mut partition = mbr.get_bsd_partition(PartitionId::One, 0).unwrap();
+            let mut buf: [u8; 1] = [0; 1];
+
+            assert_eq!(partition.len(), 8 * BLOCK_SIZE);
+            partition.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[0], 9);
+
+            assert!(matches!(
+                mbr.get_bsd_partition(PartitionId::One, 1),
+                Err(MbrError::EmptyPartition)
+            ));
+        }
+
+        // A bad checksum is caught
+        let mut img = test_img(0);
+
+        img[9 * BLOCK_SIZE as usize + CHECKSUM_OFFSET] ^= 1;
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_disklabel(PartitionId::One),
+            Err(MbrError::InvalidDisklabel)
+        ));
+        assert!(matches!(
+            mbr.get_disklabel(PartitionId::Two),
+            Err(MbrError::MissingDisklabel)
+        ));
+
+        // A slice starting at the last LBA can't hold a label after it
+        let mut img = test_img(0);
+
+        write_table(
+            &mut img,
+            0,
+            &[PartitionRecord::new(
+                u32::MAX,
+                48,
+                PartitionType::FreeBSD,
+                false,
+            )],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_disklabel(PartitionId::One),
+            Err(MbrError::InvalidDisklabel)
+        ));
+    }
+
+    #[test]
+    /// Ensure that OpenBSD and NetBSD labels are read with absolute offsets,
+    /// and that raw partitions covering the whole disk are cut to the slice
+    fn test_disklabel_whole_disk() {
+        // OpenBSD's 'c' covers the whole disk
+        let img = label_img(PartitionType::OpenBSD, &[(16, 8, 7), (0, 0, 0), (0, 64, 0)]);
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+        let label = mbr.get_disklabel(PartitionId::One).unwrap();
+
+        assert_eq!(label.get_record(0).unwrap().get_offset(), 16);
+        assert_eq!(label.get_record(2).unwrap().get_offset(), 8);
+        assert_eq!(label.get_record(2).unwrap().get_size(), 48);
+
+        let mut partition = mbr.get_bsd_partition(PartitionId::One, 0).unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 9);
+
+        // NetBSD's 'c' covers the slice and 'd' the whole disk
+        let img = label_img(
+            PartitionType::NetBSD,
+            &[(16, 8, 7), (0, 0, 0), (8, 48, 0), (0, 64, 0)],
+        );
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+        let label = mbr.get_disklabel(PartitionId::One).unwrap();
+
+        assert_eq!(label.partition_count(), 4);
+        assert_eq!(label.get_record(0).unwrap().get_offset(), 16);
+        assert_eq!(label.get_record(2).unwrap().get_offset(), 8);
+        assert_eq!(label.get_record(3).unwrap().get_offset(), 8);
+        assert_eq!(label.get_record(3).unwrap().get_size(), 48);
+
+        // Other sub-partitions outside the slice are still rejected
+        let img = label_img(PartitionType::NetBSD, &[(0, 8, 7), (0, 0, 0), (8, 48, 0)]);
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_disklabel(PartitionId::One),
+            Err(MbrError::InvalidDisklabel)
+        ));
+    }
+}
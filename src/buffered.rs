@@ -0,0 +1,604 @@
+//! Buffering wrappers for devices on slow buses.
+//!
+//! Every transfer to an SD card over SPI has a fixed cost on top of the
+//! bytes moved, so many small transfers are far slower than a few large
+//! ones. The wrappers here turn one into the other. [`ReadAhead`] and
+//! [`WriteBuffer`] each help one direction, while [`BufStream`] buffers
+//! both through a single stack buffer.
+
+use core::cmp;
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io, SeekFrom,
+};
+
+/// Wrapper reading `N` bytes ahead, so that sequential small reads are
+/// served from memory
+///
+/// Reads of `N` bytes or more go straight to the device. Writes go straight
+/// to the device too, dropping whatever was read ahead.
+pub struct ReadAhead<IO, const N: usize> {
+    io: IO,
+    pos: u64,
+    buffer: [u8; N],
+    buffer_pos: u64,
+    buffer_len: usize,
+}
+
+impl<IO, const N: usize> ReadAhead<IO, N> {
+    /// Wrap a device, starting at the beginning of it
+    pub fn new(io: IO) -> Self {
+        Self {
+            io,
+            pos: 0,
+            buffer: [0; N],
+            buffer_pos: 0,
+            buffer_len: 0,
+        }
+    }
+
+    /// Drop whatever was read ahead, for when the device was changed behind
+    /// the wrapper's back
+    pub fn invalidate(&mut self) {
+        self.buffer_len = 0;
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device
+    ///
+    /// Writes made through it aren't seen by the wrapper, so call
+    /// [`ReadAhead::invalidate`] afterwards
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+
+    /// Get the part of the buffer from the current position onwards
+    fn buffered(&self) -> &[u8] {
+        let buffer_end = self.buffer_pos + self.buffer_len as u64;
+
+        match self.pos >= self.buffer_pos && self.pos < buffer_end {
+            true => &self.buffer[(self.pos - self.buffer_pos) as usize..self.buffer_len],
+            false => &[],
+        }
+    }
+}
+
+impl<IO: Io, const N: usize> Io for ReadAhead<IO, N> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Seek, const N: usize> Read for ReadAhead<IO, N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.buffered().is_empty() {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+
+            if buf.len() >= N {
+                let read = self.io.read(buf)?;
+
+                self.pos += read as u64;
+
+                return Ok(read);
+            }
+
+            self.buffer_len = self.io.read(&mut self.buffer)?;
+            self.buffer_pos = self.pos;
+        }
+
+        let buffered = self.buffered();
+        let len = cmp::min(buf.len(), buffered.len());
+
+        buf[..len].copy_from_slice(&buffered[..len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<IO: Write + Seek, const N: usize> Write for ReadAhead<IO, N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.invalidate();
+        self.io.seek(SeekFrom::Start(self.pos))?;
+
+        let written = self.io.write(buf)?;
+
+        self.pos += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.io.flush()
+    }
+}
+
+impl<IO: Seek, const N: usize> Seek for ReadAhead<IO, N> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        // Let the device decide what is a valid position
+        if let SeekFrom::Current(_) = pos {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+        }
+
+        self.pos = self.io.seek(pos)?;
+
+        Ok(self.pos)
+    }
+}
+
+/// Wrapper collecting adjacent small writes, so they reach the device as
+/// fewer, larger writes
+///
+/// Buffered data is written out once the buffer fills up, on a write that
+/// doesn't follow on from it, before reads and seeks from the end, on
+/// [`flush`](Write::flush), and when dropped. Errors while dropping are lost,
+/// so flush before dropping to catch them. `N` should be a multiple of the
+/// sector size to get whole-sector writes.
+///
+/// As it has to write out the buffer when dropped, the device can't be
+/// taken back out. Wrap a `&mut` to the device to keep ownership of it.
+pub struct WriteBuffer<IO: Write + Seek, const N: usize> {
+    io: IO,
+    pos: u64,
+    buffer: [u8; N],
+    buffer_pos: u64,
+    buffer_len: usize,
+}
+
+impl<IO: Write + Seek, const N: usize> WriteBuffer<IO, N> {
+    /// Wrap a device, starting at the beginning of it
+    pub fn new(io: IO) -> Self {
+        Self {
+            io,
+            pos: 0,
+            buffer: [0; N],
+            buffer_pos: 0,
+            buffer_len: 0,
+        }
+    }
+
+    #[inline]
+    /// Get the number of bytes waiting to be written
+    pub fn get_pending(&self) -> usize {
+        self.buffer_len
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device
+    ///
+    /// Data that is still buffered hasn't reached it yet
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    /// Write out whatever is buffered
+    fn write_buffer(&mut self) -> Result<(), IO::Error> {
+        if self.buffer_len > 0 {
+            self.io.seek(SeekFrom::Start(self.buffer_pos))?;
+            self.io.write_all(&self.buffer[..self.buffer_len])?;
+            self.buffer_len = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl<IO: Write + Seek, const N: usize> Drop for WriteBuffer<IO, N> {
+    fn drop(&mut self) {
+        let _ = self.write_buffer();
+    }
+}
+
+impl<IO: Write + Seek, const N: usize> Io for WriteBuffer<IO, N> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Read for WriteBuffer<IO, N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.write_buffer()?;
+        self.io.seek(SeekFrom::Start(self.pos))?;
+
+        let read = self.io.read(buf)?;
+
+        self.pos += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<IO: Write + Seek, const N: usize> Write for WriteBuffer<IO, N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.buffer_len > 0 && self.pos != self.buffer_pos + self.buffer_len as u64 {
+            self.write_buffer()?;
+        }
+
+        if self.buffer_len == 0 {
+            // Large writes have nothing to gain from the buffer
+            if buf.len() >= N {
+                self.io.seek(SeekFrom::Start(self.pos))?;
+
+                let written = self.io.write(buf)?;
+
+                self.pos += written as u64;
+
+                return Ok(written);
+            }
+
+            self.buffer_pos = self.pos;
+        }
+
+        let len = cmp::min(buf.len(), N - self.buffer_len);
+
+        self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&buf[..len]);
+        self.buffer_len += len;
+        self.pos += len as u64;
+
+        if self.buffer_len == N {
+            self.write_buffer()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write_buffer()?;
+        self.io.flush()
+    }
+}
+
+impl<IO: Write + Seek, const N: usize> Seek for WriteBuffer<IO, N> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        match pos {
+            // The end may move once the buffer is written
+            SeekFrom::End(_) => self.write_buffer()?,
+            SeekFrom::Current(_) => {
+                self.io.seek(SeekFrom::Start(self.pos))?;
+            }
+            SeekFrom::Start(_) => (),
+        }
+
+        self.pos = self.io.seek(pos)?;
+
+        Ok(self.pos)
+    }
+}
+
+/// Wrapper buffering both reads and writes through a single `N` byte
+/// buffer on the stack
+///
+/// The buffer holds a window of the device. Reads and writes within the
+/// window are served from memory, and anything else first writes out the
+/// window if it was changed. Transfers of `N` bytes or more outside of the
+/// window go straight to the device.
+///
+/// Like [`WriteBuffer`], changes are written out on
+/// [`flush`](Write::flush) and when dropped, and a `&mut` to the device can
+/// be wrapped to keep ownership of it. For read-only devices, use
+/// [`ReadAhead`].
+pub struct BufStream<IO: Read + Write + Seek, const N: usize> {
+    io: IO,
+    pos: u64,
+    buffer: [u8; N],
+    buffer_pos: u64,
+    buffer_len: usize,
+    dirty: bool,
+}
+
+impl<IO: Read + Write + Seek, const N: usize> BufStream<IO, N> {
+    /// Wrap a device, starting at the beginning of it
+    pub fn new(io: IO) -> Self {
+        Self {
+            io,
+            pos: 0,
+            buffer: [0; N],
+            buffer_pos: 0,
+            buffer_len: 0,
+            dirty: false,
+        }
+    }
+
+    /// Write out any changes and drop the window, for when the device was
+    /// changed behind the wrapper's back
+    pub fn invalidate(&mut self) -> Result<(), IO::Error> {
+        self.write_buffer()?;
+        self.buffer_len = 0;
+
+        Ok(())
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device
+    ///
+    /// Changes that are still buffered haven't reached it yet, and writes
+    /// made through it aren't seen by the wrapper, so call
+    /// [`BufStream::invalidate`] afterwards
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    /// Get the offset of the current position into the window, if it is
+    /// within it or right at its end
+    fn window_offset(&self) -> Option<usize> {
+        let offset = self.pos.checked_sub(self.buffer_pos)?;
+
+        match offset <= self.buffer_len as u64 {
+            true => Some(offset as usize),
+            false => None,
+        }
+    }
+
+    /// Write out the window if it was changed
+    fn write_buffer(&mut self) -> Result<(), IO::Error> {
+        if self.dirty {
+            self.io.seek(SeekFrom::Start(self.buffer_pos))?;
+            self.io.write_all(&self.buffer[..self.buffer_len])?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Drop for BufStream<IO, N> {
+    fn drop(&mut self) {
+        let _ = self.write_buffer();
+    }
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Io for BufStream<IO, N> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Read for BufStream<IO, N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let offset = match self.window_offset() {
+            Some(offset) if offset < self.buffer_len => offset,
+            _ => {
+                self.write_buffer()?;
+                self.io.seek(SeekFrom::Start(self.pos))?;
+
+                if buf.len() >= N {
+                    let read = self.io.read(buf)?;
+
+                    self.pos += read as u64;
+
+                    return Ok(read);
+                }
+
+                self.buffer_len = self.io.read(&mut self.buffer)?;
+                self.buffer_pos = self.pos;
+
+                0
+            }
+        };
+
+        let len = cmp::min(buf.len(), self.buffer_len - offset);
+
+        buf[..len].copy_from_slice(&self.buffer[offset..offset + len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Write for BufStream<IO, N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let offset = match self.window_offset() {
+            Some(offset) if offset < N => offset,
+            _ => {
+                self.write_buffer()?;
+
+                if buf.len() >= N {
+                    self.io.seek(SeekFrom::Start(self.pos))?;
+
+                    let written = self.io.write(buf)?;
+
+                    self.pos += written as u64;
+
+                    return Ok(written);
+                }
+
+                self.buffer_len = 0;
+                self.buffer_pos = self.pos;
+
+                0
+            }
+        };
+
+        let len = cmp::min(buf.len(), N - offset);
+
+        self.buffer[offset..offset + len].copy_from_slice(&buf[..len]);
+        self.buffer_len = cmp::max(self.buffer_len, offset + len);
+        self.dirty = true;
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write_buffer()?;
+        self.io.flush()
+    }
+}
+
+impl<IO: Read + Write + Seek, const N: usize> Seek for BufStream<IO, N> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        match pos {
+            // The end may move once the window is written
+            SeekFrom::End(_) => self.write_buffer()?,
+            SeekFrom::Current(_) => {
+                self.io.seek(SeekFrom::Start(self.pos))?;
+            }
+            SeekFrom::Start(_) => (),
+        }
+
+        self.pos = self.io.seek(pos)?;
+
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::adapters::FromStd;
+
+    use crate::{buffered::*, lazy::tests::CountIo, PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that sequential small reads are served by a single larger read
+    fn test_read_ahead() {
+        let io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let mut mbr = MBR::new(io).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut expected: [u8; 64] = [0; 64];
+
+        partition.read_exact(&mut expected).unwrap();
+        partition.rewind().unwrap();
+
+        let mut partition = ReadAhead::<_, 64>::new(CountIo::new(partition));
+        let mut buf: [u8; 8] = [0; 8];
+
+        for chunk in expected.chunks(8) {
+            partition.read_exact(&mut buf).unwrap();
+
+            assert_eq!(&buf, chunk);
+        }
+
+        assert_eq!(partition.get_ref().reads, 1);
+
+        // Seeking back into the buffer doesn't need the device
+        partition.seek(SeekFrom::Current(-16)).unwrap();
+        partition.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, &expected[48..56]);
+        assert_eq!(partition.get_ref().reads, 1);
+
+        // Writes drop what was read ahead
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.write_all(b"Hello!").unwrap();
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.read_exact(&mut buf[..6]).unwrap();
+
+        assert_eq!(&buf[..6], b"Hello!");
+        assert_eq!(partition.get_ref().reads, 2);
+    }
+
+    #[test]
+    /// Ensure that adjacent small writes reach the device as one write
+    fn test_write_buffer() {
+        let mut img = TEST_IMG_1.to_vec();
+        let io = CountIo::new(FromStd::new(Cursor::new(&mut img)));
+        let mut device = WriteBuffer::<_, 512>::new(io);
+
+        device.seek(SeekFrom::Start(1024)).unwrap();
+
+        for byte in 0..64 {
+            device.write_all(&[byte]).unwrap();
+        }
+
+        assert_eq!(device.get_pending(), 64);
+        assert_eq!(device.get_ref().writes, 0);
+
+        // Reads see what was written so far
+        let mut buf: [u8; 4] = [0; 4];
+
+        device.seek(SeekFrom::Start(1024)).unwrap();
+        device.read_exact(&mut buf).unwrap();
+
+        assert_eq!(buf, [0, 1, 2, 3]);
+        assert_eq!(device.get_ref().writes, 1);
+
+        // A write elsewhere writes out the buffer first
+        device.seek(SeekFrom::Start(2048)).unwrap();
+        device.write_all(b"ab").unwrap();
+        device.seek(SeekFrom::Start(4096)).unwrap();
+        device.write_all(b"cd").unwrap();
+
+        assert_eq!(device.get_ref().writes, 2);
+
+        drop(device);
+
+        assert_eq!(&img[2048..2050], b"ab");
+        assert_eq!(&img[4096..4098], b"cd");
+    }
+
+    #[test]
+    /// Ensure that reads and writes within the window stay in memory until
+    /// flushed
+    fn test_buf_stream() {
+        let mut io = CountIo::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec())));
+        let mut mbr = MBR::new(&mut io).unwrap();
+        let partition = mbr.get_partition(PartitionId::Two).unwrap();
+        let mut stream = BufStream::<_, 1024>::new(partition);
+        let mut buf: [u8; 4] = [0; 4];
+
+        stream.read_exact(&mut buf).unwrap();
+        stream.write_all(b"abcd").unwrap();
+        stream.seek(SeekFrom::Start(2)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf[2..], b"ab");
+
+        stream.seek(SeekFrom::Start(1020)).unwrap();
+        stream.write_all(b"efgh").unwrap();
+
+        // One read for the MBR, and one to fill the window
+        let device = &stream.get_ref().io;
+
+        assert_eq!((device.reads, device.writes), (2, 0));
+
+        stream.flush().unwrap();
+        drop(stream);
+
+        assert_eq!(io.writes, 1);
+
+        let img = io.inner.inner().get_ref();
+        let start = 18 * 512;
+
+        assert_eq!(&img[start + 4..start + 8], b"abcd");
+        assert_eq!(&img[start + 1020..start + 1024], b"efgh");
+    }
+}
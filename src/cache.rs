@@ -0,0 +1,293 @@
+//! Sector caching for slow devices, like SD cards on an SPI bus.
+//!
+//! File systems keep going back to the same few sectors, like the start of
+//! the FAT. A [`SectorCache`] keeps the most recently used sectors in memory
+//! so those reads don't have to reach the device every time.
+
+use alloc::{vec, vec::Vec};
+use core::cmp;
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io, SeekFrom,
+};
+
+use crate::BLOCK_SIZE;
+
+/// A cached sector
+struct Entry {
+    sector: u64,
+    data: Vec<u8>,
+}
+
+/// Wrapper keeping the most recently used sectors of a device in memory
+///
+/// Reads smaller than a sector are served from the cache, while larger
+/// aligned reads go straight to the device so streaming a big file doesn't
+/// push the hot sectors out. Writes go straight through to the device and
+/// update any cached copies, so the cache never holds unwritten data.
+pub struct SectorCache<IO> {
+    io: IO,
+    pos: u64,
+    capacity: usize,
+    // Least recently used first
+    entries: Vec<Entry>,
+}
+
+impl<IO> SectorCache<IO> {
+    /// Wrap a device, caching up to `capacity` sectors
+    ///
+    /// At least one sector is always cached
+    pub fn new(io: IO, capacity: usize) -> Self {
+        let capacity = cmp::max(capacity, 1);
+
+        Self {
+            io,
+            pos: 0,
+            capacity,
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    /// Get the number of sectors the cache can hold
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    /// Get the number of sectors currently cached
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    /// Check if no sectors are cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every cached sector, for when the device was changed behind the
+    /// cache's back
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device
+    ///
+    /// Writes made through it aren't seen by the cache, so call
+    /// [`SectorCache::invalidate`] afterwards
+    pub fn get_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+
+    /// Update the cached copies of sectors that were just written
+    fn update(&mut self, pos: u64, buf: &[u8]) {
+        let end = pos + buf.len() as u64;
+
+        self.entries.retain_mut(|entry| {
+            let entry_start = entry.sector * BLOCK_SIZE;
+            let entry_end = entry_start + BLOCK_SIZE;
+
+            if entry_end <= pos || entry_start >= end {
+                return true;
+            }
+
+            let from = cmp::max(pos, entry_start);
+            let to = cmp::min(end, entry_end);
+            let offset = (from - entry_start) as usize;
+            let len = (to - from) as usize;
+
+            // A write past the cached end of the device grew it, so the
+            // cached copy can't be patched
+            if offset + len > entry.data.len() {
+                return false;
+            }
+
+            entry.data[offset..offset + len]
+                .copy_from_slice(&buf[(from - pos) as usize..(to - pos) as usize]);
+
+            true
+        });
+    }
+}
+
+impl<IO: Read + Seek> SectorCache<IO> {
+    /// Get the cached copy of a sector, reading it from the device if it
+    /// isn't cached yet
+    fn get_sector(&mut self, sector: u64) -> Result<&[u8], IO::Error> {
+        match self.entries.iter().position(|entry| entry.sector == sector) {
+            Some(index) => {
+                // Move it to the most recently used end
+                let entry = self.entries.remove(index);
+
+                self.entries.push(entry);
+            }
+            None => {
+                let mut data = vec![0; BLOCK_SIZE as usize];
+                let mut filled = 0;
+
+                self.io.seek(SeekFrom::Start(sector * BLOCK_SIZE))?;
+
+                while filled < data.len() {
+                    match self.io.read(&mut data[filled..])? {
+                        0 => break,
+                        n => filled += n,
+                    }
+                }
+
+                data.truncate(filled);
+
+                if self.entries.len() >= self.capacity {
+                    self.entries.remove(0);
+                }
+
+                self.entries.push(Entry { sector, data });
+            }
+        }
+
+        Ok(&self.entries[self.entries.len() - 1].data)
+    }
+}
+
+impl<IO: Io> Io for SectorCache<IO> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Seek> Read for SectorCache<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let offset = (self.pos % BLOCK_SIZE) as usize;
+
+        // Whole sectors are read directly, keeping the cache for the small
+        // reads that benefit from it
+        if offset == 0 && buf.len() >= BLOCK_SIZE as usize {
+            let len = buf.len() - buf.len() % BLOCK_SIZE as usize;
+
+            self.io.seek(SeekFrom::Start(self.pos))?;
+
+            let read = self.io.read(&mut buf[..len])?;
+
+            self.pos += read as u64;
+
+            return Ok(read);
+        }
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let data = self.get_sector(self.pos / BLOCK_SIZE)?;
+
+        if data.len() <= offset {
+            return Ok(0);
+        }
+
+        let len = cmp::min(buf.len(), data.len() - offset);
+
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<IO: Write + Seek> Write for SectorCache<IO> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.io.seek(SeekFrom::Start(self.pos))?;
+
+        let written = self.io.write(buf)?;
+
+        self.update(self.pos, &buf[..written]);
+        self.pos += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.io.flush()
+    }
+}
+
+impl<IO: Seek> Seek for SectorCache<IO> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        // Let the device decide what is a valid position
+        if let SeekFrom::Current(_) = pos {
+            self.io.seek(SeekFrom::Start(self.pos))?;
+        }
+
+        self.pos = self.io.seek(pos)?;
+
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::adapters::FromStd;
+
+    use crate::{cache::*, lazy::tests::CountIo, PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that repeated small reads are served from the cache, and that
+    /// writes keep it up to date
+    fn test_sector_cache() {
+        let io = CountIo::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec())));
+        let mut mbr = MBR::new(SectorCache::new(io, 2)).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        partition.write_all(b"abcd").unwrap();
+
+        let reads = partition.into_inner().get_ref().reads;
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+
+        for _ in 0..3 {
+            partition.seek(SeekFrom::Start(0)).unwrap();
+            partition.read_exact(&mut buf).unwrap();
+
+            assert_eq!(&buf, b"abcd");
+        }
+
+        assert_eq!(partition.into_inner().get_ref().reads, reads + 1);
+
+        // A write to a cached sector updates the cached copy
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+
+        partition.seek(SeekFrom::Start(2)).unwrap();
+        partition.write_all(b"xy").unwrap();
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.read_exact(&mut buf).unwrap();
+
+        assert_eq!(&buf, b"abxy");
+
+        // Using more sectors than fit pushes out the least recently used
+        for sector in 1..3 {
+            partition
+                .seek(SeekFrom::Start(sector * BLOCK_SIZE))
+                .unwrap();
+            partition.read_exact(&mut buf).unwrap();
+        }
+
+        let cache = partition.into_inner();
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.entries.iter().all(|entry| entry.sector != 1));
+    }
+}
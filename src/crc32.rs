@@ -0,0 +1,87 @@
+//! CRC32 as used by the GPT, built without any lookup table dependencies.
+
+/// Reflected polynomial of the IEEE CRC32
+const POLYNOMIAL: u32 = 0xedb88320;
+
+/// Lookup table for the IEEE CRC32, generated at compile time
+const TABLE: [u32; 256] = {
+    let mut table: [u32; 256] = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ POLYNOMIAL,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+/// Running CRC32 over data that is fed in pieces
+#[derive(Debug, Copy, Clone)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Start a new CRC32
+    pub fn new() -> Self {
+        Self { crc: 0xffffffff }
+    }
+
+    /// Feed bytes into the CRC32
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.crc = TABLE[((self.crc ^ *byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    #[inline]
+    /// Get the final CRC32 of everything fed in so far
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+/// Get the CRC32 of a slice of bytes
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+
+    crc.update(bytes);
+
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crc32::*;
+
+    #[test]
+    /// Check against the standard CRC32 check value
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+
+        let mut crc = Crc32::new();
+
+        crc.update(b"1234");
+        crc.update(b"56789");
+
+        assert_eq!(crc.finish(), 0xcbf43926);
+    }
+}
@@ -0,0 +1,667 @@
+//! Support for logical partitions inside of an extended partition.
+//!
+//! An extended partition starts with an Extended Boot Record (EBR), laid
+//! out like an MBR. Its first record describes a logical partition relative
+//! to the EBR, and its second record points to the next EBR relative to the
+//! start of the extended partition.
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    SeekFrom,
+};
+
+use crate::{
+    error::MbrError, lba_to_u64, types::PartitionType, Partition, PartitionRecord, BLOCK_SIZE, MBR,
+    RECORDS_START, RECORD_LEN, SIGNATURE, SIGNATURE_OFFSET,
+};
+
+/// Maximum number of EBRs that will be followed, to stop corrupt chains
+/// from looping forever
+pub const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+/// Smallest gap between an EBR and its logical partition, the EBR itself
+pub const MIN_LOGICAL_GAP: u32 = 1;
+
+/// Gap between an EBR and its logical partition used by most modern tools,
+/// one MiB
+pub const ALIGNED_LOGICAL_GAP: u32 = 2048;
+
+/// Used to store an EBR and where it is on the disk
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ebr {
+    lba: u32,
+    logical: PartitionRecord,
+    next_lba: Option<u32>,
+}
+
+impl Ebr {
+    /// Read the EBR at the given LBA, inside of the given extended
+    /// partition
+    pub fn read<IO: Read + Seek>(
+        io: &mut IO,
+        lba: u32,
+        extended: &PartitionRecord,
+    ) -> Result<Self, MbrError<IO::Error>> {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        if lba < extended.relative_sector || lba_to_u64(lba) >= extended.get_end_pos() {
+            return Err(MbrError::InvalidEbr(lba));
+        }
+
+        io.seek(SeekFrom::Start(lba_to_u64(lba)))?;
+        io.read_exact(&mut sector)?;
+
+        if sector[SIGNATURE_OFFSET as usize..] != SIGNATURE {
+            return Err(MbrError::InvalidEbr(lba));
+        }
+
+        let record_at = |i: usize| {
+            let record_i = RECORDS_START as usize + i * RECORD_LEN;
+
+            PartitionRecord::from_bytes(sector[record_i..record_i + RECORD_LEN].try_into().unwrap())
+        };
+
+        // The logical partition is relative to this EBR, the next EBR is
+        // relative to the start of the extended partition
+        let mut logical = record_at(0);
+        let next = record_at(1);
+
+        // A chain that wraps past the 32 bit LBAs is corrupt
+        logical.relative_sector = logical
+            .relative_sector
+            .checked_add(lba)
+            .ok_or(MbrError::InvalidEbr(lba))?;
+
+        let next_lba = match next.is_used() {
+            true => Some(
+                next.relative_sector
+                    .checked_add(extended.relative_sector)
+                    .ok_or(MbrError::InvalidEbr(lba))?,
+            ),
+            false => None,
+        };
+
+        Ok(Self {
+            lba,
+            logical,
+            next_lba,
+        })
+    }
+
+    #[inline]
+    /// Get the LBA of the EBR
+    pub fn get_lba(&self) -> u32 {
+        self.lba
+    }
+
+    #[inline]
+    /// Get the record of the logical partition, with its start made
+    /// relative to the start of the disk
+    pub fn get_logical(&self) -> PartitionRecord {
+        self.logical
+    }
+
+    #[inline]
+    /// Get the LBA of the next EBR in the chain, if there is one
+    pub fn get_next_lba(&self) -> Option<u32> {
+        self.next_lba
+    }
+}
+
+/// Lazy iterator over the EBRs of the chain, reading one sector at a time
+///
+/// Created by [`MBR::logical_partitions`]. Every EBR is yielded, including
+/// ones without a logical partition, so check [`Ebr::get_logical`] before
+/// using one. An error ends the iteration.
+#[derive(Debug)]
+pub struct LogicalPartitions<'a, IO: Read + Seek> {
+    extended: Option<PartitionRecord>,
+    next_lba: Option<u32>,
+    count: usize,
+    io: &'a mut IO,
+}
+
+impl<'a, IO: Read + Seek> Iterator for LogicalPartitions<'a, IO> {
+    type Item = Result<Ebr, MbrError<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let extended = self.extended?;
+        let lba = self.next_lba.take()?;
+
+        if self.count == MAX_LOGICAL_PARTITIONS {
+            return Some(Err(MbrError::InvalidEbr(lba)));
+        }
+
+        let ebr = Ebr::read(self.io, lba, &extended);
+
+        if let Ok(ebr) = ebr {
+            self.next_lba = ebr.next_lba;
+            self.count += 1;
+        }
+
+        Some(ebr)
+    }
+}
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Iterate over the EBRs of the chain, reading them as they are needed
+    ///
+    /// Nothing is yielded if there is no extended partition
+    pub fn logical_partitions(&mut self) -> LogicalPartitions<'_, IO> {
+        let extended = self.extended_record();
+
+        LogicalPartitions {
+            extended,
+            next_lba: extended.map(|extended| extended.relative_sector),
+            count: 0,
+            io: &mut self.io,
+        }
+    }
+
+    /// Get the record of the extended partition, if there is one
+    pub fn extended_record(&self) -> Option<PartitionRecord> {
+        self.partitions
+            .iter()
+            .find(|r| r.is_used() && r.get_partition_type().is_extended())
+            .copied()
+    }
+
+    /// Read the nth EBR of the chain, starting at zero
+    ///
+    /// At most [`MAX_LOGICAL_PARTITIONS`] EBRs are followed, so looping
+    /// chains end with [`MbrError::InvalidPartitionIndex`]
+    pub fn get_ebr(&mut self, n: usize) -> Result<Ebr, MbrError<IO::Error>> {
+        if n >= MAX_LOGICAL_PARTITIONS {
+            return Err(MbrError::InvalidPartitionIndex(n as u32));
+        }
+
+        let extended = self
+            .extended_record()
+            .ok_or(MbrError::NoExtendedPartition)?;
+        let mut ebr = Ebr::read(&mut self.io, extended.relative_sector, &extended)?;
+
+        for _ in 0..n {
+            let next_lba = ebr
+                .next_lba
+                .ok_or(MbrError::InvalidPartitionIndex(n as u32))?;
+
+            ebr = Ebr::read(&mut self.io, next_lba, &extended)?;
+        }
+
+        Ok(ebr)
+    }
+
+    /// Count the logical partitions by walking the EBR chain
+    ///
+    /// EBRs without a logical partition, like the empty one left in a fresh
+    /// extended partition, aren't counted. Returns zero if there is no
+    /// extended partition
+    pub fn logical_partition_count(&mut self) -> Result<usize, MbrError<IO::Error>> {
+        let mut count = 0;
+
+        for ebr in self.logical_partitions() {
+            if ebr?.logical.is_used() {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Get the nth logical partition, starting at zero
+    pub fn get_logical_partition(
+        &mut self,
+        n: usize,
+    ) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let ebr = self.get_ebr(n)?;
+        let logical = ebr.logical;
+
+        if !logical.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        if self.protect_table && logical.relative_sector == ebr.lba {
+            return Err(MbrError::OverlapsTable);
+        }
+
+        // get_ebr already failed if there is no extended partition
+        let extended = self
+            .extended_record()
+            .ok_or(MbrError::NoExtendedPartition)?;
+
+        if logical.get_start_pos() < extended.get_start_pos()
+            || logical.get_end_pos() > extended.get_end_pos()
+        {
+            return Err(MbrError::OutsideExtendedPartition);
+        }
+
+        Partition::new(logical.get_start_pos(), logical.get_end_pos(), &mut self.io)
+    }
+}
+
+impl<IO: Read + Write + Seek> MBR<IO> {
+    /// Append a logical partition to the end of the EBR chain
+    ///
+    /// The new EBR is placed right after the last logical partition, or
+    /// replaces the last EBR if that one holds no logical partition, and the
+    /// logical partition starts `gap` sectors after its EBR. `gap` must be at
+    /// least [`MIN_LOGICAL_GAP`], [`ALIGNED_LOGICAL_GAP`] is the customary
+    /// choice on modern disks. Returns the index of the new logical
+    /// partition.
+    pub fn add_logical_partition(
+        &mut self,
+        gap: u32,
+        total_sectors: u32,
+        partition_type: PartitionType,
+    ) -> Result<usize, MbrError<IO::Error>> {
+        let extended = self
+            .extended_record()
+            .ok_or(MbrError::NoExtendedPartition)?;
+
+        if gap < MIN_LOGICAL_GAP || total_sectors == 0 {
+            return Err(MbrError::OutsideExtendedPartition);
+        }
+
+        // Find the last EBR of the chain, along with the one linking to it
+        let mut last = match Ebr::read(&mut self.io, extended.relative_sector, &extended) {
+            Ok(ebr) => Some(ebr),
+            Err(MbrError::InvalidEbr(_)) => None,
+            Err(error) => return Err(error),
+        };
+        let mut previous = None;
+        let mut index = 0;
+
+        while let Some(next_lba) = last.and_then(|ebr| ebr.next_lba) {
+            if index + 1 == MAX_LOGICAL_PARTITIONS {
+                return Err(MbrError::InvalidEbr(next_lba));
+            }
+
+            previous = last;
+            last = Some(Ebr::read(&mut self.io, next_lba, &extended)?);
+            index += 1;
+        }
+
+        // A last EBR without a logical partition is reused in place,
+        // otherwise the new EBR goes right after the last logical partition
+        let (ebr_lba, index, previous) = match last {
+            Some(ebr) if !ebr.logical.is_used() => (ebr.lba, index, previous),
+            Some(ebr) => {
+                let ebr_lba = ebr
+                    .logical
+                    .relative_sector
+                    .checked_add(ebr.logical.total_sectors)
+                    .ok_or(MbrError::OutsideExtendedPartition)?;
+
+                (ebr_lba, index + 1, Some(ebr))
+            }
+            None => (extended.relative_sector, 0, None),
+        };
+
+        if index == MAX_LOGICAL_PARTITIONS {
+            return Err(MbrError::InvalidPartitionIndex(index as u32));
+        }
+
+        let logical_end = ebr_lba
+            .checked_add(gap)
+            .and_then(|lba| lba.checked_add(total_sectors))
+            .ok_or(MbrError::OutsideExtendedPartition)?;
+
+        if lba_to_u64(logical_end) > extended.get_end_pos() {
+            return Err(MbrError::OutsideExtendedPartition);
+        }
+
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+        let logical = PartitionRecord::new(gap, total_sectors, partition_type, false);
+
+        sector[RECORDS_START as usize..RECORDS_START as usize + RECORD_LEN]
+            .copy_from_slice(&logical.to_bytes());
+        sector[SIGNATURE_OFFSET as usize..].copy_from_slice(&SIGNATURE);
+
+        self.write_sector(ebr_lba, &sector)?;
+
+        // Link the new EBR from the previous one, the link covers the new
+        // EBR along with its logical partition
+        if let Some(previous) = previous {
+            let link = PartitionRecord::new(
+                ebr_lba - extended.relative_sector,
+                gap + total_sectors,
+                PartitionType::Extended,
+                false,
+            );
+            let link_i = RECORDS_START as usize + RECORD_LEN;
+
+            self.io.seek(SeekFrom::Start(lba_to_u64(previous.lba)))?;
+            self.io.read_exact(&mut sector)?;
+            sector[link_i..link_i + RECORD_LEN].copy_from_slice(&link.to_bytes());
+
+            self.write_sector(previous.lba, &sector)?;
+        }
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Read};
+
+    use crate::{
+        ebr::{MAX_LOGICAL_PARTITIONS, MIN_LOGICAL_GAP},
+        error::MbrError,
+        lba_to_u64,
+        types::PartitionType,
+        PartitionRecord, BLOCK_SIZE, MBR, RECORDS_START, RECORD_LEN, SIGNATURE, SIGNATURE_OFFSET,
+    };
+
+    /// Write records and a signature into the sector at the given LBA
+    pub fn write_table(img: &mut [u8], lba: u32, records: &[PartitionRecord]) {
+        let sector = &mut img[lba_to_u64(lba) as usize..lba_to_u64(lba + 1) as usize];
+
+        for (i, record) in records.iter().enumerate() {
+            let record_i = RECORDS_START as usize + i * RECORD_LEN;
+
+            sector[record_i..record_i + RECORD_LEN].copy_from_slice(&record.to_bytes());
+        }
+
+        sector[SIGNATURE_OFFSET as usize..].copy_from_slice(&SIGNATURE);
+    }
+
+    /// Build a 200 sector image with a primary partition at LBA 1 and an
+    /// extended partition from LBA 20 to 120 holding two logical
+    /// partitions, at LBA 22 and 52, with their number written to their
+    /// first byte
+    pub fn test_img() -> Vec<u8> {
+        let mut img = vec![0; 200 * BLOCK_SIZE as usize];
+
+        write_table(
+            &mut img,
+            0,
+            &[
+                PartitionRecord::new(1, 10, PartitionType::Linux, false),
+                PartitionRecord::new(20, 100, PartitionType::Extended, false),
+            ],
+        );
+        write_table(
+            &mut img,
+            20,
+            &[
+                PartitionRecord::new(2, 10, PartitionType::Linux, false),
+                PartitionRecord::new(30, 40, PartitionType::Extended, false),
+            ],
+        );
+        write_table(
+            &mut img,
+            50,
+            &[PartitionRecord::new(2, 20, PartitionType::Fat16, false)],
+        );
+
+        img[lba_to_u64(22) as usize] = 5;
+        img[lba_to_u64(52) as usize] = 6;
+
+        img
+    }
+
+    #[test]
+    /// Ensure that logical partitions can be reached through the EBR chain
+    fn test_logical_partitions() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(mbr.logical_partition_count().unwrap(), 2);
+
+        let ebr = mbr.get_ebr(1).unwrap();
+
+        assert_eq!(ebr.get_lba(), 50);
+        assert_eq!(ebr.get_next_lba(), None);
+        assert_eq!(ebr.get_logical().get_partition_type(), PartitionType::Fat16);
+
+        let mut buf: [u8; 1] = [0; 1];
+
+        let mut partition = mbr.get_logical_partition(0).unwrap();
+
+        assert_eq!(partition.len(), 10 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 5);
+
+        let mut partition = mbr.get_logical_partition(1).unwrap();
+
+        assert_eq!(partition.len(), 20 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 6);
+
+        assert!(matches!(
+            mbr.get_logical_partition(2),
+            Err(MbrError::InvalidPartitionIndex(2))
+        ));
+
+        let mut logicals = mbr.logical_partitions();
+
+        assert_eq!(logicals.next().unwrap().unwrap().get_lba(), 20);
+        assert_eq!(logicals.next().unwrap().unwrap().get_lba(), 50);
+        assert!(logicals.next().is_none());
+    }
+
+    #[test]
+    /// Ensure that broken and looping chains are caught
+    fn test_bad_ebr_chain() {
+        // Point the second EBR back at the first
+        let mut img = test_img();
+
+        write_table(
+            &mut img,
+            50,
+            &[
+                PartitionRecord::new(2, 20, PartitionType::Fat16, false),
+                PartitionRecord::new(0, 40, PartitionType::Extended, false),
+            ],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.logical_partition_count(),
+            Err(MbrError::InvalidEbr(_))
+        ));
+
+        // The iterator stops at the error instead of looping
+        let mut logicals = mbr.logical_partitions();
+
+        assert_eq!(
+            logicals.by_ref().take_while(|ebr| ebr.is_ok()).count(),
+            MAX_LOGICAL_PARTITIONS
+        );
+        assert!(logicals.next().is_none());
+
+        // Looking up an EBR far along the loop gives up at the same point
+        assert!(matches!(
+            mbr.get_ebr(usize::MAX),
+            Err(MbrError::InvalidPartitionIndex(u32::MAX))
+        ));
+        assert!(matches!(
+            mbr.get_ebr(MAX_LOGICAL_PARTITIONS),
+            Err(MbrError::InvalidPartitionIndex(128))
+        ));
+
+        // Point the first EBR outside of the extended partition
+        let mut img = test_img();
+
+        write_table(
+            &mut img,
+            20,
+            &[
+                PartitionRecord::new(2, 10, PartitionType::Linux, false),
+                PartitionRecord::new(150, 40, PartitionType::Extended, false),
+            ],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_logical_partition(1),
+            Err(MbrError::InvalidEbr(170))
+        ));
+
+        // A logical partition reaching past the end of the extended
+        // partition, which ends at LBA 120
+        let mut img = test_img();
+
+        write_table(
+            &mut img,
+            50,
+            &[PartitionRecord::new(2, 80, PartitionType::Fat16, false)],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(mbr.get_logical_partition(0).is_ok());
+        assert!(matches!(
+            mbr.get_logical_partition(1),
+            Err(MbrError::OutsideExtendedPartition)
+        ));
+
+        // Records at the last LBA wrap past the 32 bit LBAs
+        for records in [
+            [
+                PartitionRecord::new(u32::MAX, 10, PartitionType::Linux, false),
+                PartitionRecord::default(),
+            ],
+            [
+                PartitionRecord::new(2, 10, PartitionType::Linux, false),
+                PartitionRecord::new(u32::MAX, 40, PartitionType::Extended, false),
+            ],
+        ] {
+            let mut img = test_img();
+
+            write_table(&mut img, 20, &records);
+
+            let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+            assert!(matches!(mbr.get_ebr(0), Err(MbrError::InvalidEbr(20))));
+        }
+
+        // No extended partition at all
+        let mut img = test_img();
+
+        write_table(
+            &mut img,
+            0,
+            &[PartitionRecord::new(1, 10, PartitionType::Linux, false)],
+        );
+        img[RECORDS_START as usize + RECORD_LEN..SIGNATURE_OFFSET as usize].fill(0);
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert_eq!(mbr.logical_partition_count().unwrap(), 0);
+        assert!(matches!(
+            mbr.get_logical_partition(0),
+            Err(MbrError::NoExtendedPartition)
+        ));
+    }
+
+    #[test]
+    /// Ensure that logical partitions can be appended to the chain, both to
+    /// an empty extended partition and after existing logical partitions
+    fn test_add_logical_partition() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(
+            mbr.add_logical_partition(MIN_LOGICAL_GAP, 10, PartitionType::Linux)
+                .unwrap(),
+            2
+        );
+        assert_eq!(mbr.logical_partition_count().unwrap(), 3);
+
+        let ebr = mbr.get_ebr(2).unwrap();
+
+        assert_eq!(ebr.get_lba(), 72);
+        assert_eq!(
+            ebr.get_logical(),
+            PartitionRecord::new(73, 10, PartitionType::Linux, false)
+        );
+        assert_eq!(mbr.get_ebr(1).unwrap().get_next_lba(), Some(72));
+
+        // The existing logical partitions are left alone
+        let mut buf: [u8; 1] = [0; 1];
+
+        mbr.get_logical_partition(1)
+            .unwrap()
+            .read_exact(&mut buf)
+            .unwrap();
+        assert_eq!(buf[0], 6);
+
+        // There are only 37 sectors left in the extended partition
+        assert!(matches!(
+            mbr.add_logical_partition(MIN_LOGICAL_GAP, 37, PartitionType::Linux),
+            Err(MbrError::OutsideExtendedPartition)
+        ));
+
+        // Start from an extended partition without any EBRs
+        let mut img = test_img();
+
+        img[lba_to_u64(20) as usize..lba_to_u64(21) as usize].fill(0);
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert_eq!(mbr.logical_partition_count().ok(), None);
+        assert_eq!(
+            mbr.add_logical_partition(2, 20, PartitionType::Fat16)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            mbr.add_logical_partition(2, 20, PartitionType::Fat16)
+                .unwrap(),
+            1
+        );
+        assert_eq!(mbr.logical_partition_count().unwrap(), 2);
+        assert_eq!(
+            mbr.get_ebr(1).unwrap().get_logical(),
+            PartitionRecord::new(44, 20, PartitionType::Fat16, false)
+        );
+
+        // An extended partition holding only an empty EBR has no logical
+        // partitions until one is added
+        let mut img = test_img();
+
+        img[lba_to_u64(20) as usize..lba_to_u64(21) as usize].fill(0);
+        write_table(&mut img, 20, &[]);
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert_eq!(mbr.logical_partition_count().unwrap(), 0);
+        assert_eq!(
+            mbr.add_logical_partition(MIN_LOGICAL_GAP, 10, PartitionType::Linux)
+                .unwrap(),
+            0
+        );
+        assert_eq!(mbr.logical_partition_count().unwrap(), 1);
+        assert_eq!(mbr.get_ebr(0).unwrap().get_lba(), 20);
+
+        // A chain ending in an EBR without a logical partition reuses it
+        // instead of linking it to itself
+        let mut img = test_img();
+
+        img[lba_to_u64(50) as usize..lba_to_u64(51) as usize].fill(0);
+        write_table(&mut img, 50, &[]);
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert_eq!(
+            mbr.add_logical_partition(MIN_LOGICAL_GAP, 10, PartitionType::Linux)
+                .unwrap(),
+            1
+        );
+
+        let ebr = mbr.get_ebr(1).unwrap();
+
+        assert_eq!(ebr.get_lba(), 50);
+        assert_eq!(ebr.get_next_lba(), None);
+        assert_eq!(
+            ebr.get_logical(),
+            PartitionRecord::new(51, 10, PartitionType::Linux, false)
+        );
+        assert_eq!(mbr.get_ebr(0).unwrap().get_next_lba(), Some(50));
+        assert_eq!(mbr.logical_partition_count().unwrap(), 2);
+    }
+}
@@ -0,0 +1,146 @@
+//! Clearing whole partitions, before reformatting a slot or handing it
+//! back.
+//!
+//! [`Partition::fill`] works on any writable device by writing the same
+//! byte everywhere. Devices with a native erase, like flash or eMMC with
+//! discard, implement [`Erase`] so that [`Partition::erase`] can hand over
+//! the whole range at once instead.
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io,
+};
+
+use crate::{error::PartitionError, Partition};
+
+/// Devices that can erase a range of bytes natively
+pub trait Erase: Io {
+    /// Erase `len` bytes starting at the byte offset `start`
+    ///
+    /// What the range reads as afterwards is up to the device, erased flash
+    /// usually reads as 0xff
+    fn erase(&mut self, start: u64, len: u64) -> Result<(), Self::Error>;
+}
+
+impl<T: Erase + ?Sized> Erase for &mut T {
+    #[inline]
+    fn erase(&mut self, start: u64, len: u64) -> Result<(), Self::Error> {
+        T::erase(self, start, len)
+    }
+}
+
+impl<'a, IO: Read + Write + Seek> Partition<'a, IO> {
+    /// Set every byte of the partition to `byte`, writing in chunks the size
+    /// of `buf`
+    ///
+    /// The cursor isn't moved, and the contents of `buf` are overwritten.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty
+    pub fn fill(&mut self, byte: u8, buf: &mut [u8]) -> Result<(), PartitionError<IO::Error>> {
+        assert!(!buf.is_empty(), "fill buffer must not be empty");
+
+        if self.read_only {
+            return Err(PartitionError::ReadOnly);
+        }
+
+        buf.fill(byte);
+
+        let mut offset = 0;
+
+        while offset < self.len() {
+            let len = core::cmp::min(buf.len() as u64, self.len() - offset) as usize;
+
+            match self.write_at(offset, &buf[..len])? {
+                0 => return Err(PartitionError::OutOfSpace),
+                written => offset += written as u64,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, IO: Erase + Seek> Partition<'a, IO> {
+    /// Erase the whole partition through the device's native erase
+    ///
+    /// The cursor isn't moved. For devices without one, use
+    /// [`Partition::fill`]
+    pub fn erase(&mut self) -> Result<(), PartitionError<IO::Error>> {
+        if self.read_only {
+            return Err(PartitionError::ReadOnly);
+        }
+
+        self.io.erase(self.start_pos, self.len())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, SeekFrom};
+
+    use crate::{erase::*, PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    /// Device that erases to 0xff
+    struct Flash {
+        inner: FromStd<Cursor<Vec<u8>>>,
+    }
+
+    impl Io for Flash {
+        type Error = std::io::Error;
+    }
+
+    impl Seek for Flash {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl Erase for Flash {
+        fn erase(&mut self, start: u64, len: u64) -> Result<(), Self::Error> {
+            self.inner.inner_mut().get_mut()[start as usize..(start + len) as usize].fill(0xff);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// Ensure that partitions are cleared entirely, without touching their
+    /// neighbours
+    fn test_fill_and_erase() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::Two).unwrap();
+        let mut buf: [u8; 1000] = [0xaa; 1000];
+
+        partition.seek(SeekFrom::Start(7)).unwrap();
+        partition.fill(0, &mut buf).unwrap();
+
+        assert_eq!(partition.get_position(), 7);
+
+        let img = mbr.into_inner().into_inner().into_inner();
+        let (start, end) = (18 * 512, 51 * 512);
+
+        assert!(img[start..end].iter().all(|b| *b == 0));
+        assert_eq!(img[..start], TEST_IMG_1[..start]);
+        assert_eq!(img[end..], TEST_IMG_1[end..]);
+
+        let mut flash = Flash {
+            inner: FromStd::new(Cursor::new(img)),
+        };
+        let mut partition = Partition::new(start as u64, end as u64, &mut flash).unwrap();
+
+        partition.erase().unwrap();
+
+        let img = flash.inner.into_inner().into_inner();
+
+        assert!(img[start..end].iter().all(|b| *b == 0xff));
+        assert_eq!(img[end..], TEST_IMG_1[end..]);
+    }
+}
@@ -0,0 +1,261 @@
+//! Errors that can be returned when working with the MBR.
+
+use core::fmt;
+
+use embedded_io::{blocking::ReadExactError, ErrorKind};
+
+use crate::PartitionId;
+
+/// Error returned when the MBR can't be used
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MbrError<E> {
+    /// The underlying device returned an error
+    Io(E),
+    /// The device ended before all of the metadata could be read
+    UnexpectedEof,
+    /// The 0x55AA boot signature is missing
+    MissingSignature,
+    /// A partition extends past the end of the device
+    PastEndOfDevice(PartitionId),
+    /// The given backup sector would overwrite the table or a partition
+    InvalidBackupLba(u32),
+    /// The GPT header signature is missing
+    MissingGptSignature,
+    /// The GPT header contains sizes that can't be used
+    InvalidGptHeader,
+    /// The CRC32 of the GPT header doesn't match the one stored in it
+    GptHeaderCrcMismatch,
+    /// The CRC32 of the GPT partition entries doesn't match the one stored
+    /// in the header
+    GptEntriesCrcMismatch,
+    /// The device is too small to hold the requested layout
+    DeviceTooSmall,
+    /// Every partition entry is already in use
+    NoFreeEntries,
+    /// The partition entry doesn't fit within the usable LBAs
+    OutsideUsableRange,
+    /// The partition entry overlaps the entry with the given index
+    EntriesOverlap(u32),
+    /// More GPT entries were given than fit in a hybrid MBR
+    TooManyHybridEntries,
+    /// The partition can't be addressed with the 32 bit LBAs of an MBR
+    ExceedsMbrAddressing,
+    /// There is no extended partition holding logical partitions
+    NoExtendedPartition,
+    /// The EBR at the given LBA is missing or outside of the extended
+    /// partition
+    InvalidEbr(u32),
+    /// There is no partition entry with the given index
+    InvalidPartitionIndex(u32),
+    /// The requested partition doesn't cover any sectors
+    EmptyPartition,
+    /// The logical partition doesn't fit within the extended partition
+    OutsideExtendedPartition,
+    /// The partition isn't a BSD slice, or its disklabel magic is missing
+    MissingDisklabel,
+    /// The disklabel has a bad checksum, or entries that can't be used
+    InvalidDisklabel,
+    /// The APM driver descriptor map signature is missing
+    MissingApmSignature,
+    /// The APM entry with the given index is missing its signature
+    InvalidApmEntry(u32),
+    /// The partition isn't a Minix partition holding a valid subpartition
+    /// table
+    InvalidMinixTable,
+    /// The partition ends before it starts, or isn't aligned to sectors
+    InvalidPartitionBounds,
+    /// A record describes a range that ends before it starts, or that can't
+    /// be given as a byte offset
+    CorruptRecord,
+    /// The partition would include the sector holding its partition table
+    OverlapsTable,
+    /// The sfdisk script has a line that can't be used, at the given line
+    /// number
+    InvalidScript(u32),
+}
+
+impl<E> MbrError<E> {
+    /// Convert the device error held by [`MbrError::Io`], keeping every
+    /// other variant as is
+    pub fn map_io<F>(self, f: impl FnOnce(E) -> F) -> MbrError<F> {
+        match self {
+            MbrError::Io(error) => MbrError::Io(f(error)),
+            MbrError::UnexpectedEof => MbrError::UnexpectedEof,
+            MbrError::MissingSignature => MbrError::MissingSignature,
+            MbrError::PastEndOfDevice(value) => MbrError::PastEndOfDevice(value),
+            MbrError::InvalidBackupLba(value) => MbrError::InvalidBackupLba(value),
+            MbrError::MissingGptSignature => MbrError::MissingGptSignature,
+            MbrError::InvalidGptHeader => MbrError::InvalidGptHeader,
+            MbrError::GptHeaderCrcMismatch => MbrError::GptHeaderCrcMismatch,
+            MbrError::GptEntriesCrcMismatch => MbrError::GptEntriesCrcMismatch,
+            MbrError::DeviceTooSmall => MbrError::DeviceTooSmall,
+            MbrError::NoFreeEntries => MbrError::NoFreeEntries,
+            MbrError::OutsideUsableRange => MbrError::OutsideUsableRange,
+            MbrError::EntriesOverlap(value) => MbrError::EntriesOverlap(value),
+            MbrError::TooManyHybridEntries => MbrError::TooManyHybridEntries,
+            MbrError::ExceedsMbrAddressing => MbrError::ExceedsMbrAddressing,
+            MbrError::NoExtendedPartition => MbrError::NoExtendedPartition,
+            MbrError::InvalidEbr(value) => MbrError::InvalidEbr(value),
+            MbrError::InvalidPartitionIndex(value) => MbrError::InvalidPartitionIndex(value),
+            MbrError::EmptyPartition => MbrError::EmptyPartition,
+            MbrError::OutsideExtendedPartition => MbrError::OutsideExtendedPartition,
+            MbrError::MissingDisklabel => MbrError::MissingDisklabel,
+            MbrError::InvalidDisklabel => MbrError::InvalidDisklabel,
+            MbrError::MissingApmSignature => MbrError::MissingApmSignature,
+            MbrError::InvalidApmEntry(value) => MbrError::InvalidApmEntry(value),
+            MbrError::InvalidMinixTable => MbrError::InvalidMinixTable,
+            MbrError::InvalidPartitionBounds => MbrError::InvalidPartitionBounds,
+            MbrError::CorruptRecord => MbrError::CorruptRecord,
+            MbrError::OverlapsTable => MbrError::OverlapsTable,
+            MbrError::InvalidScript(value) => MbrError::InvalidScript(value),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for MbrError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MbrError::Io(error) => write!(f, "device error: {:?}", error),
+            MbrError::UnexpectedEof => {
+                f.write_str("device ended before the metadata could be read")
+            }
+            MbrError::MissingSignature => f.write_str("missing 0x55AA boot signature"),
+            MbrError::PastEndOfDevice(value) => write!(
+                f,
+                "partition {:?} extends past the end of the device",
+                value
+            ),
+            MbrError::InvalidBackupLba(value) => write!(
+                f,
+                "backup sector {} would overwrite the table or a partition",
+                value
+            ),
+            MbrError::MissingGptSignature => f.write_str("missing GPT header signature"),
+            MbrError::InvalidGptHeader => f.write_str("GPT header contains unusable sizes"),
+            MbrError::GptHeaderCrcMismatch => f.write_str("GPT header CRC32 mismatch"),
+            MbrError::GptEntriesCrcMismatch => f.write_str("GPT partition entries CRC32 mismatch"),
+            MbrError::DeviceTooSmall => f.write_str("device is too small for the requested layout"),
+            MbrError::NoFreeEntries => f.write_str("every partition entry is in use"),
+            MbrError::OutsideUsableRange => {
+                f.write_str("partition entry is outside of the usable LBAs")
+            }
+            MbrError::EntriesOverlap(value) => {
+                write!(f, "partition entry overlaps entry {}", value)
+            }
+            MbrError::TooManyHybridEntries => f.write_str("too many entries for a hybrid MBR"),
+            MbrError::ExceedsMbrAddressing => {
+                f.write_str("partition is beyond 32 bit LBA addressing")
+            }
+            MbrError::NoExtendedPartition => f.write_str("no extended partition"),
+            MbrError::InvalidEbr(value) => write!(f, "missing or misplaced EBR at LBA {}", value),
+            MbrError::InvalidPartitionIndex(value) => {
+                write!(f, "no partition with index {}", value)
+            }
+            MbrError::EmptyPartition => f.write_str("partition is empty"),
+            MbrError::OutsideExtendedPartition => {
+                f.write_str("logical partition doesn't fit in the extended partition")
+            }
+            MbrError::MissingDisklabel => f.write_str("missing BSD disklabel"),
+            MbrError::InvalidDisklabel => f.write_str("corrupt BSD disklabel"),
+            MbrError::MissingApmSignature => f.write_str("missing APM signature"),
+            MbrError::InvalidApmEntry(value) => {
+                write!(f, "missing signature in APM entry {}", value)
+            }
+            MbrError::InvalidMinixTable => {
+                f.write_str("missing or corrupt Minix subpartition table")
+            }
+            MbrError::InvalidPartitionBounds => {
+                f.write_str("partition ends before it starts or isn't aligned to sectors")
+            }
+            MbrError::CorruptRecord => {
+                f.write_str("record describes a range that can't be addressed")
+            }
+            MbrError::OverlapsTable => f.write_str("partition includes its own partition table"),
+            MbrError::InvalidScript(value) => write!(f, "invalid sfdisk script line {}", value),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for MbrError<E> {}
+
+impl<E> From<E> for MbrError<E> {
+    fn from(error: E) -> Self {
+        MbrError::Io(error)
+    }
+}
+
+impl<E> From<ReadExactError<E>> for MbrError<E> {
+    fn from(error: ReadExactError<E>) -> Self {
+        match error {
+            ReadExactError::UnexpectedEof => MbrError::UnexpectedEof,
+            ReadExactError::Other(error) => MbrError::Io(error),
+        }
+    }
+}
+
+/// Error returned when reading, writing or seeking within a
+/// [`Partition`](crate::Partition)
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PartitionError<E> {
+    /// The underlying device returned an error
+    Io(E),
+    /// A write was attempted at the end of the partition
+    OutOfSpace,
+    /// The end of the partition was reached before a buffer was filled
+    UnexpectedEof,
+    /// A seek would have moved before the start of the partition, or
+    /// overflowed
+    InvalidSeek,
+    /// A seek would have moved past the end of the partition, with
+    /// [`SeekPolicy::Error`](crate::SeekPolicy::Error) set
+    SeekPastEnd,
+    /// A write was attempted on a read-only partition
+    ReadOnly,
+    /// A read or write didn't cover whole sectors, on a partition set to
+    /// only allow those
+    Unaligned,
+}
+
+impl<E: fmt::Debug> fmt::Display for PartitionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionError::Io(error) => write!(f, "device error: {:?}", error),
+            PartitionError::OutOfSpace => f.write_str("no space left in the partition"),
+            PartitionError::UnexpectedEof => f.write_str("reached the end of the partition"),
+            PartitionError::InvalidSeek => f.write_str("seek before the start of the partition"),
+            PartitionError::SeekPastEnd => f.write_str("seek past the end of the partition"),
+            PartitionError::ReadOnly => f.write_str("partition is read-only"),
+            PartitionError::Unaligned => f.write_str("transfer doesn't cover whole sectors"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for PartitionError<E> {}
+
+impl<E: embedded_io::Error> embedded_io::Error for PartitionError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            PartitionError::Io(error) => error.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl<E> From<E> for PartitionError<E> {
+    fn from(error: E) -> Self {
+        PartitionError::Io(error)
+    }
+}
+
+impl<E> From<ReadExactError<PartitionError<E>>> for PartitionError<E> {
+    fn from(error: ReadExactError<PartitionError<E>>) -> Self {
+        match error {
+            ReadExactError::UnexpectedEof => PartitionError::UnexpectedEof,
+            ReadExactError::Other(error) => error,
+        }
+    }
+}
@@ -0,0 +1,1948 @@
+//! Support for disks partitioned with a GUID Partition Table.
+//!
+//! Only the header is kept in memory, partition entries are read from the
+//! disk as they are needed.
+
+use core::cmp;
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io, SeekFrom,
+};
+
+use crate::{
+    crc32::{crc32, Crc32},
+    error::MbrError,
+    types::{GptPartitionType, PartitionType},
+    Partition, PartitionRecord, BLOCK_SIZE, END_CHS_OFFSET, MBR, RECORDS_START, RECORD_COUNT,
+    RECORD_LEN, RELATIVE_SECTOR_OFFSET, SIGNATURE, SIGNATURE_OFFSET, START_CHS_OFFSET,
+    SYSTEM_ID_OFFSET,
+};
+
+/// Signature found at the start of a GPT header
+pub const GPT_SIGNATURE: [u8; 8] = *b"EFI PART";
+/// LBA of the primary GPT header
+pub const PRIMARY_HEADER_LBA: u64 = 1;
+/// LBA of the primary partition entries
+pub const PRIMARY_ENTRIES_LBA: u64 = 2;
+/// Revision written to new GPT headers, 1.0
+pub const GPT_REVISION: u32 = 0x00010000;
+/// Starting CHS address of the protective MBR record
+pub const PROTECTIVE_START_CHS: [u8; 3] = [0x00, 0x02, 0x00];
+/// Ending CHS address of the protective MBR record
+pub const PROTECTIVE_END_CHS: [u8; 3] = [0xff, 0xff, 0xff];
+/// Number of partition entries in a newly formatted GPT
+pub const DEFAULT_ENTRY_COUNT: u32 = 128;
+/// Minimum length of a GPT header in bytes
+pub const MIN_HEADER_LEN: u32 = 92;
+/// Minimum length of a GPT partition entry in bytes
+pub const MIN_ENTRY_LEN: u32 = 128;
+/// Length of a GUID in bytes
+pub const GUID_LEN: usize = 16;
+
+/// Offset of the revision field in the GPT header
+pub const REVISION_OFFSET: usize = 8;
+/// Offset of the header size field in the GPT header
+pub const HEADER_LEN_OFFSET: usize = 12;
+/// Offset of the header CRC32 field in the GPT header
+pub const HEADER_CRC_OFFSET: usize = 16;
+/// Offset of the current LBA field in the GPT header
+pub const CURRENT_LBA_OFFSET: usize = 24;
+/// Offset of the backup LBA field in the GPT header
+pub const BACKUP_LBA_OFFSET: usize = 32;
+/// Offset of the first usable LBA field in the GPT header
+pub const FIRST_USABLE_LBA_OFFSET: usize = 40;
+/// Offset of the last usable LBA field in the GPT header
+pub const LAST_USABLE_LBA_OFFSET: usize = 48;
+/// Offset of the disk GUID field in the GPT header
+pub const DISK_GUID_OFFSET: usize = 56;
+/// Offset of the partition entries LBA field in the GPT header
+pub const ENTRIES_LBA_OFFSET: usize = 72;
+/// Offset of the number of partition entries field in the GPT header
+pub const ENTRY_COUNT_OFFSET: usize = 80;
+/// Offset of the partition entry size field in the GPT header
+pub const ENTRY_LEN_OFFSET: usize = 84;
+/// Offset of the partition entries CRC32 field in the GPT header
+pub const ENTRIES_CRC_OFFSET: usize = 88;
+
+/// Offset of the partition type GUID in a partition entry
+pub const TYPE_GUID_OFFSET: usize = 0;
+/// Offset of the unique partition GUID in a partition entry
+pub const UNIQUE_GUID_OFFSET: usize = 16;
+/// Offset of the first LBA in a partition entry
+pub const FIRST_LBA_OFFSET: usize = 32;
+/// Offset of the last LBA in a partition entry
+pub const LAST_LBA_OFFSET: usize = 40;
+/// Offset of the attribute flags in a partition entry
+pub const ATTRIBUTES_OFFSET: usize = 48;
+/// Offset of the partition name in a partition entry
+pub const NAME_OFFSET: usize = 56;
+/// Length of the partition name in bytes
+pub const NAME_LEN: usize = 72;
+
+#[inline]
+/// Read a little endian u32 from a slice at the given offset
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline]
+/// Read a little endian u64 from a slice at the given offset
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[inline]
+/// Write a little endian u32 into a slice at the given offset
+pub(crate) fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+/// Write a little endian u64 into a slice at the given offset
+pub(crate) fn write_u64(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Largest 64 bit LBA whose end can still be given as a byte offset
+pub const MAX_LBA: u64 = u64::MAX / BLOCK_SIZE - 1;
+
+#[inline]
+/// Convert a 64 bit LBA address to a byte offset
+///
+/// LBAs past [`MAX_LBA`] saturate instead of overflowing
+pub fn lba64_to_u64(lba: u64) -> u64 {
+    lba.saturating_mul(BLOCK_SIZE)
+}
+
+/// A GUID, stored in the mixed-endian layout used on disk
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; GUID_LEN]);
+
+impl Guid {
+    /// The all-zero GUID, used to mark unused partition entries
+    pub const ZERO: Guid = Guid([0; GUID_LEN]);
+
+    /// Create a GUID from the fields of its textual form, eg.
+    /// `C12A7328-F81F-11D2-BA4B-00A0C93EC93B` is
+    /// `Guid::from_fields(0xc12a7328, 0xf81f, 0x11d2, 0xba4b00a0c93ec93b)`
+    pub const fn from_fields(data1: u32, data2: u16, data3: u16, data4: u64) -> Self {
+        let d1 = data1.to_le_bytes();
+        let d2 = data2.to_le_bytes();
+        let d3 = data3.to_le_bytes();
+        let d4 = data4.to_be_bytes();
+
+        Self([
+            d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], d4[0], d4[1], d4[2], d4[3],
+            d4[4], d4[5], d4[6], d4[7],
+        ])
+    }
+
+    /// Create a random (version 4) GUID from 16 bytes of entropy supplied
+    /// by the caller
+    pub fn from_random(mut random: [u8; GUID_LEN]) -> Self {
+        // Version 4 in the high nibble of data3, which is stored little
+        // endian, and the RFC 4122 variant in the top bits of data4
+        random[7] = (random[7] & 0x0f) | 0x40;
+        random[8] = (random[8] & 0x3f) | 0x80;
+
+        Self(random)
+    }
+
+    /// Create a GUID from its on-disk bytes
+    pub fn from_bytes(bytes: &[u8; GUID_LEN]) -> Self {
+        Self(*bytes)
+    }
+
+    #[inline]
+    /// Check if the GUID is all zeros
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+}
+
+/// Attribute flags of a GPT partition entry
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct GptAttributes(u64);
+
+impl GptAttributes {
+    /// The partition is required for the platform to function
+    pub const REQUIRED: u64 = 1 << 0;
+    /// Firmware shouldn't produce a block IO protocol for the partition
+    pub const NO_BLOCK_IO_PROTOCOL: u64 = 1 << 1;
+    /// Legacy BIOS firmware may boot from the partition
+    pub const LEGACY_BIOS_BOOTABLE: u64 = 1 << 2;
+    /// Shift of the bits whose meaning depends on the partition type
+    pub const TYPE_SPECIFIC_SHIFT: u32 = 48;
+
+    #[inline]
+    /// Create attributes from their raw bits
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    #[inline]
+    /// Get the raw bits of the attributes
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    /// Check if the required partition flag is set
+    pub fn is_required(&self) -> bool {
+        self.0 & Self::REQUIRED != 0
+    }
+
+    #[inline]
+    /// Check if the no block IO protocol flag is set
+    pub fn is_no_block_io_protocol(&self) -> bool {
+        self.0 & Self::NO_BLOCK_IO_PROTOCOL != 0
+    }
+
+    #[inline]
+    /// Check if the legacy BIOS bootable flag is set
+    pub fn is_legacy_bios_bootable(&self) -> bool {
+        self.0 & Self::LEGACY_BIOS_BOOTABLE != 0
+    }
+
+    #[inline]
+    /// Get the 16 high bits, whose meaning depends on the partition type
+    pub fn get_type_specific(&self) -> u16 {
+        (self.0 >> Self::TYPE_SPECIFIC_SHIFT) as u16
+    }
+
+    #[inline]
+    /// Set or clear the required partition flag
+    pub fn set_required(&mut self, value: bool) {
+        self.set_bit(Self::REQUIRED, value);
+    }
+
+    #[inline]
+    /// Set or clear the no block IO protocol flag
+    pub fn set_no_block_io_protocol(&mut self, value: bool) {
+        self.set_bit(Self::NO_BLOCK_IO_PROTOCOL, value);
+    }
+
+    #[inline]
+    /// Set or clear the legacy BIOS bootable flag
+    pub fn set_legacy_bios_bootable(&mut self, value: bool) {
+        self.set_bit(Self::LEGACY_BIOS_BOOTABLE, value);
+    }
+
+    #[inline]
+    /// Set the 16 high bits, whose meaning depends on the partition type
+    pub fn set_type_specific(&mut self, value: u16) {
+        self.0 &= !(0xffff << Self::TYPE_SPECIFIC_SHIFT);
+        self.0 |= (value as u64) << Self::TYPE_SPECIFIC_SHIFT;
+    }
+
+    #[inline]
+    /// Set or clear a single bit
+    fn set_bit(&mut self, bit: u64, value: bool) {
+        match value {
+            true => self.0 |= bit,
+            false => self.0 &= !bit,
+        }
+    }
+}
+
+/// Used to store the fields of a GPT header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GptHeader {
+    revision: u32,
+    header_len: u32,
+    header_crc: u32,
+    current_lba: u64,
+    backup_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    disk_guid: Guid,
+    entries_lba: u64,
+    entry_count: u32,
+    entry_len: u32,
+    entries_crc: u32,
+}
+
+impl GptHeader {
+    /// Create a GPT header from the bytes of its sector
+    ///
+    /// Returns `None` if the signature is missing
+    pub fn from_bytes(bytes: &[u8; BLOCK_SIZE as usize]) -> Option<Self> {
+        if bytes[..GPT_SIGNATURE.len()] != GPT_SIGNATURE {
+            return None;
+        }
+
+        Some(Self {
+            revision: read_u32(bytes, REVISION_OFFSET),
+            header_len: read_u32(bytes, HEADER_LEN_OFFSET),
+            header_crc: read_u32(bytes, HEADER_CRC_OFFSET),
+            current_lba: read_u64(bytes, CURRENT_LBA_OFFSET),
+            backup_lba: read_u64(bytes, BACKUP_LBA_OFFSET),
+            first_usable_lba: read_u64(bytes, FIRST_USABLE_LBA_OFFSET),
+            last_usable_lba: read_u64(bytes, LAST_USABLE_LBA_OFFSET),
+            disk_guid: Guid::from_bytes(
+                bytes[DISK_GUID_OFFSET..DISK_GUID_OFFSET + GUID_LEN]
+                    .try_into()
+                    .unwrap(),
+            ),
+            entries_lba: read_u64(bytes, ENTRIES_LBA_OFFSET),
+            entry_count: read_u32(bytes, ENTRY_COUNT_OFFSET),
+            entry_len: read_u32(bytes, ENTRY_LEN_OFFSET),
+            entries_crc: read_u32(bytes, ENTRIES_CRC_OFFSET),
+        })
+    }
+
+    /// Compute the CRC32 of a header sector, treating the CRC field as zero
+    ///
+    /// Returns `None` if the header length is invalid
+    pub fn compute_crc(bytes: &[u8; BLOCK_SIZE as usize]) -> Option<u32> {
+        let header_len = read_u32(bytes, HEADER_LEN_OFFSET);
+
+        if header_len < MIN_HEADER_LEN || header_len as u64 > BLOCK_SIZE {
+            return None;
+        }
+
+        let mut header = *bytes;
+
+        header[HEADER_CRC_OFFSET..HEADER_CRC_OFFSET + 4].fill(0);
+
+        Some(crc32(&header[..header_len as usize]))
+    }
+
+    #[inline]
+    /// Get the length of the partition entry array in bytes
+    pub fn get_entries_len(&self) -> u64 {
+        (self.entry_count as u64) * (self.entry_len as u64)
+    }
+
+    /// Convert the header to the bytes of its sector
+    ///
+    /// The header CRC32 is computed from the other fields rather than
+    /// copied
+    pub fn to_bytes(&self) -> [u8; BLOCK_SIZE as usize] {
+        let mut bytes: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        bytes[..GPT_SIGNATURE.len()].copy_from_slice(&GPT_SIGNATURE);
+        write_u32(&mut bytes, REVISION_OFFSET, self.revision);
+        write_u32(&mut bytes, HEADER_LEN_OFFSET, self.header_len);
+        write_u64(&mut bytes, CURRENT_LBA_OFFSET, self.current_lba);
+        write_u64(&mut bytes, BACKUP_LBA_OFFSET, self.backup_lba);
+        write_u64(&mut bytes, FIRST_USABLE_LBA_OFFSET, self.first_usable_lba);
+        write_u64(&mut bytes, LAST_USABLE_LBA_OFFSET, self.last_usable_lba);
+        bytes[DISK_GUID_OFFSET..DISK_GUID_OFFSET + GUID_LEN].copy_from_slice(&self.disk_guid.0);
+        write_u64(&mut bytes, ENTRIES_LBA_OFFSET, self.entries_lba);
+        write_u32(&mut bytes, ENTRY_COUNT_OFFSET, self.entry_count);
+        write_u32(&mut bytes, ENTRY_LEN_OFFSET, self.entry_len);
+        write_u32(&mut bytes, ENTRIES_CRC_OFFSET, self.entries_crc);
+
+        let header_crc = Self::compute_crc(&bytes).unwrap_or(0);
+
+        write_u32(&mut bytes, HEADER_CRC_OFFSET, header_crc);
+
+        bytes
+    }
+
+    /// Get the header that belongs at the other end of the disk, with its
+    /// partition entries at the given LBA
+    pub fn mirrored(&self, entries_lba: u64) -> Self {
+        let mut header = Self {
+            current_lba: self.backup_lba,
+            backup_lba: self.current_lba,
+            entries_lba,
+            ..*self
+        };
+
+        header.header_crc = Self::compute_crc(&header.to_bytes()).unwrap_or(0);
+
+        header
+    }
+
+    /// Check that the sizes and LBAs given in the header are usable
+    pub fn is_valid(&self) -> bool {
+        let lbas = [
+            self.current_lba,
+            self.backup_lba,
+            self.first_usable_lba,
+            self.last_usable_lba,
+            self.entries_lba,
+        ];
+
+        self.header_len >= MIN_HEADER_LEN
+            && self.header_len as u64 <= BLOCK_SIZE
+            && self.entry_len >= MIN_ENTRY_LEN
+            && self.entry_len as u64 <= BLOCK_SIZE
+            && self.entry_len.is_power_of_two()
+            && lbas.iter().all(|lba| *lba <= MAX_LBA)
+            && lba64_to_u64(self.entries_lba)
+                .checked_add(self.get_entries_len())
+                .is_some()
+    }
+
+    #[inline]
+    /// Get the revision of the header
+    pub fn get_revision(&self) -> u32 {
+        self.revision
+    }
+
+    #[inline]
+    /// Get the length of the header in bytes
+    pub fn get_header_len(&self) -> u32 {
+        self.header_len
+    }
+
+    #[inline]
+    /// Get the CRC32 of the header as stored on the disk
+    pub fn get_header_crc(&self) -> u32 {
+        self.header_crc
+    }
+
+    #[inline]
+    /// Get the LBA of this copy of the header
+    pub fn get_current_lba(&self) -> u64 {
+        self.current_lba
+    }
+
+    #[inline]
+    /// Get the LBA of the other copy of the header
+    pub fn get_backup_lba(&self) -> u64 {
+        self.backup_lba
+    }
+
+    #[inline]
+    /// Get the first LBA that can be used by a partition
+    pub fn get_first_usable_lba(&self) -> u64 {
+        self.first_usable_lba
+    }
+
+    #[inline]
+    /// Get the last LBA that can be used by a partition
+    pub fn get_last_usable_lba(&self) -> u64 {
+        self.last_usable_lba
+    }
+
+    #[inline]
+    /// Get the GUID of the disk
+    pub fn get_disk_guid(&self) -> Guid {
+        self.disk_guid
+    }
+
+    #[inline]
+    /// Get the LBA the partition entries start at
+    pub fn get_entries_lba(&self) -> u64 {
+        self.entries_lba
+    }
+
+    #[inline]
+    /// Get the number of partition entries
+    pub fn get_entry_count(&self) -> u32 {
+        self.entry_count
+    }
+
+    #[inline]
+    /// Get the length of each partition entry in bytes
+    pub fn get_entry_len(&self) -> u32 {
+        self.entry_len
+    }
+
+    #[inline]
+    /// Get the CRC32 of the partition entries as stored on the disk
+    pub fn get_entries_crc(&self) -> u32 {
+        self.entries_crc
+    }
+
+    #[inline]
+    /// Get the byte offset of a partition entry
+    pub fn get_entry_pos(&self, index: u32) -> u64 {
+        lba64_to_u64(self.entries_lba) + (index as u64) * (self.entry_len as u64)
+    }
+}
+
+/// Used to store data about partitions in the GPT
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GptEntry {
+    type_guid: Guid,
+    unique_guid: Guid,
+    first_lba: u64,
+    last_lba: u64,
+    attributes: u64,
+    name: [u8; NAME_LEN],
+}
+
+impl GptEntry {
+    /// An unused partition entry
+    pub const EMPTY: GptEntry = GptEntry {
+        type_guid: Guid::ZERO,
+        unique_guid: Guid::ZERO,
+        first_lba: 0,
+        last_lba: 0,
+        attributes: 0,
+        name: [0; NAME_LEN],
+    };
+
+    /// Create a new partition entry covering `first_lba` to `last_lba`
+    /// inclusive
+    ///
+    /// Names longer than 36 UTF-16 code units are truncated
+    pub fn new(
+        partition_type: GptPartitionType,
+        unique_guid: Guid,
+        first_lba: u64,
+        last_lba: u64,
+        name: &str,
+    ) -> Self {
+        let mut entry = Self {
+            type_guid: partition_type.guid(),
+            unique_guid,
+            first_lba,
+            last_lba,
+            ..Self::EMPTY
+        };
+
+        entry.set_name(name);
+
+        entry
+    }
+
+    /// Create a partition entry from bytes
+    pub fn from_bytes(bytes: &[u8; MIN_ENTRY_LEN as usize]) -> Self {
+        Self {
+            type_guid: Guid::from_bytes(
+                bytes[TYPE_GUID_OFFSET..TYPE_GUID_OFFSET + GUID_LEN]
+                    .try_into()
+                    .unwrap(),
+            ),
+            unique_guid: Guid::from_bytes(
+                bytes[UNIQUE_GUID_OFFSET..UNIQUE_GUID_OFFSET + GUID_LEN]
+                    .try_into()
+                    .unwrap(),
+            ),
+            first_lba: read_u64(bytes, FIRST_LBA_OFFSET),
+            last_lba: read_u64(bytes, LAST_LBA_OFFSET),
+            attributes: read_u64(bytes, ATTRIBUTES_OFFSET),
+            name: bytes[NAME_OFFSET..NAME_OFFSET + NAME_LEN]
+                .try_into()
+                .unwrap(),
+        }
+    }
+
+    /// Convert a partition entry to bytes
+    pub fn to_bytes(&self) -> [u8; MIN_ENTRY_LEN as usize] {
+        let mut bytes: [u8; MIN_ENTRY_LEN as usize] = [0; MIN_ENTRY_LEN as usize];
+
+        bytes[TYPE_GUID_OFFSET..TYPE_GUID_OFFSET + GUID_LEN].copy_from_slice(&self.type_guid.0);
+        bytes[UNIQUE_GUID_OFFSET..UNIQUE_GUID_OFFSET + GUID_LEN]
+            .copy_from_slice(&self.unique_guid.0);
+        write_u64(&mut bytes, FIRST_LBA_OFFSET, self.first_lba);
+        write_u64(&mut bytes, LAST_LBA_OFFSET, self.last_lba);
+        write_u64(&mut bytes, ATTRIBUTES_OFFSET, self.attributes);
+        bytes[NAME_OFFSET..NAME_OFFSET + NAME_LEN].copy_from_slice(&self.name);
+
+        bytes
+    }
+
+    #[inline]
+    /// Check if the entry describes a partition
+    pub fn is_used(&self) -> bool {
+        !self.type_guid.is_zero()
+    }
+
+    #[inline]
+    /// Check if two entries share at least one sector
+    pub fn overlaps(&self, other: &GptEntry) -> bool {
+        self.is_used()
+            && other.is_used()
+            && self.first_lba <= other.last_lba
+            && other.first_lba <= self.last_lba
+    }
+
+    /// Set the name of the partition
+    ///
+    /// Names longer than 36 UTF-16 code units are truncated
+    pub fn set_name(&mut self, name: &str) {
+        self.name = [0; NAME_LEN];
+
+        for (unit, bytes) in name.encode_utf16().zip(self.name.chunks_exact_mut(2)) {
+            bytes.copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    #[inline]
+    /// Set the range of the partition, from `first_lba` to `last_lba`
+    /// inclusive
+    pub fn set_range(&mut self, first_lba: u64, last_lba: u64) {
+        self.first_lba = first_lba;
+        self.last_lba = last_lba;
+    }
+
+    #[inline]
+    /// Set the attribute flags of the partition
+    pub fn set_attributes(&mut self, attributes: GptAttributes) {
+        self.attributes = attributes.bits();
+    }
+
+    #[inline]
+    /// Get the partition type GUID
+    pub fn get_type_guid(&self) -> Guid {
+        self.type_guid
+    }
+
+    #[inline]
+    /// Get the type of the partition
+    pub fn get_partition_type(&self) -> GptPartitionType {
+        GptPartitionType::from_guid(self.type_guid)
+    }
+
+    #[inline]
+    /// Get the GUID unique to this partition
+    pub fn get_unique_guid(&self) -> Guid {
+        self.unique_guid
+    }
+
+    #[inline]
+    /// Get the first LBA of the partition
+    pub fn get_first_lba(&self) -> u64 {
+        self.first_lba
+    }
+
+    #[inline]
+    /// Get the last LBA of the partition, which is inclusive
+    pub fn get_last_lba(&self) -> u64 {
+        self.last_lba
+    }
+
+    #[inline]
+    /// Get the attribute flags of the partition
+    pub fn get_attributes(&self) -> GptAttributes {
+        GptAttributes::from_bits(self.attributes)
+    }
+
+    #[inline]
+    /// Get the raw UTF-16LE name of the partition
+    pub fn get_raw_name(&self) -> &[u8; NAME_LEN] {
+        &self.name
+    }
+
+    /// Get the name of the partition, decoded from UTF-16LE as it's read
+    ///
+    /// The name ends at the first null character, and invalid UTF-16 is
+    /// replaced with `char::REPLACEMENT_CHARACTER`
+    pub fn name(&self) -> impl Iterator<Item = char> + '_ {
+        let units = self
+            .name
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|u| *u != 0);
+
+        char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Check if the name of the partition matches the given string
+    pub fn name_eq(&self, name: &str) -> bool {
+        self.name().eq(name.chars())
+    }
+
+    #[inline]
+    /// Get the starting position of the partition
+    pub fn get_start_pos(&self) -> u64 {
+        lba64_to_u64(self.first_lba)
+    }
+
+    #[inline]
+    /// Get the end position of the partition
+    pub fn get_end_pos(&self) -> u64 {
+        lba64_to_u64(self.last_lba.saturating_add(1))
+    }
+}
+
+/// Number of GPT entries that can be mirrored into a hybrid MBR
+pub const HYBRID_ENTRY_COUNT: usize = RECORD_COUNT - 1;
+
+/// A GPT entry to mirror into a hybrid MBR
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HybridEntry {
+    /// Index of the GPT entry
+    pub index: u32,
+    /// Type to give the MBR record
+    pub partition_type: PartitionType,
+    /// Whether to set the boot flag of the MBR record
+    pub bootable: bool,
+}
+
+/// Used to grab partitions from the GPT
+pub struct GPT<IO: Read + Seek> {
+    header: GptHeader,
+    from_backup: bool,
+    hybrid: [Option<HybridEntry>; HYBRID_ENTRY_COUNT],
+    io: IO,
+}
+
+impl<IO: Read + Seek> GPT<IO> {
+    /// Create a new GPT from anything that implements embedded_io
+    ///
+    /// The CRC32 of both the header and the partition entries is verified.
+    /// If the primary header is unusable the backup header at the end of
+    /// the disk is used instead
+    pub fn new(io: IO) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        Self::open(io, true)
+    }
+
+    /// Create a new GPT without verifying any CRC32s
+    ///
+    /// Useful on targets where reading the whole partition entry array on
+    /// open is too slow
+    pub fn new_unchecked(io: IO) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        Self::open(io, false)
+    }
+
+    /// Read the primary header, falling back to the backup header
+    fn open(mut io: IO, verify: bool) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        let (header, from_backup) = match read_header(&mut io, PRIMARY_HEADER_LBA, verify) {
+            Ok(header) => (header, false),
+            Err(MbrError::Io(error)) => return Err(MbrError::Io(error)),
+            Err(primary_error) => {
+                let last_lba = (io.seek(SeekFrom::End(0))? / BLOCK_SIZE).saturating_sub(1);
+
+                match read_header(&mut io, last_lba, verify) {
+                    Ok(header) => (header, true),
+                    Err(MbrError::Io(error)) => return Err(MbrError::Io(error)),
+                    // Report why the primary header couldn't be used
+                    Err(_) => return Err(primary_error),
+                }
+            }
+        };
+
+        let mut gpt = Self {
+            header,
+            from_backup,
+            hybrid: [None; HYBRID_ENTRY_COUNT],
+            io,
+        };
+
+        gpt.read_hybrid_mbr()?;
+
+        Ok(gpt)
+    }
+
+    /// Find the GPT entries mirrored by a hybrid MBR, so that they're kept
+    /// in sync when the GPT is edited
+    ///
+    /// Records in slots two through four are matched to the entry covering
+    /// the same LBA range. A protective MBR, or a missing one, mirrors
+    /// nothing
+    fn read_hybrid_mbr(&mut self) -> Result<(), MbrError<IO::Error>> {
+        let records = match MBR::new(&mut self.io) {
+            Ok(mbr) => mbr.partitions,
+            Err(MbrError::Io(error)) => return Err(MbrError::Io(error)),
+            Err(_) => return Ok(()),
+        };
+
+        if records[0].get_partition_type() != PartitionType::GPT {
+            return Ok(());
+        }
+
+        for (slot, record) in records[1..].iter().enumerate() {
+            if !record.is_used() {
+                continue;
+            }
+
+            let first_lba = record.relative_sector as u64;
+            let last_lba = first_lba + record.total_sectors as u64 - 1;
+
+            let index =
+                self.find(|entry| entry.first_lba == first_lba && entry.last_lba == last_lba)?;
+
+            self.hybrid[slot] = index.map(|index| HybridEntry {
+                index,
+                partition_type: record.get_partition_type(),
+                bootable: record.is_bootable(),
+            });
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    /// Check if the backup header is in use because the primary header was
+    /// unusable
+    pub fn is_from_backup(&self) -> bool {
+        self.from_backup
+    }
+
+    #[inline]
+    /// Get the GPT header
+    pub fn header(&self) -> &GptHeader {
+        &self.header
+    }
+
+    #[inline]
+    /// Get the number of partition entries in the GPT
+    pub fn partition_count(&self) -> u32 {
+        self.header.entry_count
+    }
+
+    /// Compute the CRC32 of the partition entry array on the disk
+    pub fn compute_entries_crc(&mut self) -> Result<u32, MbrError<IO::Error>> {
+        compute_entries_crc(&mut self.io, &self.header)
+    }
+
+    /// Read a partition entry from the disk
+    pub fn get_entry(&mut self, index: u32) -> Result<GptEntry, MbrError<IO::Error>> {
+        if index >= self.header.entry_count {
+            return Err(MbrError::InvalidPartitionIndex(index));
+        }
+
+        let mut buffer: [u8; MIN_ENTRY_LEN as usize] = [0; MIN_ENTRY_LEN as usize];
+
+        self.io
+            .seek(SeekFrom::Start(self.header.get_entry_pos(index)))?;
+        self.io.read_exact(&mut buffer)?;
+
+        Ok(GptEntry::from_bytes(&buffer))
+    }
+
+    /// Find the first used partition entry with the given name, returning
+    /// its index
+    pub fn find_by_name(&mut self, name: &str) -> Result<Option<u32>, MbrError<IO::Error>> {
+        self.find(|entry| entry.name_eq(name))
+    }
+
+    /// Find the first used partition entry with the given type, returning
+    /// its index
+    ///
+    /// Accepts either a raw [`Guid`] or a [`GptPartitionType`]
+    pub fn find_by_type_guid(
+        &mut self,
+        type_guid: impl Into<Guid>,
+    ) -> Result<Option<u32>, MbrError<IO::Error>> {
+        let type_guid = type_guid.into();
+
+        self.find(|entry| entry.type_guid == type_guid)
+    }
+
+    /// Find the first used partition entry matching a predicate
+    fn find(
+        &mut self,
+        predicate: impl Fn(&GptEntry) -> bool,
+    ) -> Result<Option<u32>, MbrError<IO::Error>> {
+        for index in 0..self.header.entry_count {
+            let entry = self.get_entry(index)?;
+
+            if entry.is_used() && predicate(&entry) {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Get a partition from the GPT
+    pub fn get_partition(&mut self, index: u32) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let entry = self.get_entry(index)?;
+
+        if !entry.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        if entry.last_lba < entry.first_lba || entry.last_lba > MAX_LBA {
+            return Err(MbrError::CorruptRecord);
+        }
+
+        Partition::new(entry.get_start_pos(), entry.get_end_pos(), &mut self.io)
+    }
+}
+
+impl<IO: Read + Write + Seek> GPT<IO> {
+    /// Write a fresh GPT with the default number of partition entries to a
+    /// device, replacing whatever is on it
+    ///
+    /// A protective MBR, both headers and both (empty) partition entry
+    /// arrays are written
+    pub fn format(io: IO, disk_guid: Guid) -> Result<Self, MbrError<IO::Error>> {
+        Self::format_with_entry_count(io, disk_guid, DEFAULT_ENTRY_COUNT)
+    }
+
+    /// Write a fresh GPT with the given number of partition entries to a
+    /// device, replacing whatever is on it
+    ///
+    /// The UEFI spec asks for room for at least 128 entries, so smaller
+    /// counts should only be used on tiny devices
+    pub fn format_with_entry_count(
+        mut io: IO,
+        disk_guid: Guid,
+        entry_count: u32,
+    ) -> Result<Self, MbrError<IO::Error>> {
+        let device_sectors = io.seek(SeekFrom::End(0))? / BLOCK_SIZE;
+        let entries_len = (entry_count as u64) * (MIN_ENTRY_LEN as u64);
+        let entries_sectors = entries_len.div_ceil(BLOCK_SIZE);
+
+        // Protective MBR, both headers, both entry arrays, and at least one
+        // usable sector
+        if entry_count == 0 || device_sectors < 4 + entries_sectors * 2 {
+            return Err(MbrError::DeviceTooSmall);
+        }
+
+        let last_lba = device_sectors - 1;
+        let zeros: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+        let mut crc = Crc32::new();
+
+        for _ in 0..entries_len / BLOCK_SIZE {
+            crc.update(&zeros);
+        }
+
+        crc.update(&zeros[..(entries_len % BLOCK_SIZE) as usize]);
+
+        let primary = GptHeader {
+            revision: GPT_REVISION,
+            header_len: MIN_HEADER_LEN,
+            header_crc: 0,
+            current_lba: PRIMARY_HEADER_LBA,
+            backup_lba: last_lba,
+            first_usable_lba: PRIMARY_ENTRIES_LBA + entries_sectors,
+            last_usable_lba: last_lba - entries_sectors - 1,
+            disk_guid,
+            entries_lba: PRIMARY_ENTRIES_LBA,
+            entry_count,
+            entry_len: MIN_ENTRY_LEN,
+            entries_crc: crc.finish(),
+        };
+        let backup = primary.mirrored(last_lba - entries_sectors);
+
+        write_protective_mbr(&mut io)?;
+
+        for header in [&primary, &backup] {
+            io.seek(SeekFrom::Start(lba64_to_u64(header.entries_lba)))?;
+
+            for _ in 0..entries_sectors {
+                io.write_all(&zeros)?;
+            }
+
+            io.seek(SeekFrom::Start(lba64_to_u64(header.current_lba)))?;
+            io.write_all(&header.to_bytes())?;
+        }
+
+        io.flush()?;
+
+        Self::new(io)
+    }
+
+    /// Add a partition entry in the first unused slot, returning its index
+    ///
+    /// Both partition entry arrays and both headers are rewritten
+    pub fn add_entry(&mut self, entry: GptEntry) -> Result<u32, MbrError<IO::Error>> {
+        for index in 0..self.header.entry_count {
+            if !self.get_entry(index)?.is_used() {
+                self.set_entry(index, entry)?;
+
+                return Ok(index);
+            }
+        }
+
+        Err(MbrError::NoFreeEntries)
+    }
+
+    /// Remove a partition entry
+    ///
+    /// Both partition entry arrays and both headers are rewritten
+    pub fn remove_entry(&mut self, index: u32) -> Result<(), MbrError<IO::Error>> {
+        self.set_entry(index, GptEntry::EMPTY)
+    }
+
+    /// Replace the partition entry at the given index
+    ///
+    /// The entry must lie within the usable LBAs and must not overlap any
+    /// other entry. Both partition entry arrays and both headers are
+    /// rewritten, repairing the primary first if the backup is in use
+    pub fn set_entry(&mut self, index: u32, entry: GptEntry) -> Result<(), MbrError<IO::Error>> {
+        if index >= self.header.entry_count {
+            return Err(MbrError::InvalidPartitionIndex(index));
+        }
+
+        if entry.is_used() {
+            if entry.first_lba < self.header.first_usable_lba
+                || entry.last_lba > self.header.last_usable_lba
+                || entry.last_lba < entry.first_lba
+            {
+                return Err(MbrError::OutsideUsableRange);
+            }
+
+            for other_index in 0..self.header.entry_count {
+                if other_index != index && self.get_entry(other_index)?.overlaps(&entry) {
+                    return Err(MbrError::EntriesOverlap(other_index));
+                }
+            }
+        }
+
+        // The primary entries may be corrupt if the backup is in use, so
+        // restore them first, otherwise the new CRC would cover bad entries
+        self.repair_primary()?;
+
+        let other = self.read_other_header()?;
+        let bytes = entry.to_bytes();
+
+        // Update the backup before the primary
+        for header in [&other, &self.header] {
+            self.io.seek(SeekFrom::Start(header.get_entry_pos(index)))?;
+            self.io.write_all(&bytes)?;
+        }
+
+        let entries_crc = compute_entries_crc(&mut self.io, &self.header)?;
+
+        self.write_headers(other, |header| header.entries_crc = entries_crc)?;
+
+        if self.hybrid.iter().any(|h| h.is_some()) {
+            self.write_hybrid_mbr()?;
+        }
+
+        self.io.flush()?;
+
+        Ok(())
+    }
+
+    /// Replace the protective MBR with a hybrid MBR mirroring up to three
+    /// GPT entries, for firmware that can only boot from an MBR
+    ///
+    /// The GPT entries are mirrored into MBR slots two through four in the
+    /// order given, slot one holds a 0xEE record covering the GPT itself.
+    /// The hybrid MBR is rewritten whenever an entry is changed through
+    /// this GPT, and its mirrored entries are found again when the GPT is
+    /// reopened
+    pub fn enable_hybrid_mbr(
+        &mut self,
+        entries: &[HybridEntry],
+    ) -> Result<(), MbrError<IO::Error>> {
+        if entries.len() > HYBRID_ENTRY_COUNT {
+            return Err(MbrError::TooManyHybridEntries);
+        }
+
+        for entry in entries {
+            if entry.index >= self.header.entry_count {
+                return Err(MbrError::InvalidPartitionIndex(entry.index));
+            }
+        }
+
+        self.hybrid = [None; HYBRID_ENTRY_COUNT];
+
+        for (slot, entry) in self.hybrid.iter_mut().zip(entries) {
+            *slot = Some(*entry);
+        }
+
+        self.write_hybrid_mbr()?;
+        self.io.flush()?;
+
+        Ok(())
+    }
+
+    /// Apply changes made to the mirrored records of the hybrid MBR back to
+    /// their GPT entries
+    ///
+    /// Use this after the hybrid MBR has been edited by something other
+    /// than this GPT
+    pub fn sync_from_hybrid_mbr(&mut self) -> Result<(), MbrError<IO::Error>> {
+        let mbr = MBR::new(&mut self.io)?;
+        let records = mbr.partitions;
+
+        for (slot, hybrid) in self.hybrid.into_iter().enumerate() {
+            let Some(hybrid) = hybrid else {
+                continue;
+            };
+
+            let record = records[slot + 1];
+            let mut entry = self.get_entry(hybrid.index)?;
+
+            if !record.is_used() || !entry.is_used() {
+                continue;
+            }
+
+            let first_lba = record.relative_sector as u64;
+            let last_lba = first_lba + record.total_sectors as u64 - 1;
+
+            if entry.first_lba != first_lba || entry.last_lba != last_lba {
+                entry.set_range(first_lba, last_lba);
+
+                self.set_entry(hybrid.index, entry)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the hybrid MBR from the current GPT entries
+    fn write_hybrid_mbr(&mut self) -> Result<(), MbrError<IO::Error>> {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+        let mut records = [PartitionRecord::default(); RECORD_COUNT];
+
+        records[0] = PartitionRecord::new(
+            PRIMARY_HEADER_LBA as u32,
+            (self.header.first_usable_lba - PRIMARY_HEADER_LBA) as u32,
+            PartitionType::GPT,
+            false,
+        );
+
+        for (slot, hybrid) in self.hybrid.into_iter().enumerate() {
+            let Some(hybrid) = hybrid else {
+                continue;
+            };
+
+            let entry = self.get_entry(hybrid.index)?;
+
+            if !entry.is_used() {
+                continue;
+            }
+
+            let sectors = entry
+                .last_lba
+                .checked_sub(entry.first_lba)
+                .ok_or(MbrError::CorruptRecord)?
+                + 1;
+
+            if entry.last_lba > u32::MAX as u64 || sectors > u32::MAX as u64 {
+                return Err(MbrError::ExceedsMbrAddressing);
+            }
+
+            records[slot + 1] = PartitionRecord::new(
+                entry.first_lba as u32,
+                sectors as u32,
+                hybrid.partition_type,
+                hybrid.bootable,
+            );
+        }
+
+        // Keep the boot code
+        self.io.seek(SeekFrom::Start(0))?;
+        self.io.read_exact(&mut sector)?;
+
+        for (i, record) in records.iter().enumerate() {
+            let record_i = RECORDS_START as usize + i * RECORD_LEN;
+
+            sector[record_i..record_i + RECORD_LEN].copy_from_slice(&record.to_bytes());
+        }
+
+        sector[SIGNATURE_OFFSET as usize..].copy_from_slice(&SIGNATURE);
+
+        self.io.seek(SeekFrom::Start(0))?;
+        self.io.write_all(&sector)?;
+
+        Ok(())
+    }
+
+    /// Move the start and end of a partition entry
+    ///
+    /// The new range must lie within the usable LBAs and must not overlap
+    /// any other entry. Data inside the partition isn't moved
+    pub fn resize_entry(
+        &mut self,
+        index: u32,
+        first_lba: u64,
+        last_lba: u64,
+    ) -> Result<(), MbrError<IO::Error>> {
+        let mut entry = self.get_entry(index)?;
+
+        if !entry.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        entry.set_range(first_lba, last_lba);
+
+        self.set_entry(index, entry)
+    }
+
+    #[inline]
+    /// Get the GUID of the disk
+    pub fn disk_guid(&self) -> Guid {
+        self.header.disk_guid
+    }
+
+    /// Change the GUID of the disk, rewriting both headers
+    ///
+    /// Cloned disks should be given a new GUID, see [`Guid::from_random`]
+    pub fn set_disk_guid(&mut self, disk_guid: Guid) -> Result<(), MbrError<IO::Error>> {
+        let other = self.read_other_header()?;
+
+        self.write_headers(other, |header| header.disk_guid = disk_guid)?;
+        self.io.flush()?;
+
+        Ok(())
+    }
+
+    /// Apply a change to both headers and write them, backup first
+    fn write_headers(
+        &mut self,
+        mut other: GptHeader,
+        update: impl Fn(&mut GptHeader),
+    ) -> Result<(), IO::Error> {
+        let (primary, backup) = match self.from_backup {
+            true => (&mut other, &mut self.header),
+            false => (&mut self.header, &mut other),
+        };
+
+        for header in [backup, primary] {
+            update(header);
+            header.header_crc = GptHeader::compute_crc(&header.to_bytes()).unwrap_or(0);
+
+            self.io
+                .seek(SeekFrom::Start(lba64_to_u64(header.current_lba)))?;
+            self.io.write_all(&header.to_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the header that isn't in use, or rebuild it from the one that
+    /// is if it's unreadable
+    fn read_other_header(&mut self) -> Result<GptHeader, MbrError<IO::Error>> {
+        match read_header(&mut self.io, self.header.backup_lba, false) {
+            Ok(header) => Ok(header),
+            Err(MbrError::Io(error)) => Err(MbrError::Io(error)),
+            Err(_) => {
+                let entries_lba = match self.from_backup {
+                    true => PRIMARY_ENTRIES_LBA,
+                    false => self.header.last_usable_lba + 1,
+                };
+
+                Ok(self.header.mirrored(entries_lba))
+            }
+        }
+    }
+
+    /// Rewrite the primary header and partition entries from the backup
+    ///
+    /// Does nothing if the primary header is already in use
+    pub fn repair_primary(&mut self) -> Result<(), MbrError<IO::Error>> {
+        if !self.from_backup {
+            return Ok(());
+        }
+
+        let entries_sectors = self.header.get_entries_len().div_ceil(BLOCK_SIZE);
+
+        if PRIMARY_ENTRIES_LBA + entries_sectors > self.header.first_usable_lba {
+            return Err(MbrError::InvalidGptHeader);
+        }
+
+        let mut buffer: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        for i in 0..entries_sectors {
+            self.io
+                .seek(SeekFrom::Start(lba64_to_u64(self.header.entries_lba + i)))?;
+            self.io.read_exact(&mut buffer)?;
+            self.io
+                .seek(SeekFrom::Start(lba64_to_u64(PRIMARY_ENTRIES_LBA + i)))?;
+            self.io.write_all(&buffer)?;
+        }
+
+        let primary = self.header.mirrored(PRIMARY_ENTRIES_LBA);
+
+        self.io
+            .seek(SeekFrom::Start(lba64_to_u64(PRIMARY_HEADER_LBA)))?;
+        self.io.write_all(&primary.to_bytes())?;
+        self.io.flush()?;
+
+        self.header = primary;
+        self.from_backup = false;
+
+        Ok(())
+    }
+}
+
+/// Build the single record of a protective MBR for a device with the
+/// given number of sectors
+///
+/// The record starts at LBA 1 and covers the rest of the device, capped at
+/// 0xFFFFFFFF sectors for devices larger than 2 TiB
+pub fn protective_record(device_sectors: u64) -> [u8; RECORD_LEN] {
+    let total_sectors = cmp::min(device_sectors.saturating_sub(1), u32::MAX as u64) as u32;
+    let record = PartitionRecord::new(1, total_sectors, PartitionType::GPT, false);
+    let mut bytes = record.to_bytes();
+
+    bytes[START_CHS_OFFSET..SYSTEM_ID_OFFSET].copy_from_slice(&PROTECTIVE_START_CHS);
+    bytes[END_CHS_OFFSET..RELATIVE_SECTOR_OFFSET].copy_from_slice(&PROTECTIVE_END_CHS);
+
+    bytes
+}
+
+/// Write a protective MBR covering the whole device, as used in front of a
+/// GPT
+///
+/// The boot code in sector 0 is kept, the other three records are cleared
+pub fn write_protective_mbr<IO: Read + Write + Seek>(
+    io: &mut IO,
+) -> Result<(), MbrError<IO::Error>> {
+    let device_sectors = io.seek(SeekFrom::End(0))? / BLOCK_SIZE;
+    let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+    io.seek(SeekFrom::Start(0))?;
+    io.read_exact(&mut sector)?;
+
+    sector[RECORDS_START as usize..SIGNATURE_OFFSET as usize].fill(0);
+    sector[RECORDS_START as usize..RECORDS_START as usize + RECORD_LEN]
+        .copy_from_slice(&protective_record(device_sectors));
+    sector[SIGNATURE_OFFSET as usize..].copy_from_slice(&SIGNATURE);
+
+    io.seek(SeekFrom::Start(0))?;
+    io.write_all(&sector)?;
+    io.flush()?;
+
+    Ok(())
+}
+
+/// Read and check a GPT header at the given LBA
+fn read_header<IO: Read + Seek>(
+    io: &mut IO,
+    lba: u64,
+    verify: bool,
+) -> Result<GptHeader, MbrError<IO::Error>> {
+    let mut buffer: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+    io.seek(SeekFrom::Start(lba64_to_u64(lba)))?;
+    io.read_exact(&mut buffer)?;
+
+    let header = GptHeader::from_bytes(&buffer).ok_or(MbrError::MissingGptSignature)?;
+
+    if !header.is_valid() || header.current_lba != lba {
+        return Err(MbrError::InvalidGptHeader);
+    }
+
+    if verify {
+        if GptHeader::compute_crc(&buffer) != Some(header.header_crc) {
+            return Err(MbrError::GptHeaderCrcMismatch);
+        }
+
+        if compute_entries_crc(io, &header)? != header.entries_crc {
+            return Err(MbrError::GptEntriesCrcMismatch);
+        }
+    }
+
+    Ok(header)
+}
+
+/// Compute the CRC32 of the partition entry array described by a header
+fn compute_entries_crc<IO: Read + Seek>(
+    io: &mut IO,
+    header: &GptHeader,
+) -> Result<u32, MbrError<IO::Error>> {
+    let mut buffer: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+    let mut crc = Crc32::new();
+    let mut remaining = header.get_entries_len();
+
+    io.seek(SeekFrom::Start(lba64_to_u64(header.entries_lba)))?;
+
+    while remaining > 0 {
+        let chunk_len = cmp::min(remaining, BLOCK_SIZE) as usize;
+
+        io.read_exact(&mut buffer[..chunk_len])?;
+        crc.update(&buffer[..chunk_len]);
+
+        remaining -= chunk_len as u64;
+    }
+
+    Ok(crc.finish())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{
+        adapters::FromStd,
+        blocking::{Read, Seek, Write},
+    };
+
+    use crate::{
+        error::MbrError,
+        gpt::*,
+        types::{GptPartitionType, PartitionType},
+        PartitionRecord, BLOCK_SIZE, MBR, RECORDS_START, RECORD_LEN, SIGNATURE, SIGNATURE_OFFSET,
+        TOTAL_SECTORS_OFFSET,
+    };
+
+    /// Number of sectors in the test image
+    pub const TEST_SECTORS: u64 = 64;
+    /// Number of partition entries in the test image
+    pub const TEST_ENTRY_COUNT: u32 = 4;
+    /// Type GUID used for the test partitions
+    pub const TEST_TYPE_GUID: Guid = Guid([
+        0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d,
+        0xe4,
+    ]);
+
+    /// Write a partition entry into an image
+    fn write_entry(img: &mut [u8], index: usize, first_lba: u64, last_lba: u64, name: &str) {
+        let pos = 2 * BLOCK_SIZE as usize + index * MIN_ENTRY_LEN as usize;
+        let entry = &mut img[pos..pos + MIN_ENTRY_LEN as usize];
+
+        entry[TYPE_GUID_OFFSET..TYPE_GUID_OFFSET + GUID_LEN].copy_from_slice(&TEST_TYPE_GUID.0);
+        entry[UNIQUE_GUID_OFFSET] = index as u8 + 1;
+        entry[FIRST_LBA_OFFSET..FIRST_LBA_OFFSET + 8].copy_from_slice(&first_lba.to_le_bytes());
+        entry[LAST_LBA_OFFSET..LAST_LBA_OFFSET + 8].copy_from_slice(&last_lba.to_le_bytes());
+
+        for (i, c) in name.encode_utf16().enumerate() {
+            entry[NAME_OFFSET + i * 2..NAME_OFFSET + i * 2 + 2].copy_from_slice(&c.to_le_bytes());
+        }
+    }
+
+    /// Build a small GPT image with a protective MBR and two partitions,
+    /// "first" and "second", each with their index written to their first
+    /// byte
+    pub fn test_img() -> Vec<u8> {
+        let mut img = vec![0; (TEST_SECTORS * BLOCK_SIZE) as usize];
+        let header = &mut img[BLOCK_SIZE as usize..2 * BLOCK_SIZE as usize];
+
+        header[..8].copy_from_slice(&GPT_SIGNATURE);
+        header[REVISION_OFFSET..REVISION_OFFSET + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+        header[HEADER_LEN_OFFSET..HEADER_LEN_OFFSET + 4]
+            .copy_from_slice(&MIN_HEADER_LEN.to_le_bytes());
+        header[CURRENT_LBA_OFFSET..CURRENT_LBA_OFFSET + 8].copy_from_slice(&1u64.to_le_bytes());
+        header[BACKUP_LBA_OFFSET..BACKUP_LBA_OFFSET + 8]
+            .copy_from_slice(&(TEST_SECTORS - 1).to_le_bytes());
+        header[FIRST_USABLE_LBA_OFFSET..FIRST_USABLE_LBA_OFFSET + 8]
+            .copy_from_slice(&3u64.to_le_bytes());
+        header[LAST_USABLE_LBA_OFFSET..LAST_USABLE_LBA_OFFSET + 8]
+            .copy_from_slice(&(TEST_SECTORS - 3).to_le_bytes());
+        header[DISK_GUID_OFFSET] = 0xaa;
+        header[ENTRIES_LBA_OFFSET..ENTRIES_LBA_OFFSET + 8].copy_from_slice(&2u64.to_le_bytes());
+        header[ENTRY_COUNT_OFFSET..ENTRY_COUNT_OFFSET + 4]
+            .copy_from_slice(&TEST_ENTRY_COUNT.to_le_bytes());
+        header[ENTRY_LEN_OFFSET..ENTRY_LEN_OFFSET + 4]
+            .copy_from_slice(&MIN_ENTRY_LEN.to_le_bytes());
+
+        // Protective MBR
+        let record = PartitionRecord::new(1, TEST_SECTORS as u32 - 1, PartitionType::GPT, false);
+        let record_i = RECORDS_START as usize;
+
+        img[record_i..record_i + RECORD_LEN].copy_from_slice(&record.to_bytes());
+        img[SIGNATURE_OFFSET as usize..BLOCK_SIZE as usize].copy_from_slice(&SIGNATURE);
+
+        write_entry(&mut img, 0, 3, 18, "first");
+        write_entry(&mut img, 1, 19, 60, "second");
+
+        img[3 * BLOCK_SIZE as usize] = 1;
+        img[19 * BLOCK_SIZE as usize] = 2;
+
+        update_crcs(&mut img);
+
+        // Backup entries and header
+        let entries_pos = 2 * BLOCK_SIZE as usize;
+        let backup_entries_pos = (TEST_SECTORS as usize - 2) * BLOCK_SIZE as usize;
+        let primary =
+            GptHeader::from_bytes(img[BLOCK_SIZE as usize..entries_pos].try_into().unwrap())
+                .unwrap();
+
+        img.copy_within(
+            entries_pos..entries_pos + BLOCK_SIZE as usize,
+            backup_entries_pos,
+        );
+        img[backup_entries_pos + BLOCK_SIZE as usize..]
+            .copy_from_slice(&primary.mirrored(TEST_SECTORS - 2).to_bytes());
+
+        img
+    }
+
+    /// Recompute the CRC32s of the primary header and entries of an image
+    pub fn update_crcs(img: &mut [u8]) {
+        let entries_pos = 2 * BLOCK_SIZE as usize;
+        let entries_len = (TEST_ENTRY_COUNT * MIN_ENTRY_LEN) as usize;
+        let entries_crc = crc32(&img[entries_pos..entries_pos + entries_len]);
+        let header = &mut img[BLOCK_SIZE as usize..2 * BLOCK_SIZE as usize];
+
+        header[ENTRIES_CRC_OFFSET..ENTRIES_CRC_OFFSET + 4]
+            .copy_from_slice(&entries_crc.to_le_bytes());
+
+        let header_crc = GptHeader::compute_crc((&*header).try_into().unwrap()).unwrap();
+
+        header[HEADER_CRC_OFFSET..HEADER_CRC_OFFSET + 4].copy_from_slice(&header_crc.to_le_bytes());
+    }
+
+    #[test]
+    /// Ensure that the header and entries of a GPT can be read
+    fn test_gpt() {
+        let img = FromStd::new(Cursor::new(test_img()));
+
+        let mut gpt = GPT::new(img).unwrap();
+
+        assert_eq!(gpt.partition_count(), TEST_ENTRY_COUNT);
+        assert_eq!(gpt.header().get_first_usable_lba(), 3);
+        assert_eq!(gpt.header().get_disk_guid().0[0], 0xaa);
+
+        let entry = gpt.get_entry(1).unwrap();
+
+        assert!(entry.is_used());
+        assert_eq!(entry.get_type_guid(), TEST_TYPE_GUID);
+        assert_eq!(
+            entry.get_partition_type(),
+            GptPartitionType::LinuxFilesystem
+        );
+        assert_eq!(entry.get_first_lba(), 19);
+        assert_eq!(entry.get_last_lba(), 60);
+
+        let mut buf: [u8; 1] = [0; 1];
+
+        let mut partition = gpt.get_partition(0).unwrap();
+
+        assert_eq!(partition.len(), 16 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 1);
+
+        let mut partition = gpt.get_partition(1).unwrap();
+
+        assert_eq!(partition.len(), 42 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 2);
+
+        assert!(matches!(
+            gpt.get_partition(2),
+            Err(MbrError::EmptyPartition)
+        ));
+
+        // Entries that end before they start or can't be addressed are
+        // reported instead of overflowing
+        let mut img = test_img();
+
+        write_entry(&mut img, 2, 40, 30, "backwards");
+        write_entry(&mut img, 3, 3, u64::MAX, "huge");
+        update_crcs(&mut img);
+
+        let mut corrupt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            corrupt.get_partition(2),
+            Err(MbrError::CorruptRecord)
+        ));
+        assert!(matches!(
+            corrupt.get_partition(3),
+            Err(MbrError::CorruptRecord)
+        ));
+        assert_eq!(corrupt.get_entry(3).unwrap().get_end_pos(), u64::MAX);
+        assert!(matches!(
+            gpt.get_partition(TEST_ENTRY_COUNT),
+            Err(MbrError::InvalidPartitionIndex(_))
+        ));
+    }
+
+    #[test]
+    /// Ensure that attribute flags are read and set correctly
+    fn test_gpt_attributes() {
+        let mut attributes = GptAttributes::from_bits((0xf00d << 48) | 0b101);
+
+        assert!(attributes.is_required());
+        assert!(!attributes.is_no_block_io_protocol());
+        assert!(attributes.is_legacy_bios_bootable());
+        assert_eq!(attributes.get_type_specific(), 0xf00d);
+
+        attributes.set_required(false);
+        attributes.set_no_block_io_protocol(true);
+        attributes.set_type_specific(0x1234);
+
+        assert_eq!(attributes.bits(), (0x1234 << 48) | 0b110);
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(gpt.get_entry(0).unwrap().get_attributes().bits(), 0);
+    }
+
+    #[test]
+    /// Ensure that partition names are decoded
+    fn test_gpt_name() {
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        let entry = gpt.get_entry(0).unwrap();
+
+        assert!(entry.name().eq("first".chars()));
+        assert!(entry.name_eq("first"));
+        assert!(!entry.name_eq("firs"));
+        assert!(!entry.name_eq("second"));
+        assert!(gpt.get_entry(1).unwrap().name_eq("second"));
+        assert!(gpt.get_entry(2).unwrap().name_eq(""));
+    }
+
+    #[test]
+    /// Ensure that entries can be found by name and type
+    fn test_gpt_find() {
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(gpt.find_by_name("second").unwrap(), Some(1));
+        assert_eq!(gpt.find_by_name("third").unwrap(), None);
+        // Unused entries have empty names but shouldn't match
+        assert_eq!(gpt.find_by_name("").unwrap(), None);
+        assert_eq!(gpt.find_by_type_guid(TEST_TYPE_GUID).unwrap(), Some(0));
+        assert_eq!(
+            gpt.find_by_type_guid(GptPartitionType::LinuxFilesystem)
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            gpt.find_by_type_guid(GptPartitionType::EfiSystem).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    /// Ensure that CRC32 mismatches are caught unless verification is
+    /// skipped
+    fn test_gpt_crc() {
+        let backup_entries_pos = (TEST_SECTORS as usize - 2) * BLOCK_SIZE as usize;
+        let backup_header_pos = (TEST_SECTORS as usize - 1) * BLOCK_SIZE as usize;
+
+        // Corrupt the name of the first partition in both entry arrays
+        let mut img = test_img();
+
+        img[2 * BLOCK_SIZE as usize + NAME_OFFSET] = b'F';
+        img[backup_entries_pos + NAME_OFFSET] = b'F';
+
+        assert!(matches!(
+            GPT::new(FromStd::new(Cursor::new(img.clone()))),
+            Err(MbrError::GptEntriesCrcMismatch)
+        ));
+        GPT::new_unchecked(FromStd::new(Cursor::new(img))).unwrap();
+
+        // Corrupt the first usable LBA in both headers
+        let mut img = test_img();
+
+        img[BLOCK_SIZE as usize + FIRST_USABLE_LBA_OFFSET] = 4;
+        img[backup_header_pos + FIRST_USABLE_LBA_OFFSET] = 4;
+
+        assert!(matches!(
+            GPT::new(FromStd::new(Cursor::new(img.clone()))),
+            Err(MbrError::GptHeaderCrcMismatch)
+        ));
+        GPT::new_unchecked(FromStd::new(Cursor::new(img))).unwrap();
+    }
+
+    #[test]
+    /// Ensure that the backup header is used when the primary is corrupt,
+    /// and that the primary can be repaired from it
+    fn test_gpt_backup() {
+        let mut img = test_img();
+        let entries_pos = 2 * BLOCK_SIZE as usize;
+
+        // Corrupt the primary entries
+        img[entries_pos + NAME_OFFSET] = b'F';
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+        assert_eq!(gpt.header().get_current_lba(), TEST_SECTORS - 1);
+        assert_eq!(gpt.get_entry(1).unwrap().get_first_lba(), 19);
+
+        gpt.repair_primary().unwrap();
+
+        assert!(!gpt.is_from_backup());
+
+        let img = gpt.io.into_inner().into_inner();
+
+        assert_eq!(img, test_img());
+    }
+
+    #[test]
+    /// Ensure that a freshly formatted disk can be opened
+    fn test_gpt_format() {
+        let img = FromStd::new(Cursor::new(vec![0; 128 * BLOCK_SIZE as usize]));
+        let disk_guid = Guid::from_fields(0x12345678, 0x9abc, 0xdef0, 0x0123456789abcdef);
+
+        let gpt = GPT::format(img, disk_guid).unwrap();
+
+        assert!(!gpt.is_from_backup());
+        assert_eq!(gpt.partition_count(), DEFAULT_ENTRY_COUNT);
+        assert_eq!(gpt.header().get_disk_guid(), disk_guid);
+        assert_eq!(gpt.header().get_first_usable_lba(), 34);
+        assert_eq!(gpt.header().get_last_usable_lba(), 94);
+
+        let mut img = gpt.io.into_inner().into_inner();
+
+        // Both the protective MBR and the backup should be usable
+        assert!(crate::MBR::new(FromStd::new(Cursor::new(img.clone())))
+            .unwrap()
+            .is_protective());
+
+        img[BLOCK_SIZE as usize] = 0;
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+        assert!(!gpt.get_entry(0).unwrap().is_used());
+
+        // Too small to hold the default entry arrays
+        let img = FromStd::new(Cursor::new(vec![0; 64 * BLOCK_SIZE as usize]));
+
+        assert!(matches!(
+            GPT::format(img, disk_guid),
+            Err(MbrError::DeviceTooSmall)
+        ));
+
+        // An entry array that doesn't fill its last sector still verifies
+        let img = FromStd::new(Cursor::new(vec![0; 16 * BLOCK_SIZE as usize]));
+        let mut gpt = GPT::format_with_entry_count(img, disk_guid, 5).unwrap();
+
+        assert_eq!(gpt.partition_count(), 5);
+        assert_eq!(
+            gpt.compute_entries_crc().unwrap(),
+            gpt.header().get_entries_crc()
+        );
+    }
+
+    #[test]
+    /// Ensure that entries can be added and removed, keeping both copies of
+    /// the GPT valid
+    fn test_gpt_add_remove() {
+        let img = FromStd::new(Cursor::new(vec![0; 128 * BLOCK_SIZE as usize]));
+
+        let mut gpt = GPT::format(img, Guid::ZERO).unwrap();
+
+        let root = GptEntry::new(
+            GptPartitionType::LinuxFilesystem,
+            Guid::from_fields(1, 2, 3, 4),
+            34,
+            63,
+            "root",
+        );
+        let home = GptEntry::new(
+            GptPartitionType::LinuxHome,
+            Guid::from_fields(5, 6, 7, 8),
+            64,
+            94,
+            "home",
+        );
+
+        assert_eq!(gpt.add_entry(root).unwrap(), 0);
+        assert_eq!(gpt.add_entry(home).unwrap(), 1);
+
+        // Overlapping and out of range entries are refused
+        let bad = GptEntry::new(GptPartitionType::LinuxSwap, Guid::ZERO, 60, 70, "bad");
+
+        assert!(matches!(
+            gpt.add_entry(bad),
+            Err(MbrError::EntriesOverlap(0))
+        ));
+
+        let bad = GptEntry::new(GptPartitionType::LinuxSwap, Guid::ZERO, 90, 95, "bad");
+
+        assert!(matches!(
+            gpt.add_entry(bad),
+            Err(MbrError::OutsideUsableRange)
+        ));
+
+        gpt.remove_entry(0).unwrap();
+
+        let mut img = gpt.io.into_inner().into_inner();
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img.clone()))).unwrap();
+
+        assert!(!gpt.is_from_backup());
+        assert!(!gpt.get_entry(0).unwrap().is_used());
+        assert_eq!(gpt.get_entry(1).unwrap(), home);
+
+        // The backup should have been updated too
+        img[BLOCK_SIZE as usize] = 0;
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+        assert!(!gpt.get_entry(0).unwrap().is_used());
+        assert_eq!(gpt.get_entry(1).unwrap(), home);
+        assert_eq!(gpt.add_entry(root).unwrap(), 0);
+    }
+
+    #[test]
+    /// Ensure that entries can be resized within the usable LBAs
+    fn test_gpt_resize() {
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        // Shrink the second partition and grow the first into the space
+        gpt.resize_entry(1, 30, 60).unwrap();
+        gpt.resize_entry(0, 3, 29).unwrap();
+
+        assert!(matches!(
+            gpt.resize_entry(0, 3, 30),
+            Err(MbrError::EntriesOverlap(1))
+        ));
+        assert!(matches!(
+            gpt.resize_entry(1, 30, 62),
+            Err(MbrError::OutsideUsableRange)
+        ));
+        assert!(matches!(
+            gpt.resize_entry(2, 30, 60),
+            Err(MbrError::EmptyPartition)
+        ));
+
+        let mut gpt = GPT::new(gpt.io).unwrap();
+
+        assert!(!gpt.is_from_backup());
+        assert_eq!(gpt.get_entry(0).unwrap().get_last_lba(), 29);
+        assert_eq!(gpt.get_entry(1).unwrap().get_first_lba(), 30);
+        assert!(gpt.get_entry(1).unwrap().name_eq("second"));
+    }
+
+    #[test]
+    /// Ensure that editing a table opened from the backup leaves both
+    /// copies valid
+    fn test_gpt_edit_from_backup() {
+        let mut img = test_img();
+
+        // Corrupt the primary entries
+        img[2 * BLOCK_SIZE as usize + NAME_OFFSET] = b'F';
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+
+        gpt.resize_entry(1, 19, 59).unwrap();
+
+        let mut img = gpt.io.into_inner().into_inner();
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img.clone()))).unwrap();
+
+        assert!(!gpt.is_from_backup());
+        assert_eq!(
+            gpt.get_entry(0).unwrap(),
+            GPT::new(FromStd::new(Cursor::new(test_img())))
+                .unwrap()
+                .get_entry(0)
+                .unwrap()
+        );
+        assert_eq!(gpt.get_entry(1).unwrap().get_last_lba(), 59);
+
+        // The backup should match too
+        img[BLOCK_SIZE as usize] = 0;
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+        assert_eq!(
+            gpt.compute_entries_crc().unwrap(),
+            gpt.header().get_entries_crc()
+        );
+        assert_eq!(gpt.get_entry(1).unwrap().get_last_lba(), 59);
+    }
+
+    #[test]
+    /// Ensure that a hybrid MBR follows GPT edits and vice versa
+    fn test_gpt_hybrid_mbr() {
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        gpt.enable_hybrid_mbr(&[HybridEntry {
+            index: 1,
+            partition_type: PartitionType::Fat16,
+            bootable: true,
+        }])
+        .unwrap();
+
+        let mbr = MBR::new(&mut gpt.io).unwrap();
+
+        assert_eq!(mbr.partitions[0].get_partition_type(), PartitionType::GPT);
+        assert_eq!(mbr.partitions[0].get_end_pos(), 3 * BLOCK_SIZE);
+        assert_eq!(mbr.partitions[1].get_start_pos(), 19 * BLOCK_SIZE);
+        assert_eq!(mbr.partitions[1].get_end_pos(), 61 * BLOCK_SIZE);
+        assert!(mbr.partitions[1].is_bootable());
+        assert!(!mbr.partitions[2].is_used());
+
+        // Editing the GPT updates the MBR
+        gpt.resize_entry(1, 20, 50).unwrap();
+
+        let mut mbr = MBR::new(&mut gpt.io).unwrap();
+
+        assert_eq!(mbr.partitions[1].get_start_pos(), 20 * BLOCK_SIZE);
+        assert_eq!(mbr.partitions[1].get_end_pos(), 51 * BLOCK_SIZE);
+
+        // Editing the MBR updates the GPT
+        let record = PartitionRecord::new(22, 10, PartitionType::Fat16, true);
+
+        mbr.set_record(crate::PartitionId::Two, record);
+        mbr.flush().unwrap();
+
+        gpt.sync_from_hybrid_mbr().unwrap();
+
+        let mut gpt = GPT::new(gpt.io).unwrap();
+
+        assert_eq!(gpt.get_entry(1).unwrap().get_first_lba(), 22);
+        assert_eq!(gpt.get_entry(1).unwrap().get_last_lba(), 31);
+
+        // The mirrored entries are found again on reopen, and kept in sync
+        gpt.resize_entry(1, 24, 40).unwrap();
+
+        let mbr = MBR::new(&mut gpt.io).unwrap();
+
+        assert_eq!(mbr.partitions[1].get_start_pos(), 24 * BLOCK_SIZE);
+        assert_eq!(mbr.partitions[1].get_end_pos(), 41 * BLOCK_SIZE);
+        assert_eq!(mbr.partitions[1].get_partition_type(), PartitionType::Fat16);
+        assert!(mbr.partitions[1].is_bootable());
+    }
+
+    #[test]
+    /// Ensure that protective MBRs follow the spec, including the 2 TiB cap
+    fn test_protective_mbr() {
+        let mut img = FromStd::new(Cursor::new(vec![0xff; 64 * BLOCK_SIZE as usize]));
+
+        write_protective_mbr(&mut img).unwrap();
+
+        let img = img.into_inner().into_inner();
+        let record = &img[RECORDS_START as usize..RECORDS_START as usize + RECORD_LEN];
+
+        assert_eq!(
+            record,
+            [
+                0x00, 0x00, 0x02, 0x00, 0xee, 0xff, 0xff, 0xff, 0x01, 0x00, 0x00, 0x00, 0x3f, 0x00,
+                0x00, 0x00
+            ]
+        );
+        // Boot code is kept, the other records are cleared
+        assert_eq!(img[0], 0xff);
+        assert!(
+            img[RECORDS_START as usize + RECORD_LEN..SIGNATURE_OFFSET as usize]
+                .iter()
+                .all(|b| *b == 0)
+        );
+        assert_eq!(
+            img[SIGNATURE_OFFSET as usize..BLOCK_SIZE as usize],
+            SIGNATURE
+        );
+
+        let record = protective_record(1 << 40);
+
+        assert_eq!(record[TOTAL_SECTORS_OFFSET..], [0xff; 4]);
+    }
+
+    #[test]
+    /// Ensure that the disk GUID can be changed in both headers
+    fn test_gpt_disk_guid() {
+        let guid = Guid::from_random([0xa5; GUID_LEN]);
+
+        assert_eq!(
+            guid,
+            Guid::from_fields(0xa5a5a5a5, 0xa5a5, 0x45a5, 0xa5a5a5a5a5a5a5a5)
+        );
+
+        let mut gpt = GPT::new(FromStd::new(Cursor::new(test_img()))).unwrap();
+
+        assert_eq!(gpt.disk_guid().0[0], 0xaa);
+
+        gpt.set_disk_guid(guid).unwrap();
+
+        assert_eq!(gpt.disk_guid(), guid);
+
+        let mut img = gpt.io.into_inner().into_inner();
+
+        assert_eq!(
+            GPT::new(FromStd::new(Cursor::new(img.clone())))
+                .unwrap()
+                .disk_guid(),
+            guid
+        );
+
+        img[BLOCK_SIZE as usize] = 0;
+
+        let gpt = GPT::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(gpt.is_from_backup());
+        assert_eq!(gpt.disk_guid(), guid);
+    }
+
+    #[test]
+    /// Ensure that a disk without a GPT is rejected
+    fn test_missing_gpt() {
+        let mut img = FromStd::new(Cursor::new(test_img()));
+
+        img.seek(embedded_io::SeekFrom::Start(BLOCK_SIZE)).unwrap();
+        img.write_all(&[0; 8]).unwrap();
+        img.seek(embedded_io::SeekFrom::End(-(BLOCK_SIZE as i64)))
+            .unwrap();
+        img.write_all(&[0; 8]).unwrap();
+
+        assert!(matches!(GPT::new(img), Err(MbrError::MissingGptSignature)));
+    }
+}
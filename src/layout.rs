@@ -0,0 +1,242 @@
+//! Borrowed views over the on-disk layout of sector 0.
+//!
+//! The views read every field straight out of the sector buffer when asked,
+//! without copying the sector or parsing it up front. They also give access
+//! to fields [`PartitionRecord`] leaves out, like the CHS addresses and the
+//! disk ID. Most of the views are const, so tables baked into a bootloader
+//! can be checked at compile time.
+
+use crate::{
+    PartitionId, PartitionRecord, BLOCK_SIZE, BOOT_FLAG_OFFSET, END_CHS_OFFSET, RECORDS_START,
+    RECORD_COUNT, RECORD_LEN, RELATIVE_SECTOR_OFFSET, SIGNATURE, SIGNATURE_OFFSET,
+    START_CHS_OFFSET, SYSTEM_ID_OFFSET, TOTAL_SECTORS_OFFSET,
+};
+
+/// Offset of the disk ID in sector 0
+pub const DISK_ID_OFFSET: usize = 0x1b8;
+
+/// View over the bytes of a single partition record
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RecordView<'a> {
+    bytes: &'a [u8; RECORD_LEN],
+}
+
+impl<'a> RecordView<'a> {
+    #[inline]
+    /// Create a view over the bytes of a record
+    pub const fn new(bytes: &'a [u8; RECORD_LEN]) -> Self {
+        Self { bytes }
+    }
+
+    #[inline]
+    /// Get the raw boot flag, which is 0x80 for bootable partitions
+    pub const fn get_boot_flag(&self) -> u8 {
+        self.bytes[BOOT_FLAG_OFFSET]
+    }
+
+    #[inline]
+    /// Get the CHS address of the first sector
+    pub const fn get_start_chs(&self) -> [u8; 3] {
+        let b = self.bytes;
+
+        [
+            b[START_CHS_OFFSET],
+            b[START_CHS_OFFSET + 1],
+            b[START_CHS_OFFSET + 2],
+        ]
+    }
+
+    #[inline]
+    /// Get the system ID
+    pub const fn get_system_id(&self) -> u8 {
+        self.bytes[SYSTEM_ID_OFFSET]
+    }
+
+    #[inline]
+    /// Get the CHS address of the last sector
+    pub const fn get_end_chs(&self) -> [u8; 3] {
+        let b = self.bytes;
+
+        [
+            b[END_CHS_OFFSET],
+            b[END_CHS_OFFSET + 1],
+            b[END_CHS_OFFSET + 2],
+        ]
+    }
+
+    #[inline]
+    /// Get the first LBA
+    pub const fn get_start_lba(&self) -> u32 {
+        read_u32(self.bytes, RELATIVE_SECTOR_OFFSET)
+    }
+
+    #[inline]
+    /// Get the number of sectors
+    pub const fn get_total_sectors(&self) -> u32 {
+        read_u32(self.bytes, TOTAL_SECTORS_OFFSET)
+    }
+
+    #[inline]
+    /// Get the record the view describes
+    pub const fn to_record(&self) -> PartitionRecord {
+        PartitionRecord {
+            relative_sector: self.get_start_lba(),
+            total_sectors: self.get_total_sectors(),
+            system_id: self.get_system_id(),
+            boot_flag: self.get_boot_flag() == 0x80,
+        }
+    }
+}
+
+/// View over the whole of sector 0
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SectorView<'a> {
+    bytes: &'a [u8; BLOCK_SIZE as usize],
+}
+
+impl<'a> SectorView<'a> {
+    #[inline]
+    /// Create a view over sector 0
+    pub const fn new(bytes: &'a [u8; BLOCK_SIZE as usize]) -> Self {
+        Self { bytes }
+    }
+
+    #[inline]
+    /// Get the boot code before the disk ID
+    pub const fn get_boot_code(&self) -> &'a [u8] {
+        self.bytes.split_at(DISK_ID_OFFSET).0
+    }
+
+    #[inline]
+    /// Get the disk ID
+    pub const fn get_disk_id(&self) -> u32 {
+        read_u32(self.bytes, DISK_ID_OFFSET)
+    }
+
+    /// Get a view over the record of a partition
+    pub const fn get_record(&self, id: PartitionId) -> RecordView<'a> {
+        let start = RECORDS_START as usize + id as usize * RECORD_LEN;
+
+        match self.bytes.split_at(start).1.first_chunk() {
+            Some(bytes) => RecordView::new(bytes),
+            // Every record is within the sector
+            None => unreachable!(),
+        }
+    }
+
+    #[inline]
+    /// Check if the sector ends with the boot signature
+    pub const fn has_signature(&self) -> bool {
+        let b = self.bytes;
+
+        b[SIGNATURE_OFFSET as usize] == SIGNATURE[0]
+            && b[SIGNATURE_OFFSET as usize + 1] == SIGNATURE[1]
+    }
+
+    /// Get every record in the sector
+    pub const fn to_records(&self) -> [PartitionRecord; RECORD_COUNT] {
+        let mut records = [PartitionRecord {
+            relative_sector: 0,
+            total_sectors: 0,
+            system_id: 0,
+            boot_flag: false,
+        }; RECORD_COUNT];
+        let mut i = 0;
+
+        // Iterators can't be used in a const fn
+        while i < RECORD_COUNT {
+            records[i] = self.get_record(PartitionId::ALL[i]).to_record();
+            i += 1;
+        }
+
+        records
+    }
+}
+
+#[inline]
+/// Read a little endian u32 out of a buffer
+const fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::{error::MbrError, layout::*, NoDevice, LBA_ONLY_CHS, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that the views read the same fields as the parsed records, and
+    /// the fields the records leave out
+    fn test_layout_views() {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        sector.copy_from_slice(&TEST_IMG_1[..BLOCK_SIZE as usize]);
+        sector[DISK_ID_OFFSET..DISK_ID_OFFSET + 4].copy_from_slice(&[0x78, 0x56, 0x34, 0x12]);
+
+        let record = PartitionRecord::new(2048, 4096, crate::types::PartitionType::Linux, true);
+        let start = RECORDS_START as usize + RECORD_LEN;
+
+        sector[start..start + RECORD_LEN].copy_from_slice(&record.to_bytes());
+
+        let view = SectorView::new(&sector);
+        let record_view = view.get_record(PartitionId::Two);
+
+        assert!(view.has_signature());
+        assert_eq!(view.get_disk_id(), 0x12345678);
+        assert_eq!(view.get_boot_code().len(), DISK_ID_OFFSET);
+        assert_eq!(record_view.to_record(), record);
+        assert_eq!(record_view.get_boot_flag(), 0x80);
+        assert_eq!(record_view.get_start_chs(), LBA_ONLY_CHS);
+        assert_eq!(record_view.get_end_chs(), LBA_ONLY_CHS);
+        assert_eq!(view.to_records()[1], record);
+    }
+
+    /// Sector with a single bootable FAT32 partition at LBA 2048, built at
+    /// compile time
+    const SECTOR: [u8; BLOCK_SIZE as usize] = {
+        let record: [u8; RECORD_LEN] = [
+            0x80, 0xfe, 0xff, 0xff, 0x0c, 0xfe, 0xff, 0xff, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00,
+        ];
+        let mut sector = [0; BLOCK_SIZE as usize];
+        let mut i = 0;
+
+        while i < RECORD_LEN {
+            sector[RECORDS_START as usize + i] = record[i];
+            i += 1;
+        }
+
+        sector[SIGNATURE_OFFSET as usize] = SIGNATURE[0];
+        sector[SIGNATURE_OFFSET as usize + 1] = SIGNATURE[1];
+        sector
+    };
+
+    const RECORDS: [PartitionRecord; RECORD_COUNT] = SectorView::new(&SECTOR).to_records();
+
+    // Fails to compile if parsing is wrong
+    const _: () = assert!(RECORDS[0].get_start_lba() == 2048);
+    const _: () = assert!(RECORDS[0].get_total_sectors() == 0x10000);
+    const _: () = assert!(RECORDS[0].is_bootable() && !RECORDS[1].is_used());
+
+    #[test]
+    /// Ensure that tables can be parsed at compile time
+    fn test_const_parsing() {
+        const PARSED: Result<MBR<NoDevice>, MbrError<Infallible>> = MBR::from_bytes(&SECTOR);
+        const RECORD: PartitionRecord = SectorView::new(&SECTOR)
+            .get_record(PartitionId::One)
+            .to_record();
+
+        let mbr = PARSED.unwrap();
+
+        assert_eq!(mbr.get_record(PartitionId::One), &RECORDS[0]);
+        assert_eq!(RECORD, RECORDS[0]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Deferred parsing of the MBR, for firmware where every read at boot
+//! counts.
+//!
+//! A [`LazyMBR`] holds on to the device without touching it, and only reads
+//! sector 0 the first time the table is needed.
+
+use embedded_io::blocking::{Read, Seek};
+
+use crate::{error::MbrError, Partition, PartitionIndex, PartitionRecord, MBR, RECORD_COUNT};
+
+/// An MBR that isn't read until it is first used
+pub struct LazyMBR<IO: Read + Seek> {
+    mbr: MBR<IO>,
+    loaded: bool,
+}
+
+impl<IO: Read + Seek> LazyMBR<IO> {
+    /// Create a new lazy MBR from anything that implements embedded_io,
+    /// without reading from it
+    ///
+    /// The signature is checked once the table is read, like [`MBR::new`]
+    pub fn new(io: IO) -> Self {
+        Self {
+            mbr: MBR {
+                partitions: [PartitionRecord::default(); RECORD_COUNT],
+                protect_table: true,
+                verify_signature: true,
+                io,
+            },
+            loaded: false,
+        }
+    }
+
+    #[inline]
+    /// Check if the table has been read yet
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    /// Get the MBR, reading the table if it hasn't been read yet
+    ///
+    /// If reading fails, the next call tries again
+    pub fn get_mbr(&mut self) -> Result<&mut MBR<IO>, MbrError<IO::Error>> {
+        if !self.loaded {
+            self.mbr.reload()?;
+            self.loaded = true;
+        }
+
+        Ok(&mut self.mbr)
+    }
+
+    /// Get a partition, reading the table if it hasn't been read yet
+    pub fn get_partition(
+        &mut self,
+        index: impl Into<PartitionIndex>,
+    ) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        self.get_mbr()?.get_partition(index)
+    }
+
+    /// Turn into a regular MBR, reading the table if it hasn't been read yet
+    pub fn into_mbr(mut self) -> Result<MBR<IO>, MbrError<IO::Error>> {
+        self.get_mbr()?;
+
+        Ok(self.mbr)
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.mbr.io
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{
+        adapters::FromStd,
+        blocking::{Read, Seek, Write},
+        Io, SeekFrom,
+    };
+
+    use crate::{lazy::*, PartitionId, BLOCK_SIZE};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    /// Wrapper counting how many reads, writes and seeks reach the device
+    pub(crate) struct CountIo<T> {
+        pub(crate) inner: T,
+        pub(crate) reads: usize,
+        pub(crate) writes: usize,
+        pub(crate) seeks: usize,
+    }
+
+    impl<T> CountIo<T> {
+        pub(crate) fn new(inner: T) -> Self {
+            Self {
+                inner,
+                reads: 0,
+                writes: 0,
+                seeks: 0,
+            }
+        }
+    }
+
+    impl<T: Io> Io for CountIo<T> {
+        type Error = T::Error;
+    }
+
+    impl<T: Read> Read for CountIo<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl<T: Write> Write for CountIo<T> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.writes += 1;
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.inner.flush()
+        }
+    }
+
+    impl<T: Seek> Seek for CountIo<T> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    /// Ensure that the table is only read once it is needed
+    fn test_lazy_mbr() {
+        let io = CountIo::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec())));
+        let mut mbr = LazyMBR::new(io);
+
+        assert!(!mbr.is_loaded());
+        assert_eq!(mbr.mbr.io.reads, 0);
+
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        partition.read_exact(&mut buf).unwrap();
+
+        assert!(mbr.is_loaded());
+        assert!(mbr
+            .get_mbr()
+            .unwrap()
+            .get_record(PartitionId::One)
+            .is_used());
+
+        // A blank device fails on first use rather than on creation
+        let mut mbr = LazyMBR::new(FromStd::new(Cursor::new(vec![0; BLOCK_SIZE as usize])));
+
+        assert!(matches!(
+            mbr.get_partition(PartitionId::One),
+            Err(MbrError::MissingSignature)
+        ));
+        assert!(!mbr.is_loaded());
+    }
+}
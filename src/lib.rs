@@ -24,7 +24,7 @@
 //! Here's `ape-mbr` being coupled with `ape-fatfs`
 //!
 //! ```rust
-//! use std::io::prelude::*;
+//! use ape_fatfs::io::Write;
 //! use ape_fatfs::{
 //!     fs::{
 //!         FsOptions,
@@ -69,16 +69,58 @@
 //!     # std::fs::remove_file("test.img").unwrap();
 //! }
 //! ```
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
-use core::cmp;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::{cmp, convert::Infallible, fmt};
 use embedded_io::{
     blocking::{Read, Seek, Write},
     Io, SeekFrom,
 };
-use types::PartitionType;
+use error::{MbrError, PartitionError};
+use gpt::GPT;
+use layout::{RecordView, SectorView};
+use superfloppy::Superfloppy;
+use types::{GptPartitionType, PartitionType};
+use validate::{Finding, ValidationReport};
+
+/// Emit a debug event through the log crate, if the `log` feature is
+/// enabled
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    };
+}
 
+pub mod apm;
+#[cfg(feature = "futures-io")]
+pub mod async_io;
+pub mod bounce;
+pub mod bsd;
+pub mod buffered;
+#[cfg(feature = "alloc")]
+pub mod cache;
+mod crc32;
+pub mod ebr;
+pub mod erase;
+pub mod error;
+pub mod gpt;
+pub mod layout;
+pub mod lazy;
+pub mod minix;
+pub mod sfdisk;
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod std_io;
+pub mod superfloppy;
 pub mod types;
+pub mod validate;
+pub mod vectored;
 
 /// Length of each record in bytes
 pub const RECORD_LEN: usize = 16;
@@ -96,8 +138,19 @@ pub const TOTAL_SECTORS_OFFSET: usize = 12;
 pub const SYSTEM_ID_OFFSET: usize = 4;
 /// Offset of the boot indicator flag in a partition record
 pub const BOOT_FLAG_OFFSET: usize = 0;
+/// Offset to the boot signature
+pub const SIGNATURE_OFFSET: u64 = 0x1fe;
+/// Boot signature found at the end of a valid MBR
+pub const SIGNATURE: [u8; 2] = [0x55, 0xaa];
+/// Offset of the starting CHS address in a partition record
+pub const START_CHS_OFFSET: usize = 1;
+/// Offset of the ending CHS address in a partition record
+pub const END_CHS_OFFSET: usize = 5;
+/// CHS address used when a partition is only addressable through LBA
+pub const LBA_ONLY_CHS: [u8; 3] = [0xfe, 0xff, 0xff];
 
 /// ID of each partition
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(usize)]
 pub enum PartitionId {
     One = 0,
@@ -106,33 +159,143 @@ pub enum PartitionId {
     Four = 3,
 }
 
+impl PartitionId {
+    /// All partition IDs, in table order
+    pub const ALL: [PartitionId; RECORD_COUNT] = [
+        PartitionId::One,
+        PartitionId::Two,
+        PartitionId::Three,
+        PartitionId::Four,
+    ];
+
+    #[inline]
+    /// Get the partition ID at an index in the table, if there is one
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+/// Index of any partition reachable through the MBR, primary or logical
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PartitionIndex {
+    /// One of the four partitions in the MBR itself
+    Primary(PartitionId),
+    /// The nth logical partition inside of the extended partition, starting
+    /// at zero
+    ///
+    /// Logical partitions are usually numbered from five, so partition five
+    /// is `Logical(0)`
+    Logical(usize),
+}
+
+impl From<PartitionId> for PartitionIndex {
+    fn from(id: PartitionId) -> Self {
+        PartitionIndex::Primary(id)
+    }
+}
+
 #[inline]
 /// Convert an LBA address to a u64
-pub fn lba_to_u64(lba: u32) -> u64 {
+pub const fn lba_to_u64(lba: u32) -> u64 {
     (lba as u64) * BLOCK_SIZE
 }
 
+/// What a partition does when asked to seek past its end
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SeekPolicy {
+    /// Stop at the end of the partition
+    #[default]
+    Clamp,
+    /// Fail the seek with [`PartitionError::SeekPastEnd`]
+    Error,
+    /// Move past the end, reads there return nothing and writes fail with
+    /// [`PartitionError::OutOfSpace`]
+    Allow,
+}
+
+impl SeekPolicy {
+    /// Find where a seek ends up in a partition of the given length
+    fn resolve<E>(self, seek: SeekFrom, pos: u64, len: u64) -> Result<u64, PartitionError<E>> {
+        // Negative results and overflows are errors, like std's seek
+        let target = match seek {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+        }
+        .ok_or(PartitionError::InvalidSeek)?;
+
+        if target > len {
+            debug!(
+                "Seek to {:#x} past the end of a partition of {:#x} bytes",
+                target, len
+            );
+        }
+
+        match self {
+            // Ensure that we don't go past the partition boundries
+            SeekPolicy::Clamp => Ok(cmp::min(target, len)),
+            SeekPolicy::Error if target > len => Err(PartitionError::SeekPastEnd),
+            SeekPolicy::Error | SeekPolicy::Allow => Ok(target),
+        }
+    }
+}
+
 /// Used to interface with partitions
+///
+/// The partition keeps track of where the device's cursor should be, and
+/// only seeks the device when it isn't there already
 pub struct Partition<'a, IO> {
     start_pos: u64,
     end_pos: u64,
     pos: u64,
+    seek_policy: SeekPolicy,
+    read_only: bool,
+    aligned_only: bool,
+    // Where the device's cursor is, if known
+    device_pos: Option<u64>,
     io: &'a mut IO,
 }
 
 impl<'a, IO: Io + Seek> Partition<'a, IO> {
     /// Create a new partition given the start and end position
-    pub fn new(start_pos: u64, end_pos: u64, io: &'a mut IO) -> Result<Self, <Self as Io>::Error> {
+    ///
+    /// Fails with [`MbrError::InvalidPartitionBounds`] if the partition ends
+    /// before it starts
+    pub fn new(start_pos: u64, end_pos: u64, io: &'a mut IO) -> Result<Self, MbrError<IO::Error>> {
+        if end_pos < start_pos {
+            return Err(MbrError::InvalidPartitionBounds);
+        }
+
         // Seek to the start of the partition
         io.seek(SeekFrom::Start(start_pos))?;
 
+        debug!("Opened partition from {:#x} to {:#x}", start_pos, end_pos);
+
         Ok(Self {
             start_pos,
             end_pos,
             pos: 0,
+            seek_policy: SeekPolicy::default(),
+            read_only: false,
+            aligned_only: false,
+            device_pos: Some(start_pos),
             io,
         })
     }
+
+    /// Create a new partition given the start and end position, which must
+    /// both be aligned to sectors
+    pub fn new_aligned(
+        start_pos: u64,
+        end_pos: u64,
+        io: &'a mut IO,
+    ) -> Result<Self, MbrError<IO::Error>> {
+        if start_pos % BLOCK_SIZE != 0 || end_pos % BLOCK_SIZE != 0 {
+            return Err(MbrError::InvalidPartitionBounds);
+        }
+
+        Self::new(start_pos, end_pos, io)
+    }
 }
 
 impl<'a, IO> Partition<'a, IO> {
@@ -141,325 +304,2783 @@ impl<'a, IO> Partition<'a, IO> {
     pub fn len(&self) -> u64 {
         self.end_pos - self.start_pos
     }
+
+    #[inline]
+    /// Get the length of the partition in whole sectors
+    pub fn len_sectors(&self) -> u64 {
+        self.len() / BLOCK_SIZE
+    }
+
+    #[inline]
+    /// Check if the partition has a length of zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    /// Get the starting position of the partition on the device
+    pub fn get_start_pos(&self) -> u64 {
+        self.start_pos
+    }
+
+    #[inline]
+    /// Get the end position of the partition on the device
+    pub fn get_end_pos(&self) -> u64 {
+        self.end_pos
+    }
+
+    #[inline]
+    /// Get the position of the cursor, relative to the start of the
+    /// partition
+    ///
+    /// The same as `seek(SeekFrom::Current(0))`, without touching the device
+    pub fn get_position(&self) -> u64 {
+        self.pos
+    }
+
+    #[inline]
+    /// Get the number of bytes left between the cursor and the end of the
+    /// partition
+    ///
+    /// This is zero at the end of the partition, and past it when
+    /// [`SeekPolicy::Allow`] is set
+    pub fn remaining(&self) -> u64 {
+        self.len().saturating_sub(self.pos)
+    }
+
+    #[inline]
+    /// Get what the partition does when seeking past its end
+    pub fn get_seek_policy(&self) -> SeekPolicy {
+        self.seek_policy
+    }
+
+    #[inline]
+    /// Set what the partition does when seeking past its end
+    pub fn set_seek_policy(&mut self, seek_policy: SeekPolicy) {
+        self.seek_policy = seek_policy;
+    }
+
+    #[inline]
+    /// Check if writes to the partition are refused
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    #[inline]
+    /// Set whether writes to the partition are refused with
+    /// [`PartitionError::ReadOnly`], so it can be inspected without risk of
+    /// changing it
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    #[inline]
+    /// Make the partition read-only, see [`Partition::set_read_only`]
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    #[inline]
+    /// Check if reads and writes must cover whole sectors
+    pub fn is_aligned_only(&self) -> bool {
+        self.aligned_only
+    }
+
+    #[inline]
+    /// Set whether reads and writes that don't start on a sector boundary
+    /// and cover a whole number of sectors are refused with
+    /// [`PartitionError::Unaligned`]
+    ///
+    /// Useful on raw NAND or eMMC drivers that can't read-modify-write, where
+    /// such a transfer would otherwise corrupt data
+    pub fn set_aligned_only(&mut self, aligned_only: bool) {
+        self.aligned_only = aligned_only;
+    }
+
+    #[inline]
+    /// Only allow reads and writes of whole sectors, see
+    /// [`Partition::set_aligned_only`]
+    pub fn aligned_only(mut self) -> Self {
+        self.aligned_only = true;
+        self
+    }
+
+    /// Ensure that a transfer covers whole sectors, if the partition
+    /// requires it
+    fn check_alignment<E>(&self, offset: u64, len: usize) -> Result<(), PartitionError<E>> {
+        match self.aligned_only && !(offset % BLOCK_SIZE == 0 && len as u64 % BLOCK_SIZE == 0) {
+            true => Err(PartitionError::Unaligned),
+            false => Ok(()),
+        }
+    }
+
+    #[inline]
+    /// Release the partition, giving back the borrowed device
+    ///
+    /// The device's cursor is left wherever the partition last moved it
+    pub fn into_inner(self) -> &'a mut IO {
+        self.io
+    }
+}
+
+impl<'a, IO: Seek> Partition<'a, IO> {
+    /// Seek to the start of a sector, relative to the start of the
+    /// partition
+    ///
+    /// Follows the [`SeekPolicy`] of the partition like any other seek
+    pub fn seek_sector(&mut self, lba: u32) -> Result<(), PartitionError<IO::Error>> {
+        self.seek(SeekFrom::Start(lba_to_u64(lba)))?;
+
+        Ok(())
+    }
+
+    /// Move the cursor back to the start of the partition
+    pub fn rewind(&mut self) -> Result<(), PartitionError<IO::Error>> {
+        self.seek(SeekFrom::Start(0))?;
+
+        Ok(())
+    }
+
+    #[inline]
+    /// Get the sector the cursor is in, relative to the start of the
+    /// partition
+    pub fn position_sector(&self) -> u64 {
+        self.pos / BLOCK_SIZE
+    }
+
+    /// Seek the device back to the cursor of the partition, for when
+    /// something else may have moved it
+    ///
+    /// Only needed when the device is a handle that other code can move too,
+    /// as the partition otherwise knows where the device is
+    pub fn resync(&mut self) -> Result<(), PartitionError<IO::Error>> {
+        self.device_pos = None;
+
+        Ok(self.sync_cursor()?)
+    }
+
+    /// Seek the device to a position, unless it is already there
+    fn seek_device(&mut self, pos: u64) -> Result<(), IO::Error> {
+        if self.device_pos != Some(pos) {
+            self.device_pos = None;
+            self.io.seek(SeekFrom::Start(pos))?;
+            self.device_pos = Some(pos);
+        }
+
+        Ok(())
+    }
+
+    /// Put the device where the cursor of the partition is, which never goes
+    /// past the end of the partition
+    fn sync_cursor(&mut self) -> Result<(), IO::Error> {
+        self.seek_device(self.start_pos + cmp::min(self.pos, self.len()))
+    }
+}
+
+impl<'a, IO: Read + Seek> Partition<'a, IO> {
+    /// Read from an offset into the partition without moving the cursor
+    ///
+    /// Like [`Read::read`], this may read less than the whole buffer, and
+    /// reads nothing at or past the end of the partition
+    pub fn read_at(
+        &mut self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, PartitionError<IO::Error>> {
+        self.check_alignment(offset, buf.len())?;
+
+        let available = self.len().saturating_sub(offset);
+        let len = cmp::min(buf.len() as u64, available) as usize;
+
+        let offset = self.start_pos + cmp::min(offset, self.len());
+
+        self.seek_device(offset)?;
+
+        match self.io.read(&mut buf[..len]) {
+            Ok(read) => {
+                self.device_pos = Some(offset + read as u64);
+                self.sync_cursor()?;
+
+                Ok(read)
+            }
+            Err(error) => {
+                // Report the failed read even if restoring the cursor fails
+                self.device_pos = None;
+                let _ = self.sync_cursor();
+
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Read a whole sector of the partition without moving the cursor
+    ///
+    /// The LBA is relative to the start of the partition. Fails with
+    /// [`PartitionError::UnexpectedEof`] if the sector isn't entirely
+    /// within the partition
+    pub fn read_sector(
+        &mut self,
+        lba: u32,
+        sector: &mut [u8; BLOCK_SIZE as usize],
+    ) -> Result<(), PartitionError<IO::Error>> {
+        let mut offset = lba_to_u64(lba);
+
+        if offset + BLOCK_SIZE > self.len() {
+            return Err(PartitionError::UnexpectedEof);
+        }
+
+        let mut buf = &mut sector[..];
+
+        // The device may return short reads
+        while !buf.is_empty() {
+            match self.read_at(offset, buf)? {
+                0 => return Err(PartitionError::UnexpectedEof),
+                read => {
+                    offset += read as u64;
+                    buf = &mut buf[read..];
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, IO: Read + Seek> Partition<'a, IO> {
+    /// Copy the whole partition to the start of another partition, in
+    /// chunks the size of `buf`, returning the number of bytes copied
+    ///
+    /// Anything with the same error type works as the destination, like a
+    /// partition on another device of the same type or a shared partition.
+    /// Fails with [`PartitionError::OutOfSpace`] before anything is written
+    /// if the destination is smaller, and leaves the rest of a larger
+    /// destination untouched. Both cursors end up after the copied data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty
+    pub fn copy_to<W>(
+        &mut self,
+        other: &mut W,
+        buf: &mut [u8],
+    ) -> Result<u64, PartitionError<IO::Error>>
+    where
+        W: Write<Error = PartitionError<IO::Error>> + Seek,
+    {
+        assert!(!buf.is_empty(), "copy buffer must not be empty");
+
+        if other.seek(SeekFrom::End(0))? < self.len() {
+            return Err(PartitionError::OutOfSpace);
+        }
+
+        self.rewind()?;
+        other.seek(SeekFrom::Start(0))?;
+
+        let mut copied: u64 = 0;
+
+        while copied < self.len() {
+            match self.read(buf)? {
+                0 => return Err(PartitionError::UnexpectedEof),
+                read => {
+                    other.write_all(&buf[..read])?;
+                    copied += read as u64;
+                }
+            }
+        }
+
+        Ok(copied)
+    }
+}
+
+impl<'a, IO: Write + Seek> Partition<'a, IO> {
+    /// Write to an offset into the partition without moving the cursor
+    ///
+    /// Like [`Write::write`], this may write less than the whole buffer, and
+    /// fails with [`PartitionError::OutOfSpace`] at or past the end of the
+    /// partition
+    pub fn write_at(
+        &mut self,
+        offset: u64,
+        buf: &[u8],
+    ) -> Result<usize, PartitionError<IO::Error>> {
+        if self.read_only {
+            return Err(PartitionError::ReadOnly);
+        }
+
+        self.check_alignment(offset, buf.len())?;
+
+        let available = self.len().saturating_sub(offset);
+
+        if available == 0 && !buf.is_empty() {
+            return Err(PartitionError::OutOfSpace);
+        }
+
+        let len = cmp::min(buf.len() as u64, available) as usize;
+
+        let offset = self.start_pos + cmp::min(offset, self.len());
+
+        self.seek_device(offset)?;
+
+        match self.io.write(&buf[..len]) {
+            Ok(written) => {
+                self.device_pos = Some(offset + written as u64);
+                self.sync_cursor()?;
+
+                Ok(written)
+            }
+            Err(error) => {
+                // Report the failed write even if restoring the cursor fails
+                self.device_pos = None;
+                let _ = self.sync_cursor();
+
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Write a whole sector of the partition without moving the cursor
+    ///
+    /// The LBA is relative to the start of the partition. Fails with
+    /// [`PartitionError::OutOfSpace`] if the sector isn't entirely within
+    /// the partition
+    pub fn write_sector(
+        &mut self,
+        lba: u32,
+        sector: &[u8; BLOCK_SIZE as usize],
+    ) -> Result<(), PartitionError<IO::Error>> {
+        let mut offset = lba_to_u64(lba);
+
+        if offset + BLOCK_SIZE > self.len() {
+            return Err(PartitionError::OutOfSpace);
+        }
+
+        let mut buf = &sector[..];
+
+        // The device may return short writes
+        while !buf.is_empty() {
+            match self.write_at(offset, buf)? {
+                0 => return Err(PartitionError::OutOfSpace),
+                written => {
+                    offset += written as u64;
+                    buf = &buf[written..];
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows where the partition is, leaving out the device
+impl<'a, IO> fmt::Debug for Partition<'a, IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partition")
+            .field("start_pos", &self.start_pos)
+            .field("end_pos", &self.end_pos)
+            .field("pos", &self.pos)
+            .field("seek_policy", &self.seek_policy)
+            .field("read_only", &self.read_only)
+            .field("aligned_only", &self.aligned_only)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<'a, IO: Io> Io for Partition<'a, IO> {
-    type Error = IO::Error;
+    type Error = PartitionError<IO::Error>;
 }
 
+/// Reads stop at the end of the partition, returning `Ok(0)` once there is
+/// nothing left, the same as the end of a file
 impl<'a, IO: Read> Read for Partition<'a, IO> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.check_alignment(self.pos, buf.len())?;
+
         // Limit the amount of data available to read to the size of the partition
-        let available = self.len() - self.pos;
+        let available = self.remaining();
 
         let buf_slice = match (buf.len() as u64) < available {
             true => buf,
             false => &mut buf[..available as usize],
         };
 
-        self.pos += buf_slice.len() as u64;
+        // Only count what the device actually read
+        let device_pos = self.device_pos.take();
+        let read = self.io.read(buf_slice)?;
+
+        self.pos += read as u64;
+        self.device_pos = device_pos.map(|pos| pos + read as u64);
 
-        self.io.read(buf_slice)
+        Ok(read)
     }
 }
 
+/// Writes are cut short at the end of the partition, and fail with
+/// [`PartitionError::OutOfSpace`] once there is no room left at all
 impl<'a, IO: Write> Write for Partition<'a, IO> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.read_only {
+            return Err(PartitionError::ReadOnly);
+        }
+
+        self.check_alignment(self.pos, buf.len())?;
+
         // Limit the amount of data available to write to the size of the partition
-        let available = self.len() - self.pos;
+        let available = self.remaining();
+
+        // Returning Ok(0) here would leave write_all spinning or panicking
+        if available == 0 && !buf.is_empty() {
+            return Err(PartitionError::OutOfSpace);
+        }
 
         let buf_slice = match (buf.len() as u64) < available {
             true => buf,
-            false => &buf[..available as usize],
+            false => {
+                debug!("Write of {} bytes cut short to {}", buf.len(), available);
+                &buf[..available as usize]
+            }
         };
 
-        self.pos += buf_slice.len() as u64;
+        let device_pos = self.device_pos.take();
+        let written = self.io.write(buf_slice)?;
 
-        self.io.write(buf_slice)
+        self.pos += written as u64;
+        self.device_pos = device_pos.map(|pos| pos + written as u64);
+
+        Ok(written)
     }
 
     #[inline]
     fn flush(&mut self) -> Result<(), Self::Error> {
-        self.io.flush()
+        Ok(self.io.flush()?)
     }
 }
 
 impl<'a, IO: Seek> Seek for Partition<'a, IO> {
     fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
-        self.pos = match pos {
-            SeekFrom::Start(pos) => {
-                // Ensure that we don't go past the partition boundries
-                cmp::min(pos, self.len())
-            }
-            SeekFrom::Current(pos) => {
-                // Ensure that we don't go past the partition boundries
-                cmp::max(cmp::min((pos as i64) + pos, self.len() as i64), 0) as u64
-            }
-            SeekFrom::End(pos) => {
-                // Ensure that we don't go past the partition boundries
-                cmp::max(cmp::min((self.len() as i64) + pos, self.len() as i64), 0) as u64
-            }
-        };
-
-        self.io.seek(SeekFrom::Start(self.start_pos + self.pos))?;
+        self.pos = self.seek_policy.resolve(pos, self.pos, self.len())?;
+        self.sync_cursor()?;
 
         Ok(self.pos)
     }
 }
 
+/// Summary of everything known about a partition slot, for display or
+/// logging
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// Slot of the partition in the MBR
+    pub id: PartitionId,
+    /// Type of the partition
+    pub partition_type: PartitionType,
+    /// Whether the boot flag is set
+    pub bootable: bool,
+    /// First LBA of the partition
+    pub start_lba: u32,
+    /// Number of sectors in the partition
+    pub sectors: u32,
+    /// Starting position of the partition in bytes
+    pub start_byte: u64,
+    /// Length of the partition in bytes
+    pub len_bytes: u64,
+}
+
 /// Used to store data about partitions in the MBR
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub struct PartitionRecord {
     relative_sector: u32,
     total_sectors: u32,
-    partition_type: PartitionType,
+    system_id: u8,
     boot_flag: bool,
 }
 
 impl PartitionRecord {
-    /// Create a partition record from bytes
-    pub fn from_bytes(bytes: &[u8; RECORD_LEN]) -> Self {
-        let relative_sector_array: [u8; 4] = bytes[RELATIVE_SECTOR_OFFSET..TOTAL_SECTORS_OFFSET]
-            .try_into()
-            .unwrap();
-        let total_sectors_array: [u8; 4] =
-            bytes[TOTAL_SECTORS_OFFSET..RECORD_LEN].try_into().unwrap();
-
-        let relative_sector = u32::from_le_bytes(relative_sector_array);
-        let total_sectors = u32::from_le_bytes(total_sectors_array);
-        
-        let system_id: u8 = bytes[SYSTEM_ID_OFFSET];
-        let boot_flag: bool = bytes[BOOT_FLAG_OFFSET] == 0x80;
-
+    /// Create a new partition record
+    pub const fn new(
+        relative_sector: u32,
+        total_sectors: u32,
+        partition_type: PartitionType,
+        boot_flag: bool,
+    ) -> Self {
         Self {
             relative_sector,
             total_sectors,
-            partition_type: system_id.try_into().unwrap(),
+            system_id: partition_type.id(),
             boot_flag,
         }
     }
 
+    /// Create a partition record from bytes
+    ///
+    /// The system ID is kept as is, so records with IDs this crate doesn't
+    /// know of can still be read and written back unchanged. Being a const
+    /// fn, records baked into a bootloader can be checked at compile time
+    pub const fn from_bytes(bytes: &[u8; RECORD_LEN]) -> Self {
+        RecordView::new(bytes).to_record()
+    }
+
+    /// Convert a partition record to bytes
+    ///
+    /// CHS addresses aren't tracked, so they are written as LBA-only
+    pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut bytes: [u8; RECORD_LEN] = [0; RECORD_LEN];
+
+        bytes[BOOT_FLAG_OFFSET] = match self.boot_flag {
+            true => 0x80,
+            false => 0x00,
+        };
+        bytes[SYSTEM_ID_OFFSET] = self.system_id;
+
+        // Empty records are left zeroed
+        if self.is_used() {
+            bytes[START_CHS_OFFSET..SYSTEM_ID_OFFSET].copy_from_slice(&LBA_ONLY_CHS);
+            bytes[END_CHS_OFFSET..RELATIVE_SECTOR_OFFSET].copy_from_slice(&LBA_ONLY_CHS);
+        }
+
+        bytes[RELATIVE_SECTOR_OFFSET..TOTAL_SECTORS_OFFSET]
+            .copy_from_slice(&self.relative_sector.to_le_bytes());
+        bytes[TOTAL_SECTORS_OFFSET..RECORD_LEN].copy_from_slice(&self.total_sectors.to_le_bytes());
+
+        bytes
+    }
+
+    #[inline]
+    /// Get the first LBA of a partition
+    pub const fn get_start_lba(&self) -> u32 {
+        self.relative_sector
+    }
+
+    #[inline]
+    /// Get the number of sectors in a partition
+    pub const fn get_total_sectors(&self) -> u32 {
+        self.total_sectors
+    }
+
     #[inline]
     /// Get the starting position of a partition
-    pub fn get_start_pos(&self) -> u64 {
+    pub const fn get_start_pos(&self) -> u64 {
         lba_to_u64(self.relative_sector)
     }
 
     #[inline]
     /// Get the end position of a partition
-    pub fn get_end_pos(&self) -> u64 {
+    pub const fn get_end_pos(&self) -> u64 {
         lba_to_u64(self.relative_sector) + lba_to_u64(self.total_sectors)
     }
 
     #[inline]
     /// Get the type of a partition
-    pub fn get_partition_type(&self) -> PartitionType {
-        self.partition_type
+    ///
+    /// System IDs that aren't known are returned as
+    /// `PartitionType::Other`, keeping the original byte
+    pub const fn get_partition_type(&self) -> PartitionType {
+        PartitionType::from_id(self.system_id)
     }
 
     #[inline]
-    /// Check to see if the partition's boot flag is set
-    pub fn is_bootable(&self) -> bool {
-        self.boot_flag
+    /// Get the raw system ID of a partition
+    pub const fn get_system_id(&self) -> u8 {
+        self.system_id
     }
-}
 
-/// Used to grab partitions from the MBR
-pub struct MBR<IO: Read + Seek> {
-    partitions: [PartitionRecord; RECORD_COUNT],
-    io: IO,
-}
+    #[inline]
+    /// Get the type of a partition, or the raw system ID if it isn't known
+    pub const fn try_get_partition_type(&self) -> Result<PartitionType, u8> {
+        let partition_type = PartitionType::from_id(self.system_id);
+
+        if partition_type.is_known() {
+            Ok(partition_type)
+        } else {
+            Err(self.system_id)
+        }
+    }
 
-impl<IO: Read + Seek> MBR<IO> {
-    /// Create a new MBR from anything that implements embedded_io
-    pub fn new(mut io: IO) -> Result<Self, <IO as Io>::Error> {
-        let mut partitions: [PartitionRecord; RECORD_COUNT] =
-            [PartitionRecord::default(); RECORD_COUNT];
-        let mut buffer: [u8; RECORD_LEN * RECORD_COUNT] = [0; RECORD_LEN * RECORD_COUNT];
+    #[inline]
+    /// Check to see if the partition's boot flag is set
+    pub const fn is_bootable(&self) -> bool {
+        self.boot_flag
+    }
 
-        io.seek(SeekFrom::Start(RECORDS_START))?;
-        io.read(&mut buffer)?;
+    #[inline]
+    /// Check if the record covers at least one sector
+    pub const fn is_used(&self) -> bool {
+        self.total_sectors != 0
+    }
 
-        for i in 0..RECORD_COUNT {
-            let buffer_i = i * RECORD_LEN;
+    /// Ensure that a primary partition can be handed out, meaning it covers
+    /// some sectors within 32 bit LBAs and, if asked to, doesn't include the
+    /// MBR sector
+    fn check_primary<E>(&self, protect_table: bool) -> Result<(), MbrError<E>> {
+        if !self.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
 
-            let record_slice = &buffer[buffer_i..buffer_i + RECORD_LEN];
+        if self.exceeds_mbr_addressing() {
+            return Err(MbrError::ExceedsMbrAddressing);
+        }
 
-            partitions[i] = PartitionRecord::from_bytes(record_slice.try_into().unwrap());
+        if protect_table && self.relative_sector == 0 {
+            return Err(MbrError::OverlapsTable);
         }
 
-        Ok(Self { partitions, io })
+        Ok(())
     }
 
-    #[inline]
-    /// Get a partition from the MBR
-    pub fn get_partition(&mut self, id: PartitionId) -> Result<Partition<IO>, IO::Error> {
-        let record = self.partitions[id as usize];
-
-        Partition::new(record.get_start_pos(), record.get_end_pos(), &mut self.io)
+    /// Check if the record reaches past what 32 bit LBAs can address
+    ///
+    /// A start or length of 0xFFFFFFFF is treated as a "too large" sentinel
+    /// rather than a real value, as some tools write it for partitions
+    /// beyond 2 TiB
+    pub const fn exceeds_mbr_addressing(&self) -> bool {
+        self.relative_sector == u32::MAX
+            || self.total_sectors == u32::MAX
+            || self.relative_sector as u64 + self.total_sectors as u64 > u32::MAX as u64 + 1
     }
 
     #[inline]
-    /// Get the partition type from the MBR
-    pub fn get_partition_type(&self, id: PartitionId) -> PartitionType {
-        let record = self.partitions[id as usize];
-
-        record.get_partition_type()
+    /// Check if two records share at least one sector
+    pub const fn overlaps(&self, other: &PartitionRecord) -> bool {
+        self.is_used()
+            && other.is_used()
+            && self.get_start_pos() < other.get_end_pos()
+            && other.get_start_pos() < self.get_end_pos()
     }
+}
 
-    #[inline]
-    /// Check if a partition is bootable in the MBR
-    pub fn is_partition_bootable(&self, id: PartitionId) -> bool {
-        let record = self.partitions[id as usize];
+/// Common interface to partition tables, so code can be written once for
+/// both the MBR and the GPT
+///
+/// Partitions are addressed by their index in the table, starting at zero
+pub trait PartitionTable {
+    /// Device the partition table is stored on
+    type IO: Read + Seek;
+    /// Type used to describe what a partition contains
+    type PartitionType;
+
+    /// Get the number of partitions the table has room for
+    fn partition_count(&self) -> usize;
+
+    /// Get a partition from the table
+    fn get_partition(
+        &mut self,
+        index: usize,
+    ) -> Result<Partition<'_, Self::IO>, MbrError<<Self::IO as Io>::Error>>;
 
-        record.is_bootable()
-    }
+    /// Get the type of a partition
+    fn partition_type_of(
+        &mut self,
+        index: usize,
+    ) -> Result<Self::PartitionType, MbrError<<Self::IO as Io>::Error>>;
+
+    /// Check if a partition is marked as bootable
+    fn is_bootable(&mut self, index: usize) -> Result<bool, MbrError<<Self::IO as Io>::Error>>;
+}
+
+impl<IO: Read + Seek> PartitionTable for MBR<IO> {
+    type IO = IO;
+    type PartitionType = PartitionType;
+
+    #[inline]
+    fn partition_count(&self) -> usize {
+        RECORD_COUNT
+    }
+
+    fn get_partition(&mut self, index: usize) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        MBR::get_partition(self, mbr_index_to_id(index)?)
+    }
+
+    fn partition_type_of(&mut self, index: usize) -> Result<PartitionType, MbrError<IO::Error>> {
+        Ok(self.partitions[mbr_index_to_id(index)? as usize].get_partition_type())
+    }
+
+    fn is_bootable(&mut self, index: usize) -> Result<bool, MbrError<IO::Error>> {
+        Ok(self.is_partition_bootable(mbr_index_to_id(index)?))
+    }
+}
+
+impl<IO: Read + Seek> PartitionTable for GPT<IO> {
+    type IO = IO;
+    type PartitionType = GptPartitionType;
+
+    #[inline]
+    fn partition_count(&self) -> usize {
+        GPT::partition_count(self) as usize
+    }
+
+    fn get_partition(&mut self, index: usize) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        GPT::get_partition(self, gpt_index_to_u32(index)?)
+    }
+
+    fn partition_type_of(&mut self, index: usize) -> Result<GptPartitionType, MbrError<IO::Error>> {
+        Ok(self
+            .get_entry(gpt_index_to_u32(index)?)?
+            .get_partition_type())
+    }
+
+    /// GPT partitions are bootable if their legacy BIOS bootable attribute
+    /// is set
+    fn is_bootable(&mut self, index: usize) -> Result<bool, MbrError<IO::Error>> {
+        Ok(self
+            .get_entry(gpt_index_to_u32(index)?)?
+            .get_attributes()
+            .is_legacy_bios_bootable())
+    }
+}
+
+#[inline]
+/// Convert a partition table index to an MBR partition ID
+fn mbr_index_to_id<E>(index: usize) -> Result<PartitionId, MbrError<E>> {
+    PartitionId::from_index(index).ok_or(MbrError::InvalidPartitionIndex(index as u32))
+}
+
+#[inline]
+/// Convert a partition table index to a GPT entry index
+fn gpt_index_to_u32<E>(index: usize) -> Result<u32, MbrError<E>> {
+    index
+        .try_into()
+        .map_err(|_| MbrError::InvalidPartitionIndex(u32::MAX))
+}
+
+/// A disk opened by [`open_disk`], holding whichever partition table was
+/// found on it
+pub enum Disk<IO: Read + Seek> {
+    /// The disk has a plain MBR
+    Mbr(MBR<IO>),
+    /// The disk has a GPT behind a protective or hybrid MBR
+    Gpt(GPT<IO>),
+    /// The disk has no partition table, and holds a file system directly
+    Superfloppy(Superfloppy<IO>),
+}
+
+/// Open a disk, detecting whether it uses an MBR, a GPT, or no partition
+/// table at all
+pub fn open_disk<IO: Read + Seek>(mut io: IO) -> Result<Disk<IO>, MbrError<IO::Error>> {
+    if !superfloppy::has_partition_table(&mut io)? {
+        return Ok(Disk::Superfloppy(Superfloppy::new(io)));
+    }
+
+    let mbr = MBR::new(io)?;
+
+    match mbr.has_gpt() {
+        true => Ok(Disk::Gpt(mbr.into_gpt()?)),
+        false => Ok(Disk::Mbr(mbr)),
+    }
+}
+
+/// Used to grab partitions from the MBR
+///
+/// The device can be given by value, or as a `&mut` since embedded_io is
+/// implemented for mutable references too, in which case the caller keeps
+/// ownership and gets the device back once the MBR is dropped
+pub struct MBR<IO: Read + Seek> {
+    partitions: [PartitionRecord; RECORD_COUNT],
+    protect_table: bool,
+    verify_signature: bool,
+    io: IO,
+}
+
+/// Empty stand-in for the device of an MBR parsed with [`MBR::from_bytes`]
+///
+/// It holds no data, so every partition reads as empty
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct NoDevice;
+
+impl Io for NoDevice {
+    type Error = Infallible;
+}
+
+impl Read for NoDevice {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(0)
+    }
+}
+
+impl Seek for NoDevice {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<u64, Self::Error> {
+        Ok(0)
+    }
+}
+
+impl MBR<NoDevice> {
+    /// Parse an MBR from a sector that is already in memory, without any
+    /// device
+    ///
+    /// Fails with [`MbrError::MissingSignature`] if the sector doesn't end
+    /// with the 0x55AA boot signature. Everything that only looks at the
+    /// records works as usual, but there is nothing to read partitions from.
+    /// Being a const fn, a table baked into a bootloader can be parsed at
+    /// compile time
+    pub const fn from_bytes(
+        sector: &[u8; BLOCK_SIZE as usize],
+    ) -> Result<Self, MbrError<Infallible>> {
+        let view = SectorView::new(sector);
+
+        if !view.has_signature() {
+            return Err(MbrError::MissingSignature);
+        }
+
+        Ok(Self {
+            partitions: view.to_records(),
+            protect_table: true,
+            verify_signature: true,
+            io: NoDevice,
+        })
+    }
+}
+
+/// Shows the partition records, leaving out the device
+impl<IO: Read + Seek> fmt::Debug for MBR<IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MBR")
+            .field("partitions", &self.partitions)
+            .field("protect_table", &self.protect_table)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Create a new MBR from anything that implements embedded_io
+    ///
+    /// Fails with [`MbrError::MissingSignature`] if sector 0 doesn't end with
+    /// the 0x55AA boot signature. Like all metadata this crate reads, the
+    /// table is read in full even if the device returns short reads, and a
+    /// device that ends early fails with [`MbrError::UnexpectedEof`]
+    pub fn new(io: IO) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        Self::open(io, true)
+    }
+
+    /// Create a new MBR without checking the boot signature
+    ///
+    /// Useful for raw or forensic work on damaged disks, a blank device
+    /// opens as four empty partitions
+    pub fn new_unchecked(io: IO) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        Self::open(io, false)
+    }
+
+    /// Open the MBR, checking the signature if asked to
+    fn open(mut io: IO, verify: bool) -> Result<Self, MbrError<<IO as Io>::Error>> {
+        let partitions = Self::read_records(&mut io, verify)?;
+
+        Ok(Self {
+            partitions,
+            protect_table: true,
+            verify_signature: verify,
+            io,
+        })
+    }
+
+    /// Read the partition records, and the signature if asked to
+    fn read_records(
+        io: &mut IO,
+        verify: bool,
+    ) -> Result<[PartitionRecord; RECORD_COUNT], MbrError<IO::Error>> {
+        let mut buffer: [u8; TABLE_LEN] = [0; TABLE_LEN];
+
+        io.seek(SeekFrom::Start(RECORDS_START))?;
+        io.read_exact(&mut buffer)?;
+
+        let partitions = parse_records(&buffer, verify)?;
+
+        debug!("Read partition table: {:?}", partitions);
+
+        Ok(partitions)
+    }
+
+    /// Read the partition table from the device again, for when something
+    /// else may have rewritten it
+    ///
+    /// Changes that weren't flushed are lost. The signature is checked
+    /// unless the MBR was opened with [`MBR::new_unchecked`], and on failure
+    /// the records are left as they were
+    pub fn reload(&mut self) -> Result<(), MbrError<IO::Error>> {
+        self.partitions = Self::read_records(&mut self.io, self.verify_signature)?;
+
+        Ok(())
+    }
+
+    /// Create a new MBR, ensuring that no partition extends past the end of
+    /// the device
+    ///
+    /// The size of the device is found by seeking to its end
+    pub fn new_checked(io: IO) -> Result<Self, MbrError<IO::Error>> {
+        let mut mbr = Self::new(io)?;
+
+        mbr.check_bounds()?;
+
+        Ok(mbr)
+    }
+
+    /// Get the size of the underlying device in bytes
+    pub fn device_len(&mut self) -> Result<u64, MbrError<IO::Error>> {
+        Ok(self.io.seek(SeekFrom::End(0))?)
+    }
+
+    /// Ensure that no partition extends past the end of the device
+    pub fn check_bounds(&mut self) -> Result<(), MbrError<IO::Error>> {
+        let device_len = self.device_len()?;
+
+        for (id, record) in PartitionId::ALL.into_iter().zip(self.partitions.iter()) {
+            if record.is_used() && record.exceeds_mbr_addressing() {
+                return Err(MbrError::ExceedsMbrAddressing);
+            }
+
+            if record.is_used() && record.get_end_pos() > device_len {
+                return Err(MbrError::PastEndOfDevice(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get a partition from the MBR
+    ///
+    /// Accepts either a [`PartitionId`] for a primary partition, or a
+    /// [`PartitionIndex`] which can also address logical partitions. Fails
+    /// with [`MbrError::EmptyPartition`] if the record doesn't cover any
+    /// sectors, rather than handing out the MBR sector itself
+    pub fn get_partition(
+        &mut self,
+        index: impl Into<PartitionIndex>,
+    ) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        match index.into() {
+            PartitionIndex::Primary(id) => {
+                let record = self.partitions[id as usize];
+
+                record.check_primary(self.protect_table)?;
+
+                Partition::new(record.get_start_pos(), record.get_end_pos(), &mut self.io)
+            }
+            PartitionIndex::Logical(n) => self.get_logical_partition(n),
+        }
+    }
+
+    /// Get a partition covering the whole device, from LBA 0 to the end,
+    /// which includes the MBR itself
+    pub fn get_whole_disk(&mut self) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let end_pos = self.device_len()?;
+
+        Partition::new(0, end_pos, &mut self.io)
+    }
+
+    #[inline]
+    /// Set whether partitions that include the sector of their partition
+    /// table are refused, which is on by default
+    ///
+    /// Turning this off lets a corrupt record hand the table itself to a
+    /// file system, so it should only be done for raw or forensic work
+    pub fn set_table_protection(&mut self, enabled: bool) {
+        self.protect_table = enabled;
+    }
+
+    /// Parse an MBR stored at the start of a partition
+    ///
+    /// The nested MBR borrows this one, and its partitions are relative to
+    /// the start of the partition holding it
+    pub fn get_nested_mbr(
+        &mut self,
+        index: impl Into<PartitionIndex>,
+    ) -> Result<MBR<Partition<'_, IO>>, MbrError<PartitionError<IO::Error>>> {
+        let partition = self
+            .get_partition(index)
+            .map_err(|error| error.map_io(PartitionError::Io))?;
+
+        MBR::new_checked(partition)
+    }
+
+    #[inline]
+    /// Get the record of a partition
+    pub fn get_record(&self, id: PartitionId) -> &PartitionRecord {
+        &self.partitions[id as usize]
+    }
+
+    #[inline]
+    /// Get a mutable reference to the record of a partition
+    ///
+    /// Like [`MBR::set_record`], changes are only kept in memory until the
+    /// MBR is flushed
+    pub fn get_record_mut(&mut self, id: PartitionId) -> &mut PartitionRecord {
+        &mut self.partitions[id as usize]
+    }
+
+    /// Iterate over every partition record in the MBR, including empty ones
+    pub fn partitions(&self) -> impl Iterator<Item = (PartitionId, &PartitionRecord)> + '_ {
+        PartitionId::ALL.into_iter().zip(self.partitions.iter())
+    }
+
+    /// Iterate over the partition records in the MBR that are in use
+    pub fn used_partitions(&self) -> impl Iterator<Item = (PartitionId, &PartitionRecord)> + '_ {
+        self.partitions().filter(|(_, record)| record.is_used())
+    }
+
+    #[inline]
+    /// Get the partition type from the MBR
+    pub fn get_partition_type(&self, id: PartitionId) -> PartitionType {
+        let record = self.partitions[id as usize];
+
+        record.get_partition_type()
+    }
+
+    /// Get a summary of a partition from the MBR
+    pub fn get_info(&self, id: PartitionId) -> PartitionInfo {
+        let record = self.partitions[id as usize];
+
+        PartitionInfo {
+            id,
+            partition_type: record.get_partition_type(),
+            bootable: record.is_bootable(),
+            start_lba: record.get_start_lba(),
+            sectors: record.get_total_sectors(),
+            start_byte: record.get_start_pos(),
+            len_bytes: lba_to_u64(record.get_total_sectors()),
+        }
+    }
+
+    #[inline]
+    /// Check if a slot of the MBR holds a partition, meaning it has a
+    /// system ID other than 0x00 and covers at least one sector
+    ///
+    /// Slots with system IDs this crate doesn't know of still count as
+    /// used
+    pub fn is_slot_used(&self, id: PartitionId) -> bool {
+        let record = self.partitions[id as usize];
+
+        record.get_system_id() != 0 && record.is_used()
+    }
+
+    #[inline]
+    /// Check if a slot of the MBR is empty, the opposite of
+    /// [`MBR::is_slot_used`]
+    pub fn is_slot_empty(&self, id: PartitionId) -> bool {
+        !self.is_slot_used(id)
+    }
+
+    #[inline]
+    /// Get the first LBA of a partition from the MBR
+    pub fn get_start_lba(&self, id: PartitionId) -> u32 {
+        let record = self.partitions[id as usize];
+
+        record.get_start_lba()
+    }
+
+    #[inline]
+    /// Get the number of sectors in a partition from the MBR
+    pub fn get_total_sectors(&self, id: PartitionId) -> u32 {
+        let record = self.partitions[id as usize];
+
+        record.get_total_sectors()
+    }
+
+    #[inline]
+    /// Check if a partition is bootable in the MBR
+    pub fn is_partition_bootable(&self, id: PartitionId) -> bool {
+        let record = self.partitions[id as usize];
+
+        record.is_bootable()
+    }
+
+    /// Find the first used partition of the given type
+    pub fn find_by_type(&self, partition_type: PartitionType) -> Option<PartitionId> {
+        self.find_all_by_type(partition_type).next()
+    }
+
+    /// Find every used partition of the given type
+    pub fn find_all_by_type(
+        &self,
+        partition_type: PartitionType,
+    ) -> impl Iterator<Item = PartitionId> + '_ {
+        self.used_partitions()
+            .filter(move |(_, record)| record.get_partition_type() == partition_type)
+            .map(|(id, _)| id)
+    }
+
+    /// Find the used partition with the most sectors
+    ///
+    /// If several partitions share the largest size, the first one is
+    /// returned
+    pub fn largest_partition(&self) -> Option<PartitionId> {
+        self.used_partitions()
+            .reduce(
+                |largest, (id, record)| match record.total_sectors > largest.1.total_sectors {
+                    true => (id, record),
+                    false => largest,
+                },
+            )
+            .map(|(id, _)| id)
+    }
+
+    /// Get the first used partition with the boot flag set, which is
+    /// normally the only one
+    pub fn first_bootable(&self) -> Option<(PartitionId, &PartitionRecord)> {
+        self.used_partitions()
+            .find(|(_, record)| record.is_bootable())
+    }
+
+    /// Check if the MBR is a protective MBR, meaning the disk actually
+    /// uses a GPT
+    pub fn is_protective(&self) -> bool {
+        let mut used = self.partitions.iter().filter(|r| r.is_used());
+
+        match (used.next(), used.next()) {
+            (Some(record), None) => record.get_partition_type() == PartitionType::GPT,
+            _ => false,
+        }
+    }
+
+    /// Check if any record is a 0xEE record, meaning the disk uses a GPT
+    /// behind either a protective or a hybrid MBR
+    pub fn has_gpt(&self) -> bool {
+        self.used_partitions()
+            .any(|(_, record)| record.get_partition_type() == PartitionType::GPT)
+    }
+
+    /// Open the GPT behind a protective or hybrid MBR
+    pub fn into_gpt(self) -> Result<GPT<IO>, MbrError<IO::Error>> {
+        GPT::new(self.io)
+    }
+
+    #[inline]
+    /// Get a reference to the underlying device
+    pub fn get_io(&self) -> &IO {
+        &self.io
+    }
+
+    #[inline]
+    /// Get a mutable reference to the underlying device, for raw access
+    /// outside of any partition
+    ///
+    /// Writes through it bypass the in-memory records, which are only
+    /// read when the MBR is opened
+    pub fn get_io_mut(&mut self) -> &mut IO {
+        &mut self.io
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+
+    /// Get the first pair of partitions that share at least one sector
+    ///
+    /// Returns `None` if no partitions overlap
+    pub fn overlapping_partitions(&self) -> Option<(PartitionId, PartitionId)> {
+        self.overlapping_pairs().next()
+    }
+
+    /// Iterate over every pair of partitions that share at least one sector
+    fn overlapping_pairs(&self) -> impl Iterator<Item = (PartitionId, PartitionId)> + '_ {
+        (0..RECORD_COUNT)
+            .flat_map(|i| (i + 1..RECORD_COUNT).map(move |j| (i, j)))
+            .filter(|(i, j)| self.partitions[*i].overlaps(&self.partitions[*j]))
+            .map(|(i, j)| (PartitionId::ALL[i], PartitionId::ALL[j]))
+    }
+
+    /// Replace the record of a partition
+    ///
+    /// The change is only kept in memory until the MBR is flushed
+    pub fn set_record(&mut self, id: PartitionId, record: PartitionRecord) {
+        self.partitions[id as usize] = record;
+    }
+
+    /// Check the MBR for common problems, returning a report of everything
+    /// that was found
+    ///
+    /// An empty report means no problems were found
+    pub fn validate(&mut self) -> Result<ValidationReport, MbrError<IO::Error>> {
+        let mut report = ValidationReport::new();
+        let mut signature: [u8; 2] = [0; 2];
+
+        self.io.seek(SeekFrom::Start(SIGNATURE_OFFSET))?;
+        self.io.read_exact(&mut signature)?;
+
+        if signature != SIGNATURE {
+            report.push(Finding::MissingSignature);
+        }
+
+        let bootable_count = self.partitions.iter().filter(|r| r.is_bootable()).count();
+
+        if bootable_count > 1 {
+            report.push(Finding::MultipleBootable);
+        }
+
+        for (a, b) in self.overlapping_pairs() {
+            report.push(Finding::Overlap(a, b));
+        }
+
+        let device_len = self.device_len()?;
+
+        for (id, record) in PartitionId::ALL.into_iter().zip(self.partitions.iter()) {
+            if record.is_used() && record.get_end_pos() > device_len {
+                report.push(Finding::PastEndOfDevice(id));
+            }
+
+            if record.is_used() && record.exceeds_mbr_addressing() {
+                report.push(Finding::ExceedsMbrAddressing(id));
+            }
+
+            if !record.is_used() && record.get_system_id() != 0 {
+                report.push(Finding::ZeroLength(id));
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl<IO: Read + Write + Seek> MBR<IO> {
+    /// Open an MBR, restoring sector 0 from a backup written by
+    /// [`MBR::flush_journaled`] if sector 0 doesn't hold a valid table
+    ///
+    /// Sector 0 is left untouched if the backup isn't valid either, and
+    /// [`MbrError::MissingSignature`] is returned
+    pub fn recover(mut io: IO, backup_lba: u32) -> Result<Self, MbrError<IO::Error>> {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        io.seek(SeekFrom::Start(0))?;
+        io.read_exact(&mut sector)?;
+
+        if !has_signature(&sector) {
+            io.seek(SeekFrom::Start(lba_to_u64(backup_lba)))?;
+            io.read_exact(&mut sector)?;
+
+            if has_signature(&sector) {
+                io.seek(SeekFrom::Start(0))?;
+                io.write_all(&sector)?;
+                io.flush()?;
+            }
+        }
+
+        Self::new(io)
+    }
+
+    /// Write the partition table to sector 0
+    ///
+    /// The boot code is preserved, as are the bytes of any record that
+    /// hasn't changed
+    pub fn flush(&mut self) -> Result<(), MbrError<IO::Error>> {
+        let sector = self.build_sector()?;
+
+        self.write_sector(0, &sector)?;
+
+        Ok(())
+    }
+
+    /// Write the partition table to a backup sector before writing it to
+    /// sector 0, so that a valid table exists on the disk if power is lost
+    /// part way through
+    ///
+    /// The backup sector must be outside of every partition, and should be
+    /// passed to [`MBR::recover`] when the disk is next opened
+    pub fn flush_journaled(&mut self, backup_lba: u32) -> Result<(), MbrError<IO::Error>> {
+        let backup_pos = lba_to_u64(backup_lba);
+        let backup_in_partition = self.partitions.iter().any(|r| {
+            r.is_used() && r.get_start_pos() <= backup_pos && backup_pos < r.get_end_pos()
+        });
+
+        if backup_lba == 0 || backup_in_partition {
+            return Err(MbrError::InvalidBackupLba(backup_lba));
+        }
+
+        let sector = self.build_sector()?;
+
+        self.write_sector(backup_lba, &sector)?;
+        self.write_sector(0, &sector)?;
+
+        Ok(())
+    }
+
+    /// Build the contents of sector 0 from what's currently on the disk and
+    /// the records held in memory
+    fn build_sector(&mut self) -> Result<[u8; BLOCK_SIZE as usize], MbrError<IO::Error>> {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        self.io.seek(SeekFrom::Start(0))?;
+        self.io.read_exact(&mut sector)?;
+
+        for (i, record) in self.partitions.iter().enumerate() {
+            let record_i = RECORDS_START as usize + i * RECORD_LEN;
+            let record_slice = &mut sector[record_i..record_i + RECORD_LEN];
+
+            if PartitionRecord::from_bytes((&*record_slice).try_into().unwrap()) != *record {
+                record_slice.copy_from_slice(&record.to_bytes());
+            }
+        }
+
+        sector[SIGNATURE_OFFSET as usize..].copy_from_slice(&SIGNATURE);
+
+        Ok(sector)
+    }
+
+    /// Write and flush a whole sector
+    fn write_sector(
+        &mut self,
+        lba: u32,
+        sector: &[u8; BLOCK_SIZE as usize],
+    ) -> Result<(), IO::Error> {
+        debug!("Writing table sector at LBA {}", lba);
+
+        self.io.seek(SeekFrom::Start(lba_to_u64(lba)))?;
+        self.io.write_all(sector)?;
+        self.io.flush()
+    }
+}
+
+/// Length of the partition records followed by the signature
+const TABLE_LEN: usize = RECORD_LEN * RECORD_COUNT + SIGNATURE.len();
+
+/// Parse the partition records read from the end of sector 0, checking the
+/// signature if asked to
+fn parse_records<E>(
+    buffer: &[u8; TABLE_LEN],
+    verify: bool,
+) -> Result<[PartitionRecord; RECORD_COUNT], MbrError<E>> {
+    let mut partitions: [PartitionRecord; RECORD_COUNT] =
+        [PartitionRecord::default(); RECORD_COUNT];
+
+    if verify && buffer[RECORD_LEN * RECORD_COUNT..] != SIGNATURE {
+        return Err(MbrError::MissingSignature);
+    }
+
+    for (i, partition) in partitions.iter_mut().enumerate() {
+        let buffer_i = i * RECORD_LEN;
+
+        let record_slice = &buffer[buffer_i..buffer_i + RECORD_LEN];
+
+        *partition = RecordView::new(record_slice.try_into().unwrap()).to_record();
+    }
+
+    Ok(partitions)
+}
+
+#[inline]
+/// Check if a sector ends with the boot signature
+fn has_signature(sector: &[u8; BLOCK_SIZE as usize]) -> bool {
+    sector[SIGNATURE_OFFSET as usize..] == SIGNATURE
 }
 
 #[cfg(test)]
 mod tests {
-    use core::panic::AssertUnwindSafe;
-    use std::{io::Cursor, panic};
+    use std::io::Cursor;
+
+    use ape_fatfs::{fs::{FileSystem, FsOptions, FatType}, io::StdIoWrapper};
+    use embedded_io::{
+        adapters::FromStd,
+        blocking::{Read, ReadExactError, Seek, Write},
+    };
+
+    use crate::{lazy::tests::CountIo, types::PartitionKind, validate::Finding, *};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+    static TEST_IMG_2: &[u8] = include_bytes!("../resources/test2.img");
+    static TEST_STR_1: [u8; 10] = *b"Partition1";
+    static TEST_STR_2: [u8; 10] = *b"Partition2";
+    static TEST_STR_3: [u8; 10] = *b"Partition3";
+    static TEST_STR_4: [u8; 10] = *b"Partition4";
+
+    #[test]
+    #[allow(clippy::drop_non_drop)]
+    /// The dummy image is a four partition image with "Partition" witten to the
+    /// start of each partition and the partition number written to the end
+    /// each partition
+    ///
+    /// Partitions sizes in sectors:
+    ///     First partition size: 17
+    ///     Second partition size: 33
+    ///     Third partition size: 65
+    ///     Fourth partition size: 84
+    fn test_dummy_img() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+
+        // Test partition 1
+        let mut partition_1 = mbr.get_partition(PartitionId::One).unwrap();
+
+        let mut buf: [u8; 10] = [0; 10];
+
+        partition_1.read_exact(&mut buf[..9]).unwrap();
+        partition_1.seek(embedded_io::SeekFrom::End(-1)).unwrap();
+        partition_1.read_exact(&mut buf[9..]).unwrap();
+        assert_eq!(partition_1.len(), 17 * BLOCK_SIZE);
+        assert_eq!(buf, TEST_STR_1);
+        drop(partition_1);
+
+        // Test partition 2
+        let mut partition_2 = mbr.get_partition(PartitionId::Two).unwrap();
+
+        partition_2.read_exact(&mut buf[..9]).unwrap();
+        partition_2.seek(embedded_io::SeekFrom::End(-1)).unwrap();
+        partition_2.read_exact(&mut buf[9..]).unwrap();
+        assert_eq!(partition_2.len(), 33 * BLOCK_SIZE);
+        assert_eq!(buf, TEST_STR_2);
+        drop(partition_2);
+
+        // Test partition 3
+        let mut partition_3 = mbr.get_partition(PartitionId::Three).unwrap();
+
+        partition_3.read_exact(&mut buf[..9]).unwrap();
+        partition_3.seek(embedded_io::SeekFrom::End(-1)).unwrap();
+        partition_3.read_exact(&mut buf[9..]).unwrap();
+        assert_eq!(partition_3.len(), 65 * BLOCK_SIZE);
+        assert_eq!(buf, TEST_STR_3);
+        drop(partition_3);
+
+        // Test partition 2
+        let mut partition_4 = mbr.get_partition(PartitionId::Four).unwrap();
+
+        partition_4.read_exact(&mut buf[..9]).unwrap();
+        partition_4.seek(embedded_io::SeekFrom::End(-1)).unwrap();
+        partition_4.read_exact(&mut buf[9..]).unwrap();
+        assert_eq!(partition_4.len(), 84 * BLOCK_SIZE);
+        assert_eq!(buf, TEST_STR_4);
+    }
+
+    #[test]
+    /// The "real" image is a three partition image designed to simulate a real
+    /// drive
+    ///
+    /// Partition layout is as follows:
+    ///     fat12, 2000 sectors, boot flag set
+    ///     fat16, 5000 sectors
+    ///     fat32, 68000 sectors
+    fn test_real_img() {
+        let img = StdIoWrapper::new(Cursor::new(TEST_IMG_2.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+
+        // Test partition 1
+        assert_eq!(mbr.get_partition_type(PartitionId::One), PartitionType::Fat12);
+        assert!(mbr.is_partition_bootable(PartitionId::One));
+
+        {
+            let partition = mbr.get_partition(PartitionId::One).unwrap();
+
+            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+
+            assert_eq!(fs.fat_type(), FatType::Fat12);
+        }
+
+        // Test partition 2
+        assert_eq!(mbr.get_partition_type(PartitionId::Two), PartitionType::Fat16);
+        assert!(!mbr.is_partition_bootable(PartitionId::Two));
+
+        {
+            let partition = mbr.get_partition(PartitionId::Two).unwrap();
+
+            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+
+            assert_eq!(fs.fat_type(), FatType::Fat16);
+        }
+
+        // Test partition 3
+        assert_eq!(mbr.get_partition_type(PartitionId::Three), PartitionType::W95Fat32);
+        assert!(!mbr.is_partition_bootable(PartitionId::Three));
+
+        {
+            let partition = mbr.get_partition(PartitionId::Three).unwrap();
+
+            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+
+            assert_eq!(fs.fat_type(), FatType::Fat32);
+        }
+    }
+
+    #[test]
+    /// Ensure that we cannot read or write past the end of the partition
+    fn test_bounds() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+
+        let mut buf: [u8; 10] = [0; 10];
+        let mut partition_1 = mbr.get_partition(PartitionId::One).unwrap();
+
+        partition_1.seek(embedded_io::SeekFrom::End(0)).unwrap();
+
+        partition_1.read_exact(&mut buf).unwrap_err();
+        assert!(matches!(
+            partition_1.write_all(&buf),
+            Err(PartitionError::OutOfSpace)
+        ));
+    }
+
+    #[test]
+    /// Ensure that every kind of seek is relative to the right place, and
+    /// that seeking before the start is an error
+    fn test_seek() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let len = partition.len();
+
+        assert_eq!(partition.seek(SeekFrom::Start(16)).unwrap(), 16);
+        assert_eq!(partition.seek(SeekFrom::Current(8)).unwrap(), 24);
+        assert_eq!(partition.seek(SeekFrom::Current(-4)).unwrap(), 20);
+        assert_eq!(partition.seek(SeekFrom::End(-2)).unwrap(), len - 2);
+
+        // Past the end is clamped
+        assert_eq!(partition.seek(SeekFrom::Start(len + 1)).unwrap(), len);
+        assert_eq!(partition.seek(SeekFrom::End(1)).unwrap(), len);
+
+        // Before the start is an error, and leaves the position alone
+        assert!(matches!(
+            partition.seek(SeekFrom::Current(-(len as i64) - 1)),
+            Err(PartitionError::InvalidSeek)
+        ));
+        assert!(matches!(
+            partition.seek(SeekFrom::End(i64::MIN)),
+            Err(PartitionError::InvalidSeek)
+        ));
+        assert_eq!(partition.seek(SeekFrom::Current(0)).unwrap(), len);
+
+        // The position is relative to the partition, not the device
+        let mut first: [u8; 4] = [0; 4];
+        let mut again: [u8; 4] = [0; 4];
+
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.read_exact(&mut first).unwrap();
+        partition.seek(SeekFrom::Current(-4)).unwrap();
+        partition.read_exact(&mut again).unwrap();
+
+        assert_eq!(first, again);
+        assert_eq!(
+            first[..],
+            TEST_IMG_1[lba_to_u64(mbr.partitions[0].relative_sector) as usize..][..4]
+        );
+    }
+
+    /// Device that never reads more than a few bytes at a time
+    struct ShortReads<T>(T);
+
+    impl<T: Io> Io for ShortReads<T> {
+        type Error = T::Error;
+    }
+
+    impl<T: Read> Read for ShortReads<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = cmp::min(buf.len(), 7);
+
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl<T: Seek> Seek for ShortReads<T> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    /// Ensure that short reads don't leave the table half read, and that a
+    /// truncated device is reported
+    fn test_short_reads() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let short = ShortReads(FromStd::new(Cursor::new(TEST_IMG_1.to_vec())));
+
+        assert_eq!(
+            MBR::new(img).unwrap().partitions,
+            MBR::new(short).unwrap().partitions
+        );
+
+        let img = FromStd::new(Cursor::new(TEST_IMG_1[..0x1c0].to_vec()));
+
+        assert!(matches!(MBR::new(img), Err(MbrError::UnexpectedEof)));
+    }
+
+    #[test]
+    /// Ensure that partitions with bad bounds can't be created
+    fn test_partition_bounds() {
+        let mut img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        assert!(matches!(
+            Partition::new(BLOCK_SIZE, 0, &mut img),
+            Err(MbrError::InvalidPartitionBounds)
+        ));
+        assert!(matches!(
+            Partition::new_aligned(BLOCK_SIZE, BLOCK_SIZE + 1, &mut img),
+            Err(MbrError::InvalidPartitionBounds)
+        ));
+        assert_eq!(Partition::new(1, 1, &mut img).unwrap().len(), 0);
+        assert_eq!(
+            Partition::new_aligned(BLOCK_SIZE, 2 * BLOCK_SIZE, &mut img)
+                .unwrap()
+                .len(),
+            BLOCK_SIZE
+        );
+    }
+
+    #[test]
+    /// Ensure that records covering the partition table can't be opened
+    /// unless asked for
+    fn test_table_protection() {
+        let mut img = TEST_IMG_1.to_vec();
+
+        img[RECORDS_START as usize + RELATIVE_SECTOR_OFFSET..][..4].fill(0);
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_partition(PartitionId::One),
+            Err(MbrError::OverlapsTable)
+        ));
+
+        mbr.set_table_protection(false);
+
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut buf: [u8; 2] = [0; 2];
+
+        partition.seek(SeekFrom::Start(SIGNATURE_OFFSET)).unwrap();
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, SIGNATURE);
+
+        // A logical partition starting on its own EBR
+        let mut img = ebr::tests::test_img();
+
+        ebr::tests::write_table(
+            &mut img,
+            50,
+            &[PartitionRecord::new(0, 20, PartitionType::Fat16, false)],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_partition(PartitionIndex::Logical(1)),
+            Err(MbrError::OverlapsTable)
+        ));
+    }
+
+    #[test]
+    /// Ensure that reads and writes behave at and around the end of the
+    /// partition
+    fn test_eof() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let len = partition.len();
+        let mut buf: [u8; 4] = [0; 4];
+
+        assert_eq!(partition.remaining(), len);
+
+        // A read straddling the end is cut short
+        partition.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(partition.remaining(), 2);
+        assert_eq!(partition.read(&mut buf).unwrap(), 2);
+        assert_eq!(partition.remaining(), 0);
+
+        // At the end, reads return nothing and read_exact fails
+        assert_eq!(partition.read(&mut buf).unwrap(), 0);
+        assert!(matches!(
+            partition.read_exact(&mut buf),
+            Err(ReadExactError::UnexpectedEof)
+        ));
+
+        // A write straddling the end is cut short, then fails
+        partition.seek(SeekFrom::End(-1)).unwrap();
+        assert_eq!(partition.write(&buf).unwrap(), 1);
+        assert!(matches!(
+            partition.write(&buf),
+            Err(PartitionError::OutOfSpace)
+        ));
+
+        // Empty buffers always succeed
+        assert_eq!(partition.write(&[]).unwrap(), 0);
+        assert_eq!(partition.read(&mut []).unwrap(), 0);
+    }
+
+    #[derive(Debug)]
+    enum TestError {
+        Transfer,
+        Seek,
+    }
+
+    impl embedded_io::Error for TestError {
+        fn kind(&self) -> embedded_io::ErrorKind {
+            embedded_io::ErrorKind::Other
+        }
+    }
+
+    /// Device whose reads and writes fail, and whose seeks fail after that
+    struct FailingIo {
+        failed: bool,
+    }
+
+    impl Io for FailingIo {
+        type Error = TestError;
+    }
+
+    impl Read for FailingIo {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.failed = true;
+
+            Err(TestError::Transfer)
+        }
+    }
+
+    impl Write for FailingIo {
+        fn write(&mut self, _buf: &[u8]) -> Result<usize, Self::Error> {
+            self.failed = true;
+
+            Err(TestError::Transfer)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FailingIo {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            match (self.failed, pos) {
+                (false, SeekFrom::Start(pos)) => Ok(pos),
+                _ => Err(TestError::Seek),
+            }
+        }
+    }
+
+    #[test]
+    /// Ensure that positioned reads and writes leave the cursor alone
+    fn test_positioned_io() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let len = partition.len();
+        let mut buf: [u8; 4] = [0; 4];
+        let mut expected: [u8; 4] = [0; 4];
+
+        partition.seek(SeekFrom::Start(8)).unwrap();
+        partition.write_at(32, b"ape!").unwrap();
+        partition.read_exact(&mut expected).unwrap();
+
+        partition.seek(SeekFrom::Start(8)).unwrap();
+        assert_eq!(partition.read_at(32, &mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"ape!");
+
+        // The cursor is still where it was, so this reads from offset 8
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+
+        // Reads are cut short at the end, and writes fail past it
+        assert_eq!(partition.read_at(len - 2, &mut buf).unwrap(), 2);
+        assert_eq!(partition.read_at(len + 2, &mut buf).unwrap(), 0);
+        assert_eq!(partition.write_at(len - 1, b"ape!").unwrap(), 1);
+        assert!(matches!(
+            partition.write_at(len, b"ape!"),
+            Err(PartitionError::OutOfSpace)
+        ));
+
+        // Empty buffers at any offset do nothing
+        assert_eq!(partition.read_at(u64::MAX, &mut []).unwrap(), 0);
+        assert_eq!(partition.write_at(u64::MAX, &[]).unwrap(), 0);
+
+        // Device errors aren't hidden by the failing seek that follows
+        let mut io = FailingIo { failed: false };
+        let mut partition = Partition::new(0, 1024, &mut io).unwrap();
+
+        assert!(matches!(
+            partition.read_at(0, &mut buf),
+            Err(PartitionError::Io(TestError::Transfer))
+        ));
+
+        partition.io.failed = false;
+
+        assert!(matches!(
+            partition.write_at(0, &buf),
+            Err(PartitionError::Io(TestError::Transfer))
+        ));
+    }
+
+    #[test]
+    /// Ensure that each seek policy handles seeking past the end
+    fn test_seek_policy() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let len = partition.len();
+        let mut buf: [u8; 4] = [0; 4];
+
+        assert_eq!(partition.get_seek_policy(), SeekPolicy::Clamp);
+
+        partition.set_seek_policy(SeekPolicy::Error);
+
+        assert!(matches!(
+            partition.seek(SeekFrom::End(1)),
+            Err(PartitionError::SeekPastEnd)
+        ));
+        assert_eq!(partition.seek(SeekFrom::End(0)).unwrap(), len);
+
+        partition.set_seek_policy(SeekPolicy::Allow);
+
+        assert_eq!(partition.seek(SeekFrom::Current(10)).unwrap(), len + 10);
+        assert_eq!(partition.read(&mut buf).unwrap(), 0);
+        assert!(matches!(
+            partition.write(&buf),
+            Err(PartitionError::OutOfSpace)
+        ));
+        assert_eq!(partition.seek(SeekFrom::Current(-10)).unwrap(), len);
+    }
+
+    #[test]
+    /// Ensure that validation catches broken tables and passes good ones
+    fn test_validate() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+
+        assert!(mbr.validate().unwrap().is_empty());
+
+        // Make the second partition overlap the first, give the third a
+        // type but no sectors, push the fourth past the end of the device,
+        // set two boot flags, and remove the signature
+        let mut img = TEST_IMG_1.to_vec();
+        let record_2 = RECORDS_START as usize + RECORD_LEN;
+        let record_3 = record_2 + RECORD_LEN;
+        let record_4 = record_3 + RECORD_LEN;
+
+        img[record_2 + RELATIVE_SECTOR_OFFSET] = 0x02;
+        img[record_3 + TOTAL_SECTORS_OFFSET] = 0x00;
+        img[record_4 + TOTAL_SECTORS_OFFSET] = 0xff;
+        img[record_2 + BOOT_FLAG_OFFSET] = 0x80;
+        img[record_3 + BOOT_FLAG_OFFSET] = 0x80;
+        img[SIGNATURE_OFFSET as usize] = 0x00;
+
+        let mut mbr = MBR::new_unchecked(FromStd::new(Cursor::new(img))).unwrap();
+        let report = mbr.validate().unwrap();
+
+        assert_eq!(report.len(), 5);
+        assert!(report.contains(Finding::MissingSignature));
+        assert!(report.contains(Finding::MultipleBootable));
+        assert!(report.contains(Finding::Overlap(PartitionId::One, PartitionId::Two)));
+        assert!(report.contains(Finding::ZeroLength(PartitionId::Three)));
+        assert!(report.contains(Finding::PastEndOfDevice(PartitionId::Four)));
+    }
+
+    #[test]
+    /// Ensure that partitions extending past the end of the device are
+    /// rejected when requested
+    fn test_new_checked() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+
+        MBR::new_checked(img).unwrap();
+
+        // Cut the last sector off of the image
+        let img = TEST_IMG_1[..TEST_IMG_1.len() - BLOCK_SIZE as usize].to_vec();
+
+        match MBR::new_checked(FromStd::new(Cursor::new(img))) {
+            Err(MbrError::PastEndOfDevice(PartitionId::Four)) => {}
+            _ => panic!("expected partition four to be past the end of the device"),
+        }
+
+        // Clear the boot signature
+        let mut img = TEST_IMG_1.to_vec();
+
+        img[SIGNATURE_OFFSET as usize] = 0;
+
+        assert!(matches!(
+            MBR::new_checked(FromStd::new(Cursor::new(img))),
+            Err(MbrError::MissingSignature)
+        ));
+
+        // A blank device has no table, unless the signature check is skipped
+        let img = vec![0; 8 * BLOCK_SIZE as usize];
+
+        assert!(matches!(
+            MBR::new(FromStd::new(Cursor::new(img.clone()))),
+            Err(MbrError::MissingSignature)
+        ));
+
+        let mut mbr = MBR::new_unchecked(FromStd::new(Cursor::new(img))).unwrap();
+
+        // Empty records never hand out the MBR sector
+        assert!(matches!(
+            mbr.get_partition(PartitionId::One),
+            Err(MbrError::EmptyPartition)
+        ));
+    }
+
+    #[test]
+    /// Ensure that a journaled flush writes the backup and that an
+    /// interrupted update can be recovered
+    fn test_flush_journaled() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let backup_lba = 0x11;
+
+        let mut mbr = MBR::new(img).unwrap();
+        let record = PartitionRecord::new(0x20, 0x10, PartitionType::Fat12, true);
+
+        // The backup can't go in sector 0 or inside of a partition
+        assert!(mbr.flush_journaled(0).is_err());
+        assert!(mbr.flush_journaled(0x12).is_err());
+
+        // Shrink partition 1 so the backup has somewhere to go
+        mbr.set_record(
+            PartitionId::One,
+            PartitionRecord::new(0x01, 0x10, PartitionType::Linux, false),
+        );
+        mbr.set_record(PartitionId::Two, record);
+        mbr.flush_journaled(backup_lba).unwrap();
+
+        let mut img = mbr.into_inner().into_inner().into_inner();
+        let backup_pos = lba_to_u64(backup_lba) as usize;
+
+        assert_eq!(
+            img[..BLOCK_SIZE as usize],
+            img[backup_pos..backup_pos + BLOCK_SIZE as usize]
+        );
+
+        // Simulate losing power while sector 0 was being written
+        img[..BLOCK_SIZE as usize].fill(0);
+
+        let mut mbr = MBR::recover(FromStd::new(Cursor::new(img)), backup_lba).unwrap();
+
+        assert_eq!(mbr.partitions[PartitionId::Two as usize], record);
+        assert!(mbr.validate().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Ensure that GPT disks are detected through their protective MBR
+    fn test_open_disk() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
 
-    use ape_fatfs::{fs::{FileSystem, FsOptions, FatType}, io::StdIoWrapper};
-    use embedded_io::{
-        adapters::FromStd,
-        blocking::{Read, Seek, Write},
-    };
+        assert!(matches!(open_disk(img), Ok(Disk::Mbr(_))));
 
-    use crate::*;
+        let img = FromStd::new(Cursor::new(gpt::tests::test_img()));
 
-    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
-    static TEST_IMG_2: &[u8] = include_bytes!("../resources/test2.img");
-    static TEST_STR_1: [u8; 10] = *b"Partition1";
-    static TEST_STR_2: [u8; 10] = *b"Partition2";
-    static TEST_STR_3: [u8; 10] = *b"Partition3";
-    static TEST_STR_4: [u8; 10] = *b"Partition4";
+        match open_disk(img) {
+            Ok(Disk::Gpt(gpt)) => assert_eq!(gpt.partition_count(), gpt::tests::TEST_ENTRY_COUNT),
+            _ => panic!("expected a GPT disk"),
+        }
+
+        // A hybrid MBR also holds a GPT
+        let mut img = FromStd::new(Cursor::new(gpt::tests::test_img()));
+
+        GPT::new(&mut img)
+            .unwrap()
+            .enable_hybrid_mbr(&[gpt::HybridEntry {
+                index: 1,
+                partition_type: PartitionType::Fat16,
+                bootable: true,
+            }])
+            .unwrap();
+
+        assert!(!MBR::new(&mut img).unwrap().is_protective());
+        assert!(matches!(open_disk(&mut img), Ok(Disk::Gpt(_))));
+
+        let img = FromStd::new(Cursor::new(vec![0; 8 * BLOCK_SIZE as usize]));
+
+        assert!(matches!(open_disk(img), Ok(Disk::Superfloppy(_))));
+    }
+
+    /// Count the bootable partitions of any partition table
+    fn count_bootable<T: PartitionTable>(table: &mut T) -> usize {
+        (0..table.partition_count())
+            .filter(|i| table.get_partition(*i).is_ok() && table.is_bootable(*i).unwrap())
+            .count()
+    }
 
     #[test]
-    /// The dummy image is a four partition image with "Partition" witten to the
-    /// start of each partition and the partition number written to the end
-    /// each partition
-    ///
-    /// Partitions sizes in sectors:
-    ///     First partition size: 17
-    ///     Second partition size: 33
-    ///     Third partition size: 65
-    ///     Fourth partition size: 84
-    fn test_dummy_img() {
+    /// Ensure that both the MBR and GPT can be used through the
+    /// PartitionTable trait
+    fn test_partition_table() {
+        let img = StdIoWrapper::new(Cursor::new(TEST_IMG_2.to_vec()));
+
+        let mut mbr = MBR::new(img).unwrap();
+
+        assert_eq!(PartitionTable::partition_count(&mbr), RECORD_COUNT);
+        assert_eq!(count_bootable(&mut mbr), 1);
+        assert_eq!(mbr.partition_type_of(1).unwrap(), PartitionType::Fat16);
+        assert!(matches!(
+            PartitionTable::get_partition(&mut mbr, 3),
+            Err(MbrError::EmptyPartition)
+        ));
+        assert!(matches!(
+            mbr.partition_type_of(4),
+            Err(MbrError::InvalidPartitionIndex(4))
+        ));
+
+        let img = FromStd::new(Cursor::new(gpt::tests::test_img()));
+
+        let mut gpt = GPT::new(img).unwrap();
+
+        assert_eq!(count_bootable(&mut gpt), 0);
+        assert_eq!(
+            gpt.partition_type_of(0).unwrap(),
+            GptPartitionType::LinuxFilesystem
+        );
+        assert_eq!(
+            PartitionTable::get_partition(&mut gpt, 1).unwrap().len(),
+            42 * BLOCK_SIZE
+        );
+    }
+
+    #[test]
+    /// Ensure that records beyond 32 bit LBA addressing are flagged
+    fn test_exceeds_mbr_addressing() {
+        assert!(
+            !PartitionRecord::new(1, u32::MAX - 1, PartitionType::Linux, false)
+                .exceeds_mbr_addressing()
+        );
+        assert!(
+            PartitionRecord::new(3, u32::MAX - 1, PartitionType::Linux, false)
+                .exceeds_mbr_addressing()
+        );
+        assert!(
+            PartitionRecord::new(1, u32::MAX, PartitionType::Linux, false).exceeds_mbr_addressing()
+        );
+        assert!(
+            PartitionRecord::new(u32::MAX, 1, PartitionType::Linux, false).exceeds_mbr_addressing()
+        );
+
         let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
 
         let mut mbr = MBR::new(img).unwrap();
 
-        // Test partition 1
-        let mut partition_1 = mbr.get_partition(PartitionId::One).unwrap();
+        mbr.set_record(
+            PartitionId::Four,
+            PartitionRecord::new(0x74, u32::MAX, PartitionType::Linux, false),
+        );
+
+        assert!(mbr
+            .validate()
+            .unwrap()
+            .contains(Finding::ExceedsMbrAddressing(PartitionId::Four)));
+        assert!(matches!(
+            mbr.check_bounds(),
+            Err(MbrError::ExceedsMbrAddressing)
+        ));
+        assert!(matches!(
+            PartitionTable::get_partition(&mut mbr, 3),
+            Err(MbrError::ExceedsMbrAddressing)
+        ));
+        assert!(matches!(
+            mbr.get_partition(PartitionId::Four),
+            Err(MbrError::ExceedsMbrAddressing)
+        ));
+    }
 
-        let mut buf: [u8; 10] = [0; 10];
+    #[test]
+    /// Ensure that unknown system IDs are kept instead of panicking
+    fn test_unknown_system_id() {
+        let mut img = TEST_IMG_1.to_vec();
+
+        img[RECORDS_START as usize + SYSTEM_ID_OFFSET] = 0x13;
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+        let record = mbr.partitions[0];
+
+        assert_eq!(record.get_system_id(), 0x13);
+        assert_eq!(record.to_bytes()[SYSTEM_ID_OFFSET], 0x13);
+        assert_eq!(record.get_partition_type().id(), 0x13);
+        assert!(!record.get_partition_type().is_known());
+        #[cfg(not(feature = "minimal"))]
+        assert_eq!(record.get_partition_type(), PartitionType::Other(0x13));
+        assert_eq!(record.try_get_partition_type(), Err(0x13));
+        assert_eq!(
+            mbr.partition_type_of(0).unwrap(),
+            PartitionType::from_id(0x13)
+        );
+        assert!(mbr.validate().unwrap().is_empty());
+    }
 
-        partition_1.read_exact(&mut buf[..9]).unwrap();
-        partition_1.seek(embedded_io::SeekFrom::End(-1)).unwrap();
-        partition_1.read_exact(&mut buf[9..]).unwrap();
-        assert_eq!(partition_1.len(), 17 * BLOCK_SIZE);
-        assert_eq!(buf, TEST_STR_1);
-        drop(partition_1);
+    #[test]
+    /// Ensure that an MBR inside of a partition can be parsed, and its
+    /// partitions reached
+    fn test_nested_mbr() {
+        let mut img = vec![0; 64 * BLOCK_SIZE as usize];
+
+        ebr::tests::write_table(
+            &mut img,
+            0,
+            &[PartitionRecord::new(8, 32, PartitionType::Linux, false)],
+        );
+        ebr::tests::write_table(
+            &mut img,
+            8,
+            &[PartitionRecord::new(4, 16, PartitionType::Fat16, false)],
+        );
+        img[lba_to_u64(12) as usize] = 7;
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+        let mut nested = mbr.get_nested_mbr(PartitionId::One).unwrap();
+
+        assert_eq!(
+            nested.get_partition_type(PartitionId::One),
+            PartitionType::Fat16
+        );
+
+        let mut partition = nested.get_partition(PartitionId::One).unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        assert_eq!(partition.len(), 16 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 7);
+
+        // Also works by handing a partition to MBR::new directly
+        let partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut nested = MBR::new(partition).unwrap();
+
+        assert!(nested.check_bounds().is_ok());
+    }
 
-        // Test partition 2
-        let mut partition_2 = mbr.get_partition(PartitionId::Two).unwrap();
+    #[test]
+    /// Ensure that get_partition can reach both primary and logical
+    /// partitions
+    fn test_partition_index() {
+        let img = FromStd::new(Cursor::new(ebr::tests::test_img()));
 
-        partition_2.read_exact(&mut buf[..9]).unwrap();
-        partition_2.seek(embedded_io::SeekFrom::End(-1)).unwrap();
-        partition_2.read_exact(&mut buf[9..]).unwrap();
-        assert_eq!(partition_2.len(), 33 * BLOCK_SIZE);
-        assert_eq!(buf, TEST_STR_2);
-        drop(partition_2);
+        let mut mbr = MBR::new(img).unwrap();
 
-        // Test partition 3
-        let mut partition_3 = mbr.get_partition(PartitionId::Three).unwrap();
+        let primary = mbr.get_partition(PartitionId::One).unwrap();
 
-        partition_3.read_exact(&mut buf[..9]).unwrap();
-        partition_3.seek(embedded_io::SeekFrom::End(-1)).unwrap();
-        partition_3.read_exact(&mut buf[9..]).unwrap();
-        assert_eq!(partition_3.len(), 65 * BLOCK_SIZE);
-        assert_eq!(buf, TEST_STR_3);
-        drop(partition_3);
+        assert_eq!(primary.len(), 10 * BLOCK_SIZE);
 
-        // Test partition 2
-        let mut partition_4 = mbr.get_partition(PartitionId::Four).unwrap();
+        let primary = mbr
+            .get_partition(PartitionIndex::Primary(PartitionId::Two))
+            .unwrap();
 
-        partition_4.read_exact(&mut buf[..9]).unwrap();
-        partition_4.seek(embedded_io::SeekFrom::End(-1)).unwrap();
-        partition_4.read_exact(&mut buf[9..]).unwrap();
-        assert_eq!(partition_4.len(), 84 * BLOCK_SIZE);
-        assert_eq!(buf, TEST_STR_4);
+        assert_eq!(primary.len(), 100 * BLOCK_SIZE);
+
+        let mut logical = mbr.get_partition(PartitionIndex::Logical(1)).unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        assert_eq!(logical.len(), 20 * BLOCK_SIZE);
+        logical.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 6);
     }
 
     #[test]
-    /// The "real" image is a three partition image designed to simulate a real
-    /// drive
-    ///
-    /// Partition layout is as follows:
-    ///     fat12, 2000 sectors, boot flag set
-    ///     fat16, 5000 sectors
-    ///     fat32, 68000 sectors
-    fn test_real_img() {
-        let img = StdIoWrapper::new(Cursor::new(TEST_IMG_2.to_vec()));
+    /// Ensure that overlapping partitions are detected
+    fn test_overlapping_partitions() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
 
-        let mut mbr = MBR::new(img).unwrap();
+        let mbr = MBR::new(img).unwrap();
 
-        // Test partition 1
-        assert_eq!(mbr.get_partition_type(PartitionId::One), PartitionType::Fat12);
-        assert!(mbr.is_partition_bootable(PartitionId::One));
+        assert_eq!(mbr.overlapping_partitions(), None);
 
-        {
-            let partition = mbr.get_partition(PartitionId::One).unwrap();
+        // Move the start of the fourth partition into the third
+        let mut img = TEST_IMG_1.to_vec();
+        let record_4 = RECORDS_START as usize + RECORD_LEN * 3;
 
-            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+        img[record_4 + RELATIVE_SECTOR_OFFSET] = 0x70;
 
-            assert_eq!(fs.fat_type(), FatType::Fat12);
-        }
+        let mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
 
-        // Test partition 2
-        assert_eq!(mbr.get_partition_type(PartitionId::Two), PartitionType::Fat16);
-        assert!(!mbr.is_partition_bootable(PartitionId::Two));
+        assert_eq!(
+            mbr.overlapping_partitions(),
+            Some((PartitionId::Three, PartitionId::Four))
+        );
+    }
 
-        {
-            let partition = mbr.get_partition(PartitionId::Two).unwrap();
+    #[test]
+    /// Ensure that partition records can be iterated over
+    fn test_partitions() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
 
-            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+        assert_eq!(mbr.partitions().count(), RECORD_COUNT);
+        assert_eq!(
+            mbr.partitions().nth(2).map(|(id, record)| (id, *record)),
+            Some((PartitionId::Three, mbr.partitions[2]))
+        );
 
-            assert_eq!(fs.fat_type(), FatType::Fat16);
+        mbr.set_record(PartitionId::Two, PartitionRecord::default());
+
+        let used: Vec<PartitionId> = mbr.used_partitions().map(|(id, _)| id).collect();
+
+        assert_eq!(
+            used,
+            [PartitionId::One, PartitionId::Three, PartitionId::Four]
+        );
+    }
+
+    #[test]
+    /// Ensure that partition records can be accessed and edited in place
+    fn test_get_record() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+
+        assert_eq!(*mbr.get_record(PartitionId::Two), mbr.partitions[1]);
+        assert_eq!(
+            mbr.get_record(PartitionId::Two).get_partition_type(),
+            mbr.get_partition_type(PartitionId::Two)
+        );
+
+        assert_eq!(
+            lba_to_u64(mbr.get_start_lba(PartitionId::Two)),
+            mbr.get_record(PartitionId::Two).get_start_pos()
+        );
+        assert_eq!(
+            lba_to_u64(mbr.get_total_sectors(PartitionId::Two)),
+            mbr.get_record(PartitionId::Two).get_end_pos()
+                - mbr.get_record(PartitionId::Two).get_start_pos()
+        );
+
+        *mbr.get_record_mut(PartitionId::Two) = PartitionRecord::default();
+
+        assert!(!mbr.get_record(PartitionId::Two).is_used());
+    }
+
+    #[test]
+    /// Ensure that the device can be accessed without closing the MBR
+    fn test_get_io_mut() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut buf: [u8; 2] = [0; 2];
+
+        mbr.get_io_mut()
+            .seek(SeekFrom::Start(BLOCK_SIZE - 2))
+            .unwrap();
+        mbr.get_io_mut().read_exact(&mut buf).unwrap();
+
+        assert_eq!(buf, SIGNATURE);
+        assert_eq!(mbr.get_io().inner().get_ref().len(), TEST_IMG_1.len());
+    }
+
+    #[test]
+    /// Ensure that the whole device can be read through a partition
+    fn test_whole_disk() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut disk = mbr.get_whole_disk().unwrap();
+        let mut buf = vec![0; TEST_IMG_1.len()];
+
+        assert_eq!(disk.len(), TEST_IMG_1.len() as u64);
+        disk.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, TEST_IMG_1);
+        assert_eq!(disk.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    /// Ensure that a partition can be released early
+    fn test_partition_into_inner() {
+        let mut io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let mut partition = Partition::new(BLOCK_SIZE, 4 * BLOCK_SIZE, &mut io).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        assert_eq!(partition.get_start_pos(), BLOCK_SIZE);
+        assert_eq!(partition.get_end_pos(), 4 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+
+        let io = partition.into_inner();
+
+        assert_eq!(io.stream_position().unwrap(), BLOCK_SIZE + 4);
+
+        // The device can be handed to the next partition right away
+        let partition = Partition::new(0, BLOCK_SIZE, io).unwrap();
+
+        assert_eq!(partition.len(), BLOCK_SIZE);
+    }
+
+    #[test]
+    /// Ensure that partitions can be found by type
+    fn test_find_by_type() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+
+        mbr.set_record(
+            PartitionId::Two,
+            PartitionRecord::new(0x30, 0x10, PartitionType::W95Fat32Lba, false),
+        );
+        mbr.set_record(
+            PartitionId::Four,
+            PartitionRecord::new(0x80, 0x10, PartitionType::W95Fat32Lba, false),
+        );
+
+        assert_eq!(
+            mbr.find_by_type(PartitionType::W95Fat32Lba),
+            Some(PartitionId::Two)
+        );
+        assert_eq!(
+            mbr.find_all_by_type(PartitionType::W95Fat32Lba)
+                .collect::<Vec<_>>(),
+            [PartitionId::Two, PartitionId::Four]
+        );
+        assert_eq!(mbr.find_by_type(PartitionType::Ntfs), None);
+    }
+
+    #[test]
+    /// Ensure that the largest partition is found
+    fn test_largest_partition() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+
+        for (id, sectors) in PartitionId::ALL.into_iter().zip([0x8, 0x20, 0x20, 0x10]) {
+            mbr.set_record(
+                id,
+                PartitionRecord::new(
+                    0x100 * (id as u32 + 1),
+                    sectors,
+                    PartitionType::Linux,
+                    false,
+                ),
+            );
         }
 
-        // Test partition 3
-        assert_eq!(mbr.get_partition_type(PartitionId::Three), PartitionType::W95Fat32);
-        assert!(!mbr.is_partition_bootable(PartitionId::Three));
+        assert_eq!(mbr.largest_partition(), Some(PartitionId::Two));
 
-        {
-            let partition = mbr.get_partition(PartitionId::Three).unwrap();
+        for id in PartitionId::ALL {
+            mbr.set_record(id, PartitionRecord::default());
+        }
 
-            let fs = FileSystem::new(partition, FsOptions::new()).unwrap();
+        assert_eq!(mbr.largest_partition(), None);
+    }
 
-            assert_eq!(fs.fat_type(), FatType::Fat32);
+    #[test]
+    /// Ensure that the active partition is found
+    fn test_first_bootable() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+
+        mbr.set_record(
+            PartitionId::Three,
+            PartitionRecord::new(0x70, 0x10, PartitionType::Linux, true),
+        );
+        mbr.set_record(
+            PartitionId::Four,
+            PartitionRecord::new(0x80, 0x10, PartitionType::Linux, true),
+        );
+
+        let (id, record) = mbr.first_bootable().unwrap();
+
+        assert_eq!(id, PartitionId::Three);
+        assert_eq!(record.get_start_lba(), 0x70);
+
+        mbr.set_record(PartitionId::Three, PartitionRecord::default());
+        mbr.set_record(PartitionId::Four, PartitionRecord::default());
+
+        assert!(mbr.partitions().all(|(_, record)| !record.is_bootable()));
+        assert_eq!(mbr.first_bootable(), None);
+    }
+
+    #[test]
+    /// Ensure that the table can be read again after it changes
+    fn test_reload() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let record = PartitionRecord::new(0x30, 0x10, PartitionType::Linux, false);
+        let record_2 = RECORDS_START + RECORD_LEN as u64;
+
+        // Rewrite the second record behind the MBR's back
+        mbr.get_io_mut().seek(SeekFrom::Start(record_2)).unwrap();
+        mbr.get_io_mut().write_all(&record.to_bytes()).unwrap();
+
+        assert_ne!(*mbr.get_record(PartitionId::Two), record);
+        mbr.reload().unwrap();
+        assert_eq!(*mbr.get_record(PartitionId::Two), record);
+
+        // A broken signature is refused, keeping the old records
+        mbr.get_io_mut()
+            .seek(SeekFrom::Start(BLOCK_SIZE - 2))
+            .unwrap();
+        mbr.get_io_mut().write_all(&[0, 0]).unwrap();
+
+        assert!(matches!(mbr.reload(), Err(MbrError::MissingSignature)));
+        assert_eq!(*mbr.get_record(PartitionId::Two), record);
+    }
+
+    #[test]
+    /// Ensure that partition summaries match the record
+    fn test_get_info() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+
+        mbr.set_record(
+            PartitionId::Three,
+            PartitionRecord::new(0x70, 0x10, PartitionType::Linux, true),
+        );
+
+        assert_eq!(
+            mbr.get_info(PartitionId::Three),
+            PartitionInfo {
+                id: PartitionId::Three,
+                partition_type: PartitionType::Linux,
+                bootable: true,
+                start_lba: 0x70,
+                sectors: 0x10,
+                start_byte: 0x70 * BLOCK_SIZE,
+                len_bytes: 0x10 * BLOCK_SIZE,
+            }
+        );
+    }
+
+    #[test]
+    /// Ensure that empty slots are told apart from unknown system IDs
+    fn test_is_slot_used() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut unknown = [0; RECORD_LEN];
+
+        unknown[SYSTEM_ID_OFFSET] = 0x13;
+        unknown[TOTAL_SECTORS_OFFSET] = 0x10;
+
+        mbr.set_record(PartitionId::One, PartitionRecord::from_bytes(&unknown));
+        mbr.set_record(PartitionId::Two, PartitionRecord::default());
+        mbr.set_record(
+            PartitionId::Three,
+            PartitionRecord::new(0x70, 0, PartitionType::Linux, false),
+        );
+
+        assert_eq!(
+            mbr.get_partition_type(PartitionId::One),
+            PartitionType::from_id(0x13)
+        );
+        assert!(mbr.is_slot_used(PartitionId::One));
+        assert!(mbr.is_slot_empty(PartitionId::Two));
+        assert!(mbr.is_slot_empty(PartitionId::Three));
+        assert!(mbr.is_slot_used(PartitionId::Four));
+    }
+
+    #[test]
+    /// Ensure that read-only partitions refuse writes but can still be read
+    fn test_read_only() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let start = mbr.get_record(PartitionId::One).get_start_pos() as usize;
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap().read_only();
+        let mut buf: [u8; 4] = [0; 4];
+
+        assert!(partition.is_read_only());
+        assert!(matches!(
+            partition.write(&[1, 2, 3, 4]),
+            Err(PartitionError::ReadOnly)
+        ));
+        assert!(matches!(
+            partition.write_at(0, &[1, 2, 3, 4]),
+            Err(PartitionError::ReadOnly)
+        ));
+
+        partition.read_exact(&mut buf).unwrap();
+
+        assert_eq!(partition.stream_position().unwrap(), 4);
+
+        partition.set_read_only(false);
+        partition.write_all(&[1, 2, 3, 4]).unwrap();
+
+        let img = mbr.into_inner().into_inner().into_inner();
+
+        assert_eq!(img[start..start + 4], buf);
+        assert_eq!(img[start + 4..start + 8], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    /// Ensure that whole sectors can be read and written by LBA
+    fn test_sector_io() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let last_lba = (partition.len() / BLOCK_SIZE - 1) as u32;
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        partition.seek(SeekFrom::Start(3)).unwrap();
+        partition
+            .write_sector(last_lba, &[0xaa; BLOCK_SIZE as usize])
+            .unwrap();
+        partition.read_sector(last_lba, &mut sector).unwrap();
+
+        assert_eq!(sector, [0xaa; BLOCK_SIZE as usize]);
+        assert_eq!(partition.stream_position().unwrap(), 3);
+
+        partition.seek(SeekFrom::End(-(BLOCK_SIZE as i64))).unwrap();
+        partition.read_exact(&mut sector[..4]).unwrap();
+        assert_eq!(sector[..4], [0xaa; 4]);
+
+        assert!(matches!(
+            partition.read_sector(last_lba + 1, &mut sector),
+            Err(PartitionError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            partition.write_sector(last_lba + 1, &sector),
+            Err(PartitionError::OutOfSpace)
+        ));
+    }
+
+    #[test]
+    /// Ensure that the cursor can be moved by sector
+    fn test_seek_sector() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let start = mbr.get_record(PartitionId::One).get_start_pos() as usize;
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        partition.seek_sector(2).unwrap();
+
+        assert_eq!(partition.stream_position().unwrap(), 2 * BLOCK_SIZE);
+        assert_eq!(partition.position_sector(), 2);
+
+        partition.read_exact(&mut buf).unwrap();
+
+        let offset = start + 2 * BLOCK_SIZE as usize;
+
+        assert_eq!(buf[..], TEST_IMG_1[offset..offset + 4]);
+        assert_eq!(partition.position_sector(), 2);
+
+        partition.set_seek_policy(SeekPolicy::Error);
+
+        assert!(matches!(
+            partition.seek_sector(u32::MAX),
+            Err(PartitionError::SeekPastEnd)
+        ));
+    }
+
+    #[test]
+    /// Ensure that the cursor position can be read and reset
+    fn test_position() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+        let mut first: [u8; 4] = [0; 4];
+        let mut buf: [u8; 4] = [0; 4];
+
+        assert_eq!(partition.get_position(), 0);
+
+        partition.read_exact(&mut first).unwrap();
+        partition.read_exact(&mut buf).unwrap();
+
+        assert_eq!(partition.get_position(), 8);
+
+        partition.rewind().unwrap();
+
+        assert_eq!(partition.get_position(), 0);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, first);
+    }
+
+    #[test]
+    /// Ensure that the length of a partition is given in sectors
+    fn test_len_sectors() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let sectors = mbr.get_total_sectors(PartitionId::One);
+        let partition = mbr.get_partition(PartitionId::One).unwrap();
+
+        assert_eq!(partition.len_sectors(), sectors as u64);
+
+        // Partial sectors aren't counted
+        let mut io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let partition = Partition::new(0, 3 * BLOCK_SIZE - 1, &mut io).unwrap();
+
+        assert_eq!(partition.len_sectors(), 2);
+    }
+
+    #[test]
+    /// Ensure that partitions and MBRs can be debug printed
+    fn test_debug() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let record = format!("{:?}", mbr.get_record(PartitionId::Four));
+
+        assert!(format!("{:?}", mbr).contains(&record));
+
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap();
+
+        partition.seek(SeekFrom::Start(4)).unwrap();
+
+        let debug = format!("{:?}", partition);
+
+        assert!(debug.starts_with("Partition {"));
+        assert!(debug.contains("pos: 4"));
+    }
+
+    #[test]
+    /// Ensure that an MBR can borrow the device instead of owning it
+    fn test_borrowed_io() {
+        let mut io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let start = {
+            let mut mbr = MBR::new(&mut io).unwrap();
+            let start = mbr.get_record(PartitionId::One).get_start_pos();
+
+            mbr.get_partition(PartitionId::One)
+                .unwrap()
+                .write_all(&[1, 2, 3, 4])
+                .unwrap();
+
+            start
+        };
+        let mut buf: [u8; 4] = [0; 4];
+
+        io.seek(SeekFrom::Start(start)).unwrap();
+        io.read_exact(&mut buf).unwrap();
+
+        assert_eq!(buf, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    /// Ensure that the device is only seeked when it isn't already where the
+    /// partition needs it
+    fn test_skip_redundant_seeks() {
+        let mut io = CountIo::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec())));
+        let mut partition = Partition::new(512, 4096, &mut io).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        partition.read_exact(&mut buf).unwrap();
+        partition.seek(SeekFrom::Start(4)).unwrap();
+        partition.seek(SeekFrom::Current(0)).unwrap();
+
+        assert_eq!(partition.io.seeks, 1);
+
+        partition.seek(SeekFrom::Start(100)).unwrap();
+
+        assert_eq!(partition.io.seeks, 2);
+
+        // Positioned reads have to go there and back
+        partition.read_at(0, &mut buf).unwrap();
+
+        assert_eq!(partition.io.seeks, 4);
+
+        // Resyncing always seeks, in case the device was moved
+        partition.io.inner.seek(SeekFrom::Start(0)).unwrap();
+        partition.resync().unwrap();
+        partition.read_exact(&mut buf).unwrap();
+
+        assert_eq!(partition.io.seeks, 5);
+        assert_eq!(buf, TEST_IMG_1[612..616]);
+    }
+
+    #[test]
+    /// Ensure that partitions can be copied to partitions of other sizes
+    fn test_copy_to() {
+        let mut source_io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let mut dest_io = FromStd::new(Cursor::new(vec![0; 4096]));
+        let mut source = Partition::new(512, 1536, &mut source_io).unwrap();
+        let mut buf: [u8; 300] = [0; 300];
+
+        {
+            let mut dest = Partition::new(1024, 4096, &mut dest_io).unwrap();
+
+            assert_eq!(source.copy_to(&mut dest, &mut buf).unwrap(), 1024);
+            assert_eq!(dest.get_position(), 1024);
         }
+
+        // Smaller destinations are refused without writing anything
+        let mut small_io = FromStd::new(Cursor::new(vec![0; 1024]));
+        let mut small = Partition::new(512, 1024, &mut small_io).unwrap();
+
+        assert!(matches!(
+            source.copy_to(&mut small, &mut buf),
+            Err(PartitionError::OutOfSpace)
+        ));
+        assert!(small_io.inner().get_ref().iter().all(|b| *b == 0));
+
+        let dest = dest_io.into_inner().into_inner();
+
+        assert_eq!(&dest[1024..2048], &TEST_IMG_1[512..1536]);
+        assert!(dest[2048..].iter().all(|b| *b == 0));
     }
 
     #[test]
-    /// Ensure that we cannot read or write past the end of the partition
-    fn test_bounds() {
-        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+    /// Ensure that partitions set to only allow whole sectors refuse
+    /// anything else
+    fn test_aligned_only() {
+        let mut io = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let mut partition = Partition::new(512, 4096, &mut io).unwrap().aligned_only();
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        partition.read_exact(&mut sector).unwrap();
+        partition.write_all(&sector).unwrap();
+        partition.read_sector(3, &mut sector).unwrap();
+
+        assert!(matches!(
+            partition.read(&mut sector[..4]),
+            Err(PartitionError::Unaligned)
+        ));
+        assert!(matches!(
+            partition.write_at(4, &sector),
+            Err(PartitionError::Unaligned)
+        ));
+
+        partition.seek(SeekFrom::Start(1)).unwrap();
+
+        assert!(matches!(
+            partition.write(&sector),
+            Err(PartitionError::Unaligned)
+        ));
+
+        partition.set_aligned_only(false);
+
+        assert!(partition.write(&sector).is_ok());
+    }
 
-        let mut mbr = MBR::new(img).unwrap();
+    #[test]
+    /// Ensure that partition types round-trip through their raw IDs
+    fn test_partition_type_id() {
+        assert_eq!(PartitionType::W95Fat32Lba.id(), 0x0c);
+        assert_eq!(u8::from(PartitionType::Linux), 0x83);
+        assert_eq!(PartitionType::Unknown.id(), 0x00);
 
-        let mut buf: [u8; 10] = [0; 10];
-        let mut partition_1 = mbr.get_partition(PartitionId::One).unwrap();
+        let record = PartitionRecord::new(1, 1, PartitionType::Ntfs, false);
 
-        partition_1.seek(embedded_io::SeekFrom::End(0)).unwrap();
+        assert_eq!(record.get_system_id(), PartitionType::Ntfs.into());
+        assert_eq!(record.get_partition_type().id(), record.get_system_id());
+    }
 
-        partition_1.read_exact(&mut buf).unwrap_err();
-        // write_all panics on failure
-        assert!(panic::catch_unwind(AssertUnwindSafe(|| {
-            partition_1.write_all(&buf).unwrap_err();
-        }))
-        .is_err());
+    #[test]
+    /// Ensure that partition types are sorted into the right kinds
+    fn test_partition_kind() {
+        assert_eq!(PartitionType::Fat16.kind(), PartitionKind::Fat);
+        assert_eq!(PartitionType::Ntfs.kind(), PartitionKind::Ntfs);
+        assert_eq!(PartitionType::Linux.kind(), PartitionKind::LinuxFs);
+        assert_eq!(PartitionType::LinuxSwap.kind(), PartitionKind::Swap);
+        assert_eq!(PartitionType::LinuxLvm.kind(), PartitionKind::LvmRaid);
+        assert_eq!(PartitionType::EFI.kind(), PartitionKind::Efi);
+        assert_eq!(PartitionType::Unknown.kind(), PartitionKind::Other);
+        assert_eq!(PartitionType::from_id(0x13).kind(), PartitionKind::Other);
+
+        assert!(PartitionType::W95Fat32Lba.is_fat());
+        assert!(!PartitionType::HiddenW95Fat32Lba.is_fat());
+        assert!(PartitionType::HiddenW95Fat32Lba.is_hidden());
+        assert!(PartitionType::W95ExtendedLba.is_extended());
+        assert!(!PartitionType::Linux.is_extended());
+    }
+
+    #[test]
+    /// Ensure that FAT and NTFS types can be hidden and unhidden
+    fn test_hide_partition_type() {
+        assert_eq!(PartitionType::Fat12.hide(), PartitionType::HiddenFat12);
+        assert_eq!(PartitionType::Ntfs.hide(), PartitionType::HiddenNtfs);
+        assert_eq!(
+            PartitionType::HiddenW95Fat32Lba.unhide(),
+            PartitionType::W95Fat32Lba
+        );
+
+        // Types without a counterpart are left alone
+        assert_eq!(PartitionType::Linux.hide(), PartitionType::Linux);
+        assert_eq!(
+            PartitionType::HiddenFat16.hide(),
+            PartitionType::HiddenFat16
+        );
+        assert_eq!(PartitionType::Fat16.unhide(), PartitionType::Fat16);
+
+        let record = PartitionRecord::new(1, 1, PartitionType::W95Fat16Lba.hide(), false);
+
+        assert_eq!(record.get_system_id(), 0x1e);
+        assert!(record.get_partition_type().is_hidden());
+        assert_eq!(
+            record.get_partition_type().unhide(),
+            PartitionType::W95Fat16Lba
+        );
+    }
+
+    #[test]
+    /// Ensure that an MBR can be parsed from memory without a device
+    fn test_from_bytes() {
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        sector.copy_from_slice(&TEST_IMG_1[..BLOCK_SIZE as usize]);
+
+        let mbr = MBR::from_bytes(&sector).unwrap();
+        let from_device = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1))).unwrap();
+
+        for id in PartitionId::ALL {
+            assert_eq!(mbr.get_record(id), from_device.get_record(id));
+        }
+
+        sector[SIGNATURE_OFFSET as usize] = 0;
+
+        assert!(matches!(
+            MBR::from_bytes(&sector),
+            Err(MbrError::MissingSignature)
+        ));
+    }
+
+    #[test]
+    /// Ensure that errors have readable messages
+    fn test_error_display() {
+        let error: MbrError<()> = MbrError::InvalidScript(3);
+
+        assert_eq!(error.to_string(), "invalid sfdisk script line 3");
+        assert_eq!(
+            PartitionError::<()>::OutOfSpace.to_string(),
+            "no space left in the partition"
+        );
     }
 }
@@ -0,0 +1,175 @@
+//! Support for subpartitions inside of Minix partitions.
+//!
+//! A Minix partition can hold its own 4 entry table in its first sector,
+//! laid out exactly like an MBR. The subpartitions are addressed relative to
+//! the start of the disk, and are only parsed when asked for.
+
+use embedded_io::{
+    blocking::{Read, Seek},
+    SeekFrom,
+};
+
+use crate::{
+    error::MbrError, has_signature, types::PartitionType, Partition, PartitionId, PartitionRecord,
+    BLOCK_SIZE, MBR, RECORDS_START, RECORD_COUNT, RECORD_LEN,
+};
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Read the subpartition table of a Minix partition
+    ///
+    /// Like Linux, the table is only accepted if its first subpartition is a
+    /// Minix partition too. Unused subpartitions are left zeroed.
+    pub fn get_minix_subpartitions(
+        &mut self,
+        id: PartitionId,
+    ) -> Result<[PartitionRecord; RECORD_COUNT], MbrError<IO::Error>> {
+        let record = self.partitions[id as usize];
+        let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+        if !record.is_used() || record.get_partition_type() != PartitionType::Minix {
+            return Err(MbrError::InvalidMinixTable);
+        }
+
+        self.io.seek(SeekFrom::Start(record.get_start_pos()))?;
+        self.io.read_exact(&mut sector)?;
+
+        if !has_signature(&sector) {
+            return Err(MbrError::InvalidMinixTable);
+        }
+
+        let mut subpartitions = [PartitionRecord::default(); RECORD_COUNT];
+
+        for (i, subpartition) in subpartitions.iter_mut().enumerate() {
+            let record_i = RECORDS_START as usize + i * RECORD_LEN;
+
+            *subpartition = PartitionRecord::from_bytes(
+                sector[record_i..record_i + RECORD_LEN].try_into().unwrap(),
+            );
+
+            if subpartition.is_used()
+                && (subpartition.get_start_pos() < record.get_start_pos()
+                    || subpartition.get_end_pos() > record.get_end_pos())
+            {
+                return Err(MbrError::InvalidMinixTable);
+            }
+        }
+
+        if subpartitions[0].get_partition_type() != PartitionType::Minix {
+            return Err(MbrError::InvalidMinixTable);
+        }
+
+        Ok(subpartitions)
+    }
+
+    /// Get a subpartition of a Minix partition
+    pub fn get_minix_subpartition(
+        &mut self,
+        id: PartitionId,
+        sub_id: PartitionId,
+    ) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let subpartition = self.get_minix_subpartitions(id)?[sub_id as usize];
+
+        if !subpartition.is_used() {
+            return Err(MbrError::EmptyPartition);
+        }
+
+        Partition::new(
+            subpartition.get_start_pos(),
+            subpartition.get_end_pos(),
+            &mut self.io,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Read};
+
+    use crate::{
+        ebr::tests::write_table, error::MbrError, lba_to_u64, types::PartitionType, PartitionId,
+        PartitionRecord, BLOCK_SIZE, MBR,
+    };
+
+    #[test]
+    /// Ensure that Minix subpartitions are read, and bad tables rejected
+    fn test_minix_subpartitions() {
+        let mut img = vec![0; 64 * BLOCK_SIZE as usize];
+
+        write_table(
+            &mut img,
+            0,
+            &[
+                PartitionRecord::new(8, 48, PartitionType::Minix, false),
+                PartitionRecord::new(56, 8, PartitionType::Linux, false),
+            ],
+        );
+        write_table(
+            &mut img,
+            8,
+            &[
+                PartitionRecord::new(9, 15, PartitionType::Minix, false),
+                PartitionRecord::new(24, 32, PartitionType::Minix, false),
+            ],
+        );
+        img[lba_to_u64(24) as usize] = 3;
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img.clone()))).unwrap();
+        let subpartitions = mbr.get_minix_subpartitions(PartitionId::One).unwrap();
+
+        assert_eq!(subpartitions[0].relative_sector, 9);
+        assert!(!subpartitions[2].is_used());
+
+        let mut partition = mbr
+            .get_minix_subpartition(PartitionId::One, PartitionId::Two)
+            .unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        assert_eq!(partition.len(), 32 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 3);
+
+        assert!(matches!(
+            mbr.get_minix_subpartition(PartitionId::One, PartitionId::Three),
+            Err(MbrError::EmptyPartition)
+        ));
+        assert!(matches!(
+            mbr.get_minix_subpartitions(PartitionId::Two),
+            Err(MbrError::InvalidMinixTable)
+        ));
+
+        // A subpartition reaching outside of the Minix partition
+        write_table(
+            &mut img,
+            8,
+            &[PartitionRecord::new(9, 60, PartitionType::Minix, false)],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img.clone()))).unwrap();
+
+        assert!(matches!(
+            mbr.get_minix_subpartitions(PartitionId::One),
+            Err(MbrError::InvalidMinixTable)
+        ));
+
+        // A subpartition at the last LBA is rejected without overflowing
+        write_table(
+            &mut img,
+            8,
+            &[PartitionRecord::new(
+                u32::MAX,
+                u32::MAX,
+                PartitionType::Minix,
+                false,
+            )],
+        );
+
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(img))).unwrap();
+
+        assert!(matches!(
+            mbr.get_minix_subpartitions(PartitionId::One),
+            Err(MbrError::InvalidMinixTable)
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+//! Interop with the `sfdisk` tool from util-linux.
+//!
+//! [`MBR::write_sfdisk_json`] produces the same layout as `sfdisk --json`,
+//! so tables read or created by this crate can be handed to standard Linux
+//! tooling. Going the other way, [`parse_script`] reads the scripts made by
+//! `sfdisk --dump`, so a known-good layout can be reproduced in firmware.
+
+use core::fmt;
+
+use embedded_io::blocking::{Read, Seek};
+
+use crate::{error::MbrError, PartitionId, PartitionRecord, BLOCK_SIZE, MBR, RECORD_COUNT};
+
+/// Type given to partitions that don't set one, like sfdisk does
+const DEFAULT_SYSTEM_ID: u8 = 0x83;
+
+/// Write a string escaped for use inside a JSON string literal
+fn write_escaped<W: fmt::Write>(out: &mut W, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the name sfdisk gives a partition on a device, which gets a `p`
+/// before the number when the device name ends in a digit, like
+/// `/dev/mmcblk0p1`
+fn write_node<W: fmt::Write>(out: &mut W, device: &str, number: usize) -> fmt::Result {
+    let separator = match device.ends_with(|c: char| c.is_ascii_digit()) {
+        true => "p",
+        false => "",
+    };
+
+    out.write_char('"')?;
+    write_escaped(out, device)?;
+    write!(out, "{}{}\"", separator, number)
+}
+
+/// Parse a partition line of a script into the slot it names, if any, and
+/// its record
+fn parse_partition(line: &str) -> Option<(Option<usize>, PartitionRecord)> {
+    // Dumps name each partition, like "/dev/sda1 : start=..."
+    let (node, fields) = match line.find(':') {
+        Some(colon) if line.find('=').map_or(true, |equals| colon < equals) => {
+            (Some(line[..colon].trim()), &line[colon + 1..])
+        }
+        _ => (None, line),
+    };
+
+    let slot = match node {
+        Some(node) => {
+            let digits = node.len() - node.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            let number: usize = node[node.len() - digits..].parse().ok()?;
+
+            match number {
+                1..=RECORD_COUNT => Some(number - 1),
+                _ => return None,
+            }
+        }
+        None => None,
+    };
+
+    let mut start = None;
+    let mut size = None;
+    let mut record = PartitionRecord {
+        system_id: DEFAULT_SYSTEM_ID,
+        ..Default::default()
+    };
+
+    for field in fields.split(',').map(str::trim) {
+        match field.split_once('=') {
+            Some((key, value)) => {
+                let value = value.trim();
+
+                match key.trim() {
+                    "start" => start = Some(value.parse().ok()?),
+                    "size" => size = Some(value.parse().ok()?),
+                    "type" => {
+                        let value = value.strip_prefix("0x").unwrap_or(value);
+
+                        record.system_id = u8::from_str_radix(value, 16).ok()?;
+                    }
+                    _ => return None,
+                }
+            }
+            None if field == "bootable" => record.boot_flag = true,
+            None => return None,
+        }
+    }
+
+    record.relative_sector = start?;
+    record.total_sectors = size?;
+
+    Some((slot, record))
+}
+
+/// Check that a header line of a script describes a table this crate can
+/// build
+fn check_header(line: &str) -> bool {
+    let (key, value) = match line.split_once(':') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => return false,
+    };
+
+    match key {
+        "label" => value == "dos",
+        "unit" => value == "sectors",
+        "sector-size" => value.parse() == Ok(BLOCK_SIZE),
+        _ => true,
+    }
+}
+
+/// Parse a script in the format of `sfdisk --dump` into partition records
+///
+/// Partition lines take `start=` and `size=` in sectors, an optional
+/// hexadecimal `type=` defaulting to Linux, and an optional `bootable`.
+/// Lines naming a partition, like `/dev/sda3 : start=...`, go in that slot,
+/// and other lines go in the slot after the previous one. Headers are
+/// checked to describe a DOS table with 512 byte sectors, and blank lines
+/// and `#` comments are skipped. Partitions that overlap one another or
+/// reach past 32 bit LBAs are refused.
+pub fn parse_script<E>(script: &str) -> Result<[PartitionRecord; RECORD_COUNT], MbrError<E>> {
+    let mut records = [PartitionRecord::default(); RECORD_COUNT];
+    let mut next_slot = 0;
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        let invalid = || MbrError::InvalidScript(number as u32 + 1);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if !line.contains('=') {
+            match check_header(line) {
+                true => continue,
+                false => return Err(invalid()),
+            }
+        }
+
+        let (slot, record) = parse_partition(line).ok_or_else(invalid)?;
+        let slot = slot.unwrap_or(next_slot);
+
+        if slot >= RECORD_COUNT || record.exceeds_mbr_addressing() {
+            return Err(invalid());
+        }
+
+        let overlaps = records
+            .iter()
+            .enumerate()
+            .any(|(i, other)| i != slot && other.overlaps(&record));
+
+        if overlaps {
+            return Err(invalid());
+        }
+
+        records[slot] = record;
+
+        next_slot = slot + 1;
+    }
+
+    Ok(records)
+}
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Write the table in the format of `sfdisk --json`
+    ///
+    /// `device` is the path of the disk, like `/dev/sda`, and is used to name
+    /// the partitions. Empty slots are left out, as sfdisk does. The disk
+    /// identifier isn't kept by the MBR, so the `id` field is left out too.
+    pub fn write_sfdisk_json<W: fmt::Write>(&self, device: &str, out: &mut W) -> fmt::Result {
+        out.write_str(
+            "{\n   \"partitiontable\": {\n      \"label\": \"dos\",\n      \"device\": \"",
+        )?;
+        write_escaped(out, device)?;
+        write!(
+            out,
+            "\",\n      \"unit\": \"sectors\",\n      \"sectorsize\": {},\n      \"partitions\": [",
+            BLOCK_SIZE
+        )?;
+
+        for (i, (id, record)) in self.used_partitions().enumerate() {
+            if i > 0 {
+                out.write_char(',')?;
+            }
+
+            out.write_str("\n         {\"node\": ")?;
+            write_node(out, device, id as usize + 1)?;
+            write!(
+                out,
+                ", \"start\": {}, \"size\": {}, \"type\": \"{:x}\"",
+                record.get_start_lba(),
+                record.get_total_sectors(),
+                record.get_system_id()
+            )?;
+
+            if record.is_bootable() {
+                out.write_str(", \"bootable\": true")?;
+            }
+
+            out.write_char('}')?;
+        }
+
+        out.write_str("\n      ]\n   }\n}\n")
+    }
+
+    /// Replace every record with the layout described by an sfdisk script,
+    /// see [`parse_script`]
+    ///
+    /// The table is left untouched if the script can't be parsed, and the
+    /// change is only kept in memory until the MBR is flushed
+    pub fn apply_sfdisk_script(&mut self, script: &str) -> Result<(), MbrError<IO::Error>> {
+        let records = parse_script(script)?;
+
+        for (id, record) in PartitionId::ALL.into_iter().zip(records) {
+            self.set_record(id, record);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    /// Get the table in the format of `sfdisk --json`, see
+    /// [`MBR::write_sfdisk_json`]
+    pub fn to_sfdisk_json(&self, device: &str) -> String {
+        let mut json = String::new();
+
+        // Writing to a String can't fail
+        let _ = self.write_sfdisk_json(device, &mut json);
+
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::adapters::FromStd;
+
+    use crate::{error::MbrError, types::PartitionType, PartitionId, PartitionRecord, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that tables are exported like `sfdisk --json` does
+    fn test_sfdisk_json() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1))).unwrap();
+        let mut json = String::new();
+
+        mbr.set_record(PartitionId::Three, PartitionRecord::default());
+        mbr.get_record_mut(PartitionId::One).boot_flag = true;
+        mbr.write_sfdisk_json("/dev/mmcblk0", &mut json).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{
+   "partitiontable": {
+      "label": "dos",
+      "device": "/dev/mmcblk0",
+      "unit": "sectors",
+      "sectorsize": 512,
+      "partitions": [
+         {"node": "/dev/mmcblk0p1", "start": 1, "size": 17, "type": "83", "bootable": true},
+         {"node": "/dev/mmcblk0p2", "start": 18, "size": 33, "type": "83"},
+         {"node": "/dev/mmcblk0p4", "start": 116, "size": 84, "type": "83"}
+      ]
+   }
+}
+"#
+        );
+
+        #[cfg(feature = "std")]
+        assert!(mbr
+            .to_sfdisk_json("/dev/sda")
+            .contains(r#"{"node": "/dev/sda4", "start": 116"#));
+    }
+
+    #[test]
+    /// Ensure that sfdisk scripts are parsed into the records they describe
+    fn test_sfdisk_script() {
+        let mut mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let script = "label: dos
+label-id: 0x12345678
+device: /dev/sda
+unit: sectors
+sector-size: 512
+
+/dev/sda1 : start=        2048, size=      204800, type=c, bootable
+/dev/sda3 : start=      206848, size=       10240, type=0x7
+# A line without a node goes in the next slot
+start=217088, size=4096
+";
+
+        mbr.apply_sfdisk_script(script).unwrap();
+
+        assert_eq!(
+            *mbr.get_record(PartitionId::One),
+            PartitionRecord::new(2048, 204800, PartitionType::W95Fat32Lba, true)
+        );
+        assert!(!mbr.get_record(PartitionId::Two).is_used());
+        assert_eq!(
+            *mbr.get_record(PartitionId::Three),
+            PartitionRecord::new(206848, 10240, PartitionType::Ntfs, false)
+        );
+        assert_eq!(
+            *mbr.get_record(PartitionId::Four),
+            PartitionRecord::new(217088, 4096, PartitionType::Linux, false)
+        );
+
+        // Tables that can't be built are refused without changing anything
+        for (script, line) in [
+            ("label: gpt\n", 1),
+            ("start=1, size=2\nstart=3\n", 2),
+            ("start=1, size=2, type=zz\n", 1),
+            ("/dev/sda5 : start=1, size=2\n", 1),
+            ("\n\nstart=1, size=2, name=boot\n", 3),
+            ("start=1, size=10\nstart=5, size=10\n", 2),
+            ("start=4294967295, size=2\n", 1),
+            ("start=4294967000, size=1000\n", 1),
+        ] {
+            assert!(matches!(
+                mbr.apply_sfdisk_script(script),
+                Err(MbrError::InvalidScript(l)) if l == line
+            ));
+        }
+
+        assert!(mbr.get_record(PartitionId::One).is_bootable());
+    }
+}
@@ -0,0 +1,553 @@
+//! Sharing one device between several partitions at once.
+//!
+//! [`MBR::get_partition`] borrows the whole MBR mutably, so only one
+//! partition can be open at a time. A [`SharedMBR`] instead keeps the device
+//! behind a [`DeviceLock`], and each [`SharedPartition`] only holds the lock
+//! for a single read, write or seek, putting the device back at its own
+//! cursor first. This allows, for example, copying files from one partition
+//! to another.
+//!
+//! [`RefCell`] is enough on a single core. With the `std` feature
+//! `std::sync::Mutex` can be used too, and anything else, such as a
+//! critical section mutex, only needs to implement [`DeviceLock`].
+//!
+//! Devices that are cheap to clone can skip the lock, giving each reader
+//! its own clone through a [`PartitionReader`].
+
+use core::{cell::RefCell, ops::Deref};
+
+use embedded_io::{
+    blocking::{Read, Seek, Write},
+    Io, SeekFrom,
+};
+
+use crate::{
+    error::{MbrError, PartitionError},
+    Partition, PartitionId, PartitionRecord, SeekPolicy, MBR, RECORD_COUNT,
+};
+
+/// Gives exclusive access to a shared device for the length of a closure
+pub trait DeviceLock {
+    /// The device being shared
+    type IO: Seek;
+
+    /// Run a closure with exclusive access to the device
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::IO) -> R) -> R;
+}
+
+impl<IO: Seek> DeviceLock for RefCell<IO> {
+    type IO = IO;
+
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::IO) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<IO: Seek> DeviceLock for std::sync::Mutex<IO> {
+    type IO = IO;
+
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::IO) -> R) -> R {
+        // Partitions always restore their own cursor, so a panic while the
+        // lock was held doesn't leave anything for the next user to fix
+        let mut io = std::sync::Mutex::lock(self).unwrap_or_else(|error| error.into_inner());
+
+        f(&mut io)
+    }
+}
+
+/// Partitions of a split MBR, `None` for unused slots
+type SplitPartitions<D> = [Option<SharedPartition<D>>; RECORD_COUNT];
+
+/// An MBR whose partitions can be open at the same time
+pub struct SharedMBR<L: DeviceLock> {
+    partitions: [PartitionRecord; RECORD_COUNT],
+    protect_table: bool,
+    device: L,
+}
+
+impl<L: DeviceLock> SharedMBR<L>
+where
+    L::IO: Read,
+{
+    /// Create a new shared MBR from a device behind a lock, eg. a
+    /// `RefCell<IO>`
+    ///
+    /// Fails with [`MbrError::MissingSignature`] like [`MBR::new`]
+    pub fn new(device: L) -> Result<Self, MbrError<<L::IO as Io>::Error>> {
+        let partitions = device.lock(|io| MBR::read_records(io, true))?;
+
+        Ok(Self {
+            partitions,
+            protect_table: true,
+            device,
+        })
+    }
+}
+
+impl<L: DeviceLock> SharedMBR<L> {
+    #[inline]
+    /// Get the record of a partition
+    pub fn get_record(&self, id: PartitionId) -> &PartitionRecord {
+        &self.partitions[id as usize]
+    }
+
+    /// Get a primary partition, which can be held alongside any others
+    ///
+    /// Fails the same way as [`MBR::get_partition`]
+    pub fn get_partition(
+        &self,
+        id: PartitionId,
+    ) -> Result<SharedPartition<&L>, MbrError<<L::IO as Io>::Error>> {
+        let record = self.partitions[id as usize];
+
+        record.check_primary(self.protect_table)?;
+
+        SharedPartition::new(record.get_start_pos(), record.get_end_pos(), &self.device)
+    }
+
+    /// Split into an owned partition for every used slot, each with its
+    /// own handle to the device, so they can be handed to different
+    /// subsystems
+    ///
+    /// `D` is the handle shared between the partitions, eg. `Rc<RefCell<IO>>`
+    /// or `Arc<Mutex<IO>>`. The device can be taken back out of it once every
+    /// partition has been dropped
+    pub fn split<D>(self) -> Result<SplitPartitions<D>, MbrError<<L::IO as Io>::Error>>
+    where
+        D: From<L> + Clone + Deref<Target = L>,
+    {
+        let device = D::from(self.device);
+        let mut partitions: SplitPartitions<D> = Default::default();
+
+        for (partition, record) in partitions.iter_mut().zip(self.partitions.iter()) {
+            if !record.is_used() {
+                continue;
+            }
+
+            record.check_primary(self.protect_table)?;
+
+            *partition = Some(SharedPartition::new(
+                record.get_start_pos(),
+                record.get_end_pos(),
+                device.clone(),
+            )?);
+        }
+
+        Ok(partitions)
+    }
+
+    #[inline]
+    /// Get the lock around the device
+    pub fn get_device(&self) -> &L {
+        &self.device
+    }
+
+    #[inline]
+    /// Get the lock around the device back
+    pub fn into_inner(self) -> L {
+        self.device
+    }
+}
+
+impl<IO: Read + Seek> MBR<IO> {
+    /// Turn into an MBR whose partitions can be open at the same time,
+    /// keeping the device in a [`RefCell`]
+    pub fn into_shared(self) -> SharedMBR<RefCell<IO>> {
+        SharedMBR {
+            partitions: self.partitions,
+            protect_table: self.protect_table,
+            device: RefCell::new(self.io),
+        }
+    }
+}
+
+/// A partition on a shared device
+///
+/// Behaves exactly like a [`Partition`], but only locks the device while
+/// it is being used. `D` is anything that leads to the lock, like a
+/// reference to it.
+pub struct SharedPartition<D> {
+    start_pos: u64,
+    end_pos: u64,
+    pos: u64,
+    seek_policy: SeekPolicy,
+    read_only: bool,
+    device: D,
+}
+
+impl<D, L> SharedPartition<D>
+where
+    D: Deref<Target = L>,
+    L: DeviceLock + ?Sized,
+{
+    /// Create a new partition on a shared device given the start and end
+    /// position
+    ///
+    /// Fails with [`MbrError::InvalidPartitionBounds`] if the partition ends
+    /// before it starts. Unlike [`Partition::new`] the device isn't touched
+    /// until the partition is first used
+    pub fn new(
+        start_pos: u64,
+        end_pos: u64,
+        device: D,
+    ) -> Result<Self, MbrError<<L::IO as Io>::Error>> {
+        if end_pos < start_pos {
+            return Err(MbrError::InvalidPartitionBounds);
+        }
+
+        Ok(Self {
+            start_pos,
+            end_pos,
+            pos: 0,
+            seek_policy: SeekPolicy::default(),
+            read_only: false,
+            device,
+        })
+    }
+
+    #[inline]
+    /// Get the length of the partition in bytes
+    pub fn len(&self) -> u64 {
+        self.end_pos - self.start_pos
+    }
+
+    #[inline]
+    /// Check if the partition has a length of zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    /// Set what the partition does when seeking past its end
+    pub fn set_seek_policy(&mut self, seek_policy: SeekPolicy) {
+        self.seek_policy = seek_policy;
+    }
+
+    #[inline]
+    /// Set whether writes to the partition are refused, see
+    /// [`Partition::set_read_only`]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    #[inline]
+    /// Release the partition, giving back its handle to the device
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    /// Lock the device and run a closure on it as a regular partition,
+    /// keeping the cursor it leaves behind
+    fn with_partition<R>(
+        &mut self,
+        f: impl FnOnce(&mut Partition<'_, L::IO>) -> Result<R, PartitionError<<L::IO as Io>::Error>>,
+    ) -> Result<R, PartitionError<<L::IO as Io>::Error>> {
+        let (start_pos, end_pos, pos) = (self.start_pos, self.end_pos, self.pos);
+        let (seek_policy, read_only) = (self.seek_policy, self.read_only);
+
+        let (result, pos) = self.device.lock(|io| {
+            let mut partition = Partition {
+                start_pos,
+                end_pos,
+                pos,
+                seek_policy,
+                read_only,
+                aligned_only: false,
+                device_pos: None,
+                io,
+            };
+
+            // Other partitions may have moved the device since
+            let result = match partition.sync_cursor() {
+                Ok(_) => f(&mut partition),
+                Err(error) => Err(PartitionError::Io(error)),
+            };
+
+            (result, partition.pos)
+        });
+
+        self.pos = pos;
+
+        result
+    }
+}
+
+impl<D: Deref<Target = L>, L: DeviceLock + ?Sized> Io for SharedPartition<D> {
+    type Error = PartitionError<<L::IO as Io>::Error>;
+}
+
+impl<D: Deref<Target = L>, L: DeviceLock + ?Sized> Read for SharedPartition<D>
+where
+    L::IO: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.with_partition(|partition| partition.read(buf))
+    }
+}
+
+impl<D: Deref<Target = L>, L: DeviceLock + ?Sized> Write for SharedPartition<D>
+where
+    L::IO: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.with_partition(|partition| partition.write(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.with_partition(|partition| partition.flush())
+    }
+}
+
+impl<D: Deref<Target = L>, L: DeviceLock + ?Sized> Seek for SharedPartition<D> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.with_partition(|partition| partition.seek(pos))
+    }
+}
+
+/// A read-only partition holding its own clone of the device
+///
+/// For devices that are cheap to clone, like a handle to a shared bus.
+/// Every clone of the partition has its own cursor, and the device is put
+/// back at that cursor before each read, so parallel readers don't get in
+/// each other's way.
+#[derive(Clone)]
+pub struct PartitionReader<IO> {
+    start_pos: u64,
+    end_pos: u64,
+    pos: u64,
+    seek_policy: SeekPolicy,
+    io: IO,
+}
+
+impl<IO: Read + Seek> PartitionReader<IO> {
+    /// Create a new read-only partition given the start and end position
+    ///
+    /// Fails with [`MbrError::InvalidPartitionBounds`] if the partition ends
+    /// before it starts
+    pub fn new(start_pos: u64, end_pos: u64, io: IO) -> Result<Self, MbrError<IO::Error>> {
+        if end_pos < start_pos {
+            return Err(MbrError::InvalidPartitionBounds);
+        }
+
+        Ok(Self {
+            start_pos,
+            end_pos,
+            pos: 0,
+            seek_policy: SeekPolicy::default(),
+            io,
+        })
+    }
+
+    #[inline]
+    /// Get the length of the partition in bytes
+    pub fn len(&self) -> u64 {
+        self.end_pos - self.start_pos
+    }
+
+    #[inline]
+    /// Check if the partition has a length of zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    /// Get the position of the cursor, relative to the start of the
+    /// partition
+    pub fn get_position(&self) -> u64 {
+        self.pos
+    }
+
+    #[inline]
+    /// Set what the partition does when seeking past its end
+    pub fn set_seek_policy(&mut self, seek_policy: SeekPolicy) {
+        self.seek_policy = seek_policy;
+    }
+
+    /// Run a closure on the device as a regular read-only partition,
+    /// keeping the cursor it leaves behind
+    fn with_partition<R>(
+        &mut self,
+        f: impl FnOnce(&mut Partition<'_, IO>) -> Result<R, PartitionError<IO::Error>>,
+    ) -> Result<R, PartitionError<IO::Error>> {
+        let mut partition = Partition {
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+            pos: self.pos,
+            seek_policy: self.seek_policy,
+            read_only: true,
+            aligned_only: false,
+            device_pos: None,
+            io: &mut self.io,
+        };
+
+        // Other clones of the device may have moved it since
+        partition.sync_cursor()?;
+
+        let result = f(&mut partition);
+
+        self.pos = partition.pos;
+
+        result
+    }
+}
+
+impl<IO: Io> Io for PartitionReader<IO> {
+    type Error = PartitionError<IO::Error>;
+}
+
+impl<IO: Read + Seek> Read for PartitionReader<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.with_partition(|partition| partition.read(buf))
+    }
+}
+
+impl<IO: Read + Seek> Seek for PartitionReader<IO> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.with_partition(|partition| partition.seek(pos))
+    }
+}
+
+impl<IO: Read + Seek + Clone> MBR<IO> {
+    /// Get a read-only partition with its own clone of the device, which
+    /// can itself be cloned for more independent readers
+    ///
+    /// Fails the same way as [`MBR::get_partition`]
+    pub fn get_partition_reader(
+        &self,
+        id: PartitionId,
+    ) -> Result<PartitionReader<IO>, MbrError<IO::Error>> {
+        let record = self.partitions[id as usize];
+
+        record.check_primary(self.protect_table)?;
+
+        PartitionReader::new(
+            record.get_start_pos(),
+            record.get_end_pos(),
+            self.io.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, rc::Rc};
+
+    use embedded_io::{
+        adapters::FromStd,
+        blocking::{Read, Seek, Write},
+        Io, SeekFrom,
+    };
+
+    use crate::{shared::*, PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that two partitions can be used at once without stepping on
+    /// each other
+    fn test_shared_partitions() {
+        let mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let mbr = mbr.into_shared();
+
+        let mut p1 = mbr.get_partition(PartitionId::One).unwrap();
+        let mut p2 = mbr.get_partition(PartitionId::Two).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        // Interleave the writes, each partition keeps its own cursor
+        p1.write_all(&[1, 2]).unwrap();
+        p2.write_all(&[5, 6]).unwrap();
+        p1.write_all(&[3, 4]).unwrap();
+        p2.write_all(&[7, 8]).unwrap();
+
+        p1.seek(SeekFrom::Start(0)).unwrap();
+        p1.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        assert_eq!(p2.stream_position().unwrap(), 4);
+        p2.seek(SeekFrom::Current(-4)).unwrap();
+        p2.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [5, 6, 7, 8]);
+
+        // The usual partition rules still apply
+        p1.seek(SeekFrom::Start(p1.len())).unwrap();
+        assert_eq!(p1.read(&mut buf).unwrap(), 0);
+        assert!(matches!(p1.write(&buf), Err(PartitionError::OutOfSpace)));
+
+        let start = mbr.get_record(PartitionId::Two).get_start_pos() as usize;
+        let img = mbr.into_inner().into_inner().into_inner().into_inner();
+
+        assert_eq!(img[start..start + 4], [5, 6, 7, 8]);
+    }
+
+    #[test]
+    /// Ensure that an MBR can be split into partitions that outlive it
+    fn test_split() {
+        let mbr = MBR::new(FromStd::new(Cursor::new(TEST_IMG_1.to_vec()))).unwrap();
+        let start_1 = mbr.get_record(PartitionId::One).get_start_pos() as usize;
+        let start_2 = mbr.get_record(PartitionId::Two).get_start_pos() as usize;
+        let [p1, p2, ..] = mbr.into_shared().split::<Rc<RefCell<_>>>().unwrap();
+
+        let (mut p1, mut p2) = (p1.unwrap(), p2.unwrap());
+        let mut buf: [u8; 4] = [0; 4];
+
+        p2.write_all(&[5, 6, 7, 8]).unwrap();
+        p1.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[..], TEST_IMG_1[start_1..start_1 + 4]);
+
+        drop(p1);
+
+        let device = Rc::try_unwrap(p2.into_inner()).ok().unwrap();
+        let img = device.into_inner().into_inner().into_inner();
+
+        assert_eq!(img[start_2..start_2 + 4], [5, 6, 7, 8]);
+    }
+
+    /// Device sharing one image between clones, and one cursor like a
+    /// duplicated file handle
+    #[derive(Clone)]
+    struct SharedImg(Rc<RefCell<FromStd<Cursor<Vec<u8>>>>>);
+
+    impl Io for SharedImg {
+        type Error = std::io::Error;
+    }
+
+    impl Read for SharedImg {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.0.borrow_mut().read(buf)
+        }
+    }
+
+    impl Seek for SharedImg {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.0.borrow_mut().seek(pos)
+        }
+    }
+
+    #[test]
+    /// Ensure that cloned readers keep their own cursors
+    fn test_partition_reader() {
+        let img = FromStd::new(Cursor::new(TEST_IMG_1.to_vec()));
+        let mbr = MBR::new(SharedImg(Rc::new(RefCell::new(img)))).unwrap();
+        let start = mbr.get_record(PartitionId::One).get_start_pos() as usize;
+
+        let mut reader_1 = mbr.get_partition_reader(PartitionId::One).unwrap();
+        let mut buf: [u8; 4] = [0; 4];
+
+        reader_1.read_exact(&mut buf).unwrap();
+
+        let mut reader_2 = reader_1.clone();
+        let mut other: [u8; 4] = [0; 4];
+
+        reader_2.rewind().unwrap();
+        reader_2.read_exact(&mut other).unwrap();
+        reader_1.read_exact(&mut buf).unwrap();
+
+        assert_eq!(other[..], TEST_IMG_1[start..start + 4]);
+        assert_eq!(buf[..], TEST_IMG_1[start + 4..start + 8]);
+        assert_eq!(reader_1.get_position(), 8);
+        assert_eq!(reader_2.get_position(), 4);
+    }
+}
@@ -0,0 +1,151 @@
+//! Interop with `std::io`, for host-side tools built on the crate.
+//!
+//! [`MBR::from_std`] opens any `std::io` device directly, and
+//! [`Partition::into_std`] gives a partition that implements `std::io::Read`,
+//! `Write` and `Seek`. The partition itself only implements embedded_io, as
+//! libraries accepting both, like ape-fatfs, couldn't tell which to use.
+
+use std::io;
+
+use embedded_io::{
+    adapters::FromStd,
+    blocking::{Read, Seek, Write},
+    SeekFrom,
+};
+
+use crate::{
+    error::{MbrError, PartitionError},
+    Partition, MBR,
+};
+
+impl<E: core::fmt::Debug + Send + Sync + 'static> From<PartitionError<E>> for io::Error {
+    fn from(error: PartitionError<E>) -> Self {
+        let kind = match error {
+            PartitionError::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            PartitionError::OutOfSpace => io::ErrorKind::WriteZero,
+            PartitionError::InvalidSeek
+            | PartitionError::SeekPastEnd
+            | PartitionError::Unaligned => io::ErrorKind::InvalidInput,
+            PartitionError::ReadOnly => io::ErrorKind::PermissionDenied,
+            _ => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+/// A partition implementing `std::io`, created by [`Partition::into_std`]
+pub struct StdPartition<'a, IO> {
+    partition: Partition<'a, IO>,
+}
+
+impl<'a, IO> StdPartition<'a, IO> {
+    #[inline]
+    /// Get a reference to the partition
+    pub fn get_ref(&self) -> &Partition<'a, IO> {
+        &self.partition
+    }
+
+    #[inline]
+    /// Get a mutable reference to the partition
+    pub fn get_mut(&mut self) -> &mut Partition<'a, IO> {
+        &mut self.partition
+    }
+
+    #[inline]
+    /// Get the partition back
+    pub fn into_inner(self) -> Partition<'a, IO> {
+        self.partition
+    }
+}
+
+impl<'a, IO> Partition<'a, IO> {
+    #[inline]
+    /// Wrap the partition so it implements `std::io`
+    pub fn into_std(self) -> StdPartition<'a, IO> {
+        StdPartition { partition: self }
+    }
+}
+
+impl<'a, IO: Read> io::Read for StdPartition<'a, IO>
+where
+    IO::Error: Send + Sync + 'static,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.partition.read(buf)?)
+    }
+}
+
+impl<'a, IO: Write> io::Write for StdPartition<'a, IO>
+where
+    IO::Error: Send + Sync + 'static,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.partition.write(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(self.partition.flush()?)
+    }
+}
+
+impl<'a, IO: Seek> io::Seek for StdPartition<'a, IO>
+where
+    IO::Error: Send + Sync + 'static,
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            io::SeekFrom::Start(pos) => SeekFrom::Start(pos),
+            io::SeekFrom::Current(pos) => SeekFrom::Current(pos),
+            io::SeekFrom::End(pos) => SeekFrom::End(pos),
+        };
+
+        Ok(self.partition.seek(pos)?)
+    }
+}
+
+impl<T: io::Read + io::Seek> MBR<FromStd<T>> {
+    /// Create a new MBR from anything that implements `std::io`, without
+    /// wrapping it first
+    pub fn from_std(io: T) -> Result<Self, MbrError<io::Error>> {
+        Self::new(FromStd::new(io))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+    use crate::{PartitionId, MBR};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    #[test]
+    /// Ensure that partitions can be used through std::io
+    fn test_std_io() {
+        let mut mbr = MBR::from_std(Cursor::new(TEST_IMG_1.to_vec())).unwrap();
+        let mut partition = mbr.get_partition(PartitionId::One).unwrap().into_std();
+        let len = partition.get_ref().len();
+        let mut buf = Vec::new();
+
+        partition.write_all(b"Hello World!").unwrap();
+        partition.seek(SeekFrom::Start(0)).unwrap();
+        Read::by_ref(&mut partition)
+            .take(12)
+            .read_to_end(&mut buf)
+            .unwrap();
+
+        assert_eq!(buf, b"Hello World!");
+
+        // Reading to the end stops at the end of the partition
+        partition.rewind().unwrap();
+        buf.clear();
+        partition.read_to_end(&mut buf).unwrap();
+
+        assert_eq!(buf.len() as u64, len);
+
+        let error = partition.write(b"!").unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+}
@@ -0,0 +1,125 @@
+//! Support for "superfloppy" devices, which hold a file system directly
+//! without any partition table.
+//!
+//! Many USB sticks and SD cards are formatted this way, so a device without
+//! a usable MBR can still be handed to a file system as a single partition
+//! spanning the whole device.
+
+use embedded_io::{
+    blocking::{Read, Seek},
+    SeekFrom,
+};
+
+use crate::{
+    error::MbrError, has_signature, Partition, BLOCK_SIZE, BOOT_FLAG_OFFSET, RECORDS_START,
+    RECORD_COUNT, RECORD_LEN,
+};
+
+/// Check if the first sector of a device holds a usable partition table
+///
+/// There is no partition table if the boot signature is missing, if every
+/// record is zeroed, or if any boot flag is something other than 0x00 or
+/// 0x80, which happens when the records are really boot code of a volume
+/// boot record.
+pub fn has_partition_table<IO: Read + Seek>(io: &mut IO) -> Result<bool, MbrError<IO::Error>> {
+    let mut sector: [u8; BLOCK_SIZE as usize] = [0; BLOCK_SIZE as usize];
+
+    io.seek(SeekFrom::Start(0))?;
+    io.read_exact(&mut sector)?;
+
+    let records =
+        &sector[RECORDS_START as usize..RECORDS_START as usize + RECORD_LEN * RECORD_COUNT];
+
+    let zeroed = records.iter().all(|b| *b == 0);
+    let valid_flags = records
+        .chunks_exact(RECORD_LEN)
+        .all(|r| matches!(r[BOOT_FLAG_OFFSET], 0x00 | 0x80));
+
+    Ok(has_signature(&sector) && !zeroed && valid_flags)
+}
+
+/// A device without a partition table, exposed as one partition covering
+/// the whole device
+pub struct Superfloppy<IO: Read + Seek> {
+    io: IO,
+}
+
+impl<IO: Read + Seek> Superfloppy<IO> {
+    /// Create a new superfloppy from anything that implements embedded_io
+    pub fn new(io: IO) -> Self {
+        Self { io }
+    }
+
+    /// Get the size of the underlying device in bytes
+    pub fn device_len(&mut self) -> Result<u64, MbrError<IO::Error>> {
+        Ok(self.io.seek(SeekFrom::End(0))?)
+    }
+
+    /// Get the partition covering the whole device
+    pub fn get_partition(&mut self) -> Result<Partition<'_, IO>, MbrError<IO::Error>> {
+        let end_pos = self.device_len()?;
+
+        Partition::new(0, end_pos, &mut self.io)
+    }
+
+    #[inline]
+    /// Get the underlying device back
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Read};
+
+    use crate::{ebr::tests::write_table, superfloppy::*, types::PartitionType, PartitionRecord};
+
+    #[test]
+    /// Ensure that devices without a partition table are detected, and
+    /// exposed as a single partition
+    fn test_superfloppy() {
+        // A FAT-like boot sector, with boot code where the records would be
+        let mut img = vec![0; 16 * BLOCK_SIZE as usize];
+
+        img[..3].copy_from_slice(&[0xeb, 0x3c, 0x90]);
+        img[RECORDS_START as usize..RECORDS_START as usize + 4]
+            .copy_from_slice(&[0xcd, 0x19, 0xeb, 0xfe]);
+
+        let mut io = FromStd::new(Cursor::new(img.clone()));
+
+        // Without the signature, then with it
+        assert!(!has_partition_table(&mut io).unwrap());
+
+        img[BLOCK_SIZE as usize - 2..BLOCK_SIZE as usize].copy_from_slice(&crate::SIGNATURE);
+
+        let mut io = FromStd::new(Cursor::new(img));
+
+        assert!(!has_partition_table(&mut io).unwrap());
+
+        let mut superfloppy = Superfloppy::new(io);
+        let mut partition = superfloppy.get_partition().unwrap();
+        let mut buf: [u8; 1] = [0; 1];
+
+        assert_eq!(partition.len(), 16 * BLOCK_SIZE);
+        partition.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 0xeb);
+
+        // A signed but empty table
+        let mut img = vec![0; 16 * BLOCK_SIZE as usize];
+
+        write_table(&mut img, 0, &[]);
+
+        assert!(!has_partition_table(&mut FromStd::new(Cursor::new(img.clone()))).unwrap());
+
+        write_table(
+            &mut img,
+            0,
+            &[PartitionRecord::new(1, 15, PartitionType::Fat12, false)],
+        );
+
+        assert!(has_partition_table(&mut FromStd::new(Cursor::new(img))).unwrap());
+    }
+}
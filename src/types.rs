@@ -1,19 +1,105 @@
-//! Types of partitions that are detectable through the SystemID field.
+//! Types of partitions that are detectable through the SystemID field, or
+//! through the type GUID of a GPT entry.
 //!
-//! Derived from the following:
+//! MBR types are derived from the following:
 //!  * [http://www.osdever.net/documents/partitiontypes.php](http://www.osdever.net/documents/partitiontypes.php)
 //!  * fdisk utility
 //!  
 //! Most of these are likely to never be used(eg. NovellNetware286), but shall be implemented for implementation's sake
 
-use num_enum::TryFromPrimitive;
+use crate::gpt::Guid;
 
-#[derive(Debug, Default, TryFromPrimitive, Copy, Clone, PartialEq, Eq)]
-#[repr(u8)]
-#[non_exhaustive]
-pub enum PartitionType {
-    #[default]
-    Unknown = 0x00,
+/// Declare the known MBR partition types, as an enum normally, or as
+/// constants of a raw system ID with the `minimal` feature
+macro_rules! partition_types {
+    ($($name:ident = $value:literal,)*) => {
+        /// Type of an MBR partition, given by its system ID
+        ///
+        /// System IDs that aren't known are kept in [`PartitionType::Other`],
+        /// so the original byte is never lost
+        #[cfg(not(feature = "minimal"))]
+        #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+        #[non_exhaustive]
+        pub enum PartitionType {
+            #[default]
+            Unknown,
+            $($name,)*
+            /// A system ID this crate doesn't know of
+            ///
+            /// This must never hold a known ID, as `Other(0x0b)` doesn't
+            /// compare equal to `W95Fat32`, so build it through
+            /// [`PartitionType::from_id`]
+            Other(u8),
+        }
+
+        #[cfg(not(feature = "minimal"))]
+        impl PartitionType {
+            /// Get the type of a system ID, keeping unknown IDs as
+            /// [`PartitionType::Other`]
+            pub const fn from_id(id: u8) -> Self {
+                match id {
+                    0x00 => Self::Unknown,
+                    $($value => Self::$name,)*
+                    id => Self::Other(id),
+                }
+            }
+
+            /// Get the raw system ID of the type, as written in partition
+            /// records
+            pub const fn id(self) -> u8 {
+                match self {
+                    Self::Unknown => 0x00,
+                    $(Self::$name => $value,)*
+                    Self::Other(id) => id,
+                }
+            }
+
+            #[inline]
+            /// Check if the system ID is one this crate knows of
+            pub const fn is_known(self) -> bool {
+                !matches!(self, Self::Other(_))
+            }
+        }
+
+        /// Type of an MBR partition, given by its raw system ID
+        ///
+        /// The `minimal` feature replaces the enum with this to save the
+        /// code that decodes system IDs, while keeping names for the known
+        /// types
+        #[cfg(feature = "minimal")]
+        #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+        #[repr(transparent)]
+        pub struct PartitionType(pub u8);
+
+        #[cfg(feature = "minimal")]
+        #[allow(non_upper_case_globals)]
+        impl PartitionType {
+            pub const Unknown: Self = Self(0x00);
+            $(pub const $name: Self = Self($value);)*
+
+            #[inline]
+            /// Get the type of a system ID, which keeps the raw ID as it is
+            pub const fn from_id(id: u8) -> Self {
+                Self(id)
+            }
+
+            #[inline]
+            /// Get the raw system ID of the type, as written in partition
+            /// records
+            pub const fn id(self) -> u8 {
+                self.0
+            }
+
+            #[inline]
+            /// Check if the system ID is one this crate knows of
+            pub const fn is_known(self) -> bool {
+                matches!(self.0, 0x00 $(| $value)*)
+            }
+        }
+    };
+}
+
+partition_types! {
     Fat12 = 0x01,
     XenixRoot = 0x02,
     XenixUsr = 0x03,
@@ -121,3 +207,248 @@ pub enum PartitionType {
     LanStep = 0xfe,
     Bbt = 0xff,
 }
+
+/// Coarse classification of MBR partition types
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// A FAT12, FAT16 or FAT32 file system
+    Fat,
+    /// An NTFS, exFAT or HPFS file system
+    Ntfs,
+    /// A Linux native file system
+    LinuxFs,
+    /// Swap space
+    Swap,
+    /// An extended partition holding EBRs
+    Extended,
+    /// A Linux LVM physical volume or RAID member
+    LvmRaid,
+    /// A FAT or NTFS file system hidden from DOS and Windows
+    Hidden,
+    /// An EFI system partition
+    Efi,
+    /// Anything else, including empty records
+    Other,
+}
+
+impl PartitionType {
+    /// Get the coarse kind of the type
+    pub const fn kind(self) -> PartitionKind {
+        match self {
+            PartitionType::Fat12
+            | PartitionType::Fat16Lt32
+            | PartitionType::Fat16
+            | PartitionType::W95Fat32
+            | PartitionType::W95Fat32Lba
+            | PartitionType::W95Fat16Lba => PartitionKind::Fat,
+            PartitionType::Ntfs | PartitionType::NftsVolumeSet1 | PartitionType::NftsVolumeSet2 => {
+                PartitionKind::Ntfs
+            }
+            PartitionType::Linux => PartitionKind::LinuxFs,
+            PartitionType::LinuxSwap | PartitionType::BsdiSwap => PartitionKind::Swap,
+            PartitionType::Extended
+            | PartitionType::W95ExtendedLba
+            | PartitionType::LinuxExtended => PartitionKind::Extended,
+            PartitionType::LinuxLvm | PartitionType::LinuxRaidAuto => PartitionKind::LvmRaid,
+            PartitionType::HiddenFat12
+            | PartitionType::HiddenFat16Lt32
+            | PartitionType::HiddenFat16
+            | PartitionType::HiddenNtfs
+            | PartitionType::HiddenW95Fat32
+            | PartitionType::HiddenW95Fat32Lba
+            | PartitionType::HiddenW95Fat16Lba
+            | PartitionType::HiddenWinNtfs => PartitionKind::Hidden,
+            PartitionType::EFI => PartitionKind::Efi,
+            _ => PartitionKind::Other,
+        }
+    }
+
+    #[inline]
+    /// Check if the type marks a visible FAT file system
+    pub const fn is_fat(self) -> bool {
+        matches!(self.kind(), PartitionKind::Fat)
+    }
+
+    #[inline]
+    /// Check if the type marks an extended partition holding EBRs
+    pub const fn is_extended(self) -> bool {
+        matches!(self.kind(), PartitionKind::Extended)
+    }
+
+    #[inline]
+    /// Check if the type marks a file system hidden from DOS and Windows
+    pub const fn is_hidden(self) -> bool {
+        matches!(self.kind(), PartitionKind::Hidden)
+    }
+
+    /// Get the hidden counterpart of a FAT or NTFS type, as set by boot
+    /// managers to keep DOS and Windows from seeing a partition
+    ///
+    /// Types without a hidden counterpart are returned as they are
+    pub const fn hide(self) -> Self {
+        match self.id() {
+            id @ (0x01 | 0x04 | 0x06 | 0x07 | 0x0b | 0x0c | 0x0e) => Self::from_id(id | 0x10),
+            _ => self,
+        }
+    }
+
+    /// Get the visible counterpart of a hidden FAT or NTFS type, the
+    /// opposite of [`PartitionType::hide`]
+    ///
+    /// Types without a visible counterpart are returned as they are
+    pub const fn unhide(self) -> Self {
+        match self.id() {
+            id @ (0x11 | 0x14 | 0x16 | 0x17 | 0x1b | 0x1c | 0x1e) => Self::from_id(id & !0x10),
+            _ => self,
+        }
+    }
+
+    #[inline]
+    /// Check if the type marks a BSD slice holding a disklabel
+    pub const fn is_bsd(self) -> bool {
+        matches!(
+            self,
+            PartitionType::FreeBSD | PartitionType::OpenBSD | PartitionType::NetBSD
+        )
+    }
+}
+
+impl From<u8> for PartitionType {
+    #[inline]
+    fn from(id: u8) -> Self {
+        Self::from_id(id)
+    }
+}
+
+impl From<PartitionType> for u8 {
+    #[inline]
+    fn from(partition_type: PartitionType) -> Self {
+        partition_type.id()
+    }
+}
+
+/// Well known GPT partition types
+///
+/// Anything not listed here is kept as its raw GUID
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GptPartitionType {
+    #[default]
+    Unused,
+    EfiSystem,
+    BiosBoot,
+    MicrosoftReserved,
+    MicrosoftBasicData,
+    WindowsRecovery,
+    LinuxFilesystem,
+    LinuxSwap,
+    LinuxLvm,
+    LinuxRaid,
+    LinuxHome,
+    LinuxRootX86_64,
+    LinuxRootAarch64,
+    AppleHfsPlus,
+    AppleApfs,
+    FreeBsdUfs,
+    Other(Guid),
+}
+
+/// GUIDs of the well known GPT partition types
+const GPT_PARTITION_TYPES: [(GptPartitionType, Guid); 16] = [
+    (GptPartitionType::Unused, Guid::ZERO),
+    (
+        GptPartitionType::EfiSystem,
+        Guid::from_fields(0xc12a7328, 0xf81f, 0x11d2, 0xba4b00a0c93ec93b),
+    ),
+    (
+        GptPartitionType::BiosBoot,
+        Guid::from_fields(0x21686148, 0x6449, 0x6e6f, 0x744e656564454649),
+    ),
+    (
+        GptPartitionType::MicrosoftReserved,
+        Guid::from_fields(0xe3c9e316, 0x0b5c, 0x4db8, 0x817df92df00215ae),
+    ),
+    (
+        GptPartitionType::MicrosoftBasicData,
+        Guid::from_fields(0xebd0a0a2, 0xb9e5, 0x4433, 0x87c068b6b72699c7),
+    ),
+    (
+        GptPartitionType::WindowsRecovery,
+        Guid::from_fields(0xde94bba4, 0x06d1, 0x4d40, 0xa16abfd50179d6ac),
+    ),
+    (
+        GptPartitionType::LinuxFilesystem,
+        Guid::from_fields(0x0fc63daf, 0x8483, 0x4772, 0x8e793d69d8477de4),
+    ),
+    (
+        GptPartitionType::LinuxSwap,
+        Guid::from_fields(0x0657fd6d, 0xa4ab, 0x43c4, 0x84e50933c84b4f4f),
+    ),
+    (
+        GptPartitionType::LinuxLvm,
+        Guid::from_fields(0xe6d6d379, 0xf507, 0x44c2, 0xa23c238f2a3df928),
+    ),
+    (
+        GptPartitionType::LinuxRaid,
+        Guid::from_fields(0xa19d880f, 0x05fc, 0x4d3b, 0xa006743f0f84911e),
+    ),
+    (
+        GptPartitionType::LinuxHome,
+        Guid::from_fields(0x933ac7e1, 0x2eb4, 0x4f13, 0xb8440e14e2aef915),
+    ),
+    (
+        GptPartitionType::LinuxRootX86_64,
+        Guid::from_fields(0x4f68bce3, 0xe8cd, 0x4db1, 0x96e7fbcaf984b709),
+    ),
+    (
+        GptPartitionType::LinuxRootAarch64,
+        Guid::from_fields(0xb921b045, 0x1df0, 0x41c3, 0xaf444c6f280d3fae),
+    ),
+    (
+        GptPartitionType::AppleHfsPlus,
+        Guid::from_fields(0x48465300, 0x0000, 0x11aa, 0xaa1100306543ecac),
+    ),
+    (
+        GptPartitionType::AppleApfs,
+        Guid::from_fields(0x7c3457ef, 0x0000, 0x11aa, 0xaa1100306543ecac),
+    ),
+    (
+        GptPartitionType::FreeBsdUfs,
+        Guid::from_fields(0x516e7cb6, 0x6ecf, 0x11d6, 0x8ff800022d09712b),
+    ),
+];
+
+impl GptPartitionType {
+    /// Get the partition type of a type GUID
+    pub fn from_guid(guid: Guid) -> Self {
+        GPT_PARTITION_TYPES
+            .iter()
+            .find(|(_, g)| *g == guid)
+            .map(|(t, _)| *t)
+            .unwrap_or(GptPartitionType::Other(guid))
+    }
+
+    /// Get the type GUID of the partition type
+    pub fn guid(&self) -> Guid {
+        match self {
+            GptPartitionType::Other(guid) => *guid,
+            _ => GPT_PARTITION_TYPES
+                .iter()
+                .find(|(t, _)| t == self)
+                .map(|(_, g)| *g)
+                .unwrap(),
+        }
+    }
+}
+
+impl From<Guid> for GptPartitionType {
+    fn from(guid: Guid) -> Self {
+        Self::from_guid(guid)
+    }
+}
+
+impl From<GptPartitionType> for Guid {
+    fn from(partition_type: GptPartitionType) -> Self {
+        partition_type.guid()
+    }
+}
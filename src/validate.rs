@@ -0,0 +1,75 @@
+//! Diagnostics produced by [`MBR::validate`](crate::MBR::validate).
+//!
+//! Everything here is fixed-size so that a report can be produced without an
+//! allocator.
+
+use crate::{PartitionId, RECORD_COUNT};
+
+/// Maximum number of findings a single report can hold
+///
+/// One signature finding, one boot flag finding, six possible overlapping
+/// pairs, and four each of the per-record findings.
+pub const MAX_FINDINGS: usize = 2 + 6 + RECORD_COUNT * 3;
+
+/// A single problem found while validating the MBR
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The 0x55AA boot signature is missing
+    MissingSignature,
+    /// More than one partition has the boot flag set
+    MultipleBootable,
+    /// Two partitions share at least one sector
+    Overlap(PartitionId, PartitionId),
+    /// A partition extends past the end of the device
+    PastEndOfDevice(PartitionId),
+    /// A partition has a type set but a length of zero sectors
+    ZeroLength(PartitionId),
+    /// A partition reaches past what 32 bit LBAs can address
+    ExceedsMbrAddressing(PartitionId),
+}
+
+/// List of findings produced by validating the MBR
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ValidationReport {
+    findings: [Option<Finding>; MAX_FINDINGS],
+    len: usize,
+}
+
+impl ValidationReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a finding to the report
+    pub(crate) fn push(&mut self, finding: Finding) {
+        // The capacity covers every finding validate() can produce, so
+        // this can't be hit in practice
+        if self.len < MAX_FINDINGS {
+            self.findings[self.len] = Some(finding);
+            self.len += 1;
+        }
+    }
+
+    #[inline]
+    /// Get the number of findings in the report
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    /// Check if the report is empty, meaning the MBR looks valid
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if the report contains a specific finding
+    pub fn contains(&self, finding: Finding) -> bool {
+        self.iter().any(|f| f == finding)
+    }
+
+    /// Iterate over the findings in the report
+    pub fn iter(&self) -> impl Iterator<Item = Finding> + '_ {
+        self.findings[..self.len].iter().filter_map(|f| *f)
+    }
+}
@@ -0,0 +1,244 @@
+//! Vectored IO, for drivers that can scatter and gather.
+//!
+//! embedded_io has no vectored reads or writes, so [`ReadVectored`] and
+//! [`WriteVectored`] fill the gap. Drivers that can transfer into several
+//! buffers at once, like DMA engines with linked descriptor lists, implement
+//! them, and partitions pass the buffers through in a single call.
+
+use embedded_io::blocking::{Read, Write};
+
+use crate::{error::PartitionError, Partition};
+
+/// Reading into several buffers at once
+pub trait ReadVectored: Read {
+    /// Read into the buffers in order, returning the total number of bytes
+    /// read
+    ///
+    /// Like [`Read::read`], this may read less than all of the buffers. The
+    /// default only reads into the first buffer that isn't empty.
+    fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, Self::Error> {
+        match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+            Some(buf) => self.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Writing from several buffers at once
+pub trait WriteVectored: Write {
+    /// Write the buffers in order, returning the total number of bytes
+    /// written
+    ///
+    /// Like [`Write::write`], this may write less than all of the buffers.
+    /// The default only writes the first buffer that isn't empty.
+    fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, Self::Error> {
+        match bufs.iter().find(|buf| !buf.is_empty()) {
+            Some(buf) => self.write(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+impl<T: ReadVectored + ?Sized> ReadVectored for &mut T {
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, Self::Error> {
+        T::read_vectored(self, bufs)
+    }
+}
+
+impl<T: WriteVectored + ?Sized> WriteVectored for &mut T {
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, Self::Error> {
+        T::write_vectored(self, bufs)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: std::io::Read + ?Sized> ReadVectored for embedded_io::adapters::FromStd<T> {
+    fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, Self::Error> {
+        let mut slices: Vec<_> = bufs
+            .iter_mut()
+            .map(|buf| std::io::IoSliceMut::new(buf))
+            .collect();
+
+        self.inner_mut().read_vectored(&mut slices)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: std::io::Write + ?Sized> WriteVectored for embedded_io::adapters::FromStd<T> {
+    fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, Self::Error> {
+        let slices: Vec<_> = bufs.iter().map(|buf| std::io::IoSlice::new(buf)).collect();
+
+        self.inner_mut().write_vectored(&slices)
+    }
+}
+
+/// Get how many of the buffers fit within the space left, as a whole
+fn whole_buffers(lens: impl Iterator<Item = usize>, available: u64) -> usize {
+    let mut total: u64 = 0;
+
+    lens.take_while(|len| {
+        total += *len as u64;
+        total <= available
+    })
+    .count()
+}
+
+/// Buffers reaching past the end of the partition are left for the next
+/// call, unless the first one does, which is then cut short
+impl<'a, IO: ReadVectored> ReadVectored for Partition<'a, IO> {
+    fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, Self::Error> {
+        for buf in bufs.iter() {
+            self.check_alignment(self.pos, buf.len())?;
+        }
+
+        let available = self.remaining();
+        let count = whole_buffers(bufs.iter().map(|buf| buf.len()), available);
+
+        if count == 0 {
+            return match bufs.first_mut() {
+                Some(buf) => self.read(&mut buf[..available as usize]),
+                None => Ok(0),
+            };
+        }
+
+        let device_pos = self.device_pos.take();
+        let read = self.io.read_vectored(&mut bufs[..count])?;
+
+        self.pos += read as u64;
+        self.device_pos = device_pos.map(|pos| pos + read as u64);
+
+        Ok(read)
+    }
+}
+
+/// Buffers reaching past the end of the partition are left for the next
+/// call, unless the first one does, which is then cut short
+impl<'a, IO: WriteVectored> WriteVectored for Partition<'a, IO> {
+    fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, Self::Error> {
+        if self.read_only {
+            return Err(PartitionError::ReadOnly);
+        }
+
+        for buf in bufs.iter() {
+            self.check_alignment(self.pos, buf.len())?;
+        }
+
+        let available = self.remaining();
+        let count = whole_buffers(bufs.iter().map(|buf| buf.len()), available);
+
+        if count == 0 {
+            return match bufs.first() {
+                Some(buf) => self.write(buf),
+                None => Ok(0),
+            };
+        }
+
+        let device_pos = self.device_pos.take();
+        let written = self.io.write_vectored(&bufs[..count])?;
+
+        self.pos += written as u64;
+        self.device_pos = device_pos.map(|pos| pos + written as u64);
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use embedded_io::{adapters::FromStd, blocking::Seek, Io, SeekFrom};
+
+    use crate::{vectored::*, Partition};
+
+    static TEST_IMG_1: &[u8] = include_bytes!("../resources/test1.img");
+
+    /// Device filling every buffer it is given in one call
+    struct Gather {
+        inner: FromStd<Cursor<Vec<u8>>>,
+        calls: usize,
+    }
+
+    impl Io for Gather {
+        type Error = std::io::Error;
+    }
+
+    impl Read for Gather {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for Gather {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for Gather {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl ReadVectored for Gather {
+        fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, Self::Error> {
+            self.calls += 1;
+            bufs.iter_mut()
+                .try_fold(0, |total, buf| Ok(total + self.inner.read(buf)?))
+        }
+    }
+
+    impl WriteVectored for Gather {
+        fn write_vectored(&mut self, bufs: &[&[u8]]) -> Result<usize, Self::Error> {
+            self.calls += 1;
+            bufs.iter()
+                .try_fold(0, |total, buf| Ok(total + self.inner.write(buf)?))
+        }
+    }
+
+    #[test]
+    /// Ensure that buffers are passed to the device together, and that they
+    /// stop at the end of the partition
+    fn test_vectored() {
+        let mut io = Gather {
+            inner: FromStd::new(Cursor::new(TEST_IMG_1.to_vec())),
+            calls: 0,
+        };
+        let mut partition = Partition::new(512, 1024, &mut io).unwrap();
+
+        let written = partition
+            .write_vectored(&[b"Hello", b" ", b"World!"])
+            .unwrap();
+
+        assert_eq!(written, 12);
+        assert_eq!(partition.get_position(), 12);
+
+        let mut hello: [u8; 5] = [0; 5];
+        let mut world: [u8; 7] = [0; 7];
+
+        partition.rewind().unwrap();
+        partition
+            .read_vectored(&mut [&mut hello, &mut world])
+            .unwrap();
+
+        assert_eq!(&hello, b"Hello");
+        assert_eq!(&world, b" World!");
+
+        // Only the buffers that fit are read, and the rest is left
+        let mut a: [u8; 8] = [0; 8];
+        let mut b: [u8; 16] = [0; 16];
+
+        partition.seek(SeekFrom::End(-20)).unwrap();
+
+        assert_eq!(partition.read_vectored(&mut [&mut a, &mut b]).unwrap(), 8);
+        assert_eq!(partition.read_vectored(&mut [&mut b]).unwrap(), 12);
+        assert_eq!(partition.into_inner().calls, 3);
+    }
+}